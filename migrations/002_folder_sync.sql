@@ -0,0 +1,22 @@
+-- FolderSync: calendar folder metadata, per-device hierarchy sync keys and the
+-- folder list each device was last sent, so later syncs can return deltas.
+ALTER TABLE calendars ADD COLUMN display_name TEXT NOT NULL DEFAULT '';
+ALTER TABLE calendars ADD COLUMN folder_type INTEGER NOT NULL DEFAULT 13;
+
+CREATE TABLE IF NOT EXISTS folder_sync_state (
+  owner TEXT NOT NULL,
+  device_id TEXT NOT NULL,
+  sync_key TEXT NOT NULL,
+  last_sync_ts INTEGER,
+  PRIMARY KEY(owner, device_id)
+);
+
+CREATE TABLE IF NOT EXISTS folder_snapshot (
+  owner TEXT NOT NULL,
+  device_id TEXT NOT NULL,
+  collection_id TEXT NOT NULL,
+  caldav_href TEXT NOT NULL,
+  display_name TEXT NOT NULL,
+  folder_type INTEGER NOT NULL,
+  PRIMARY KEY(owner, device_id, collection_id)
+);
@@ -0,0 +1,14 @@
+-- Changes found by a Sync diff that did not fit in the client's WindowSize.
+-- Later Syncs drain this queue before diffing the collection again.
+CREATE TABLE IF NOT EXISTS sync_pending (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  owner TEXT NOT NULL,
+  collection_id TEXT NOT NULL,
+  kind TEXT NOT NULL,
+  server_id TEXT NOT NULL,
+  resource_href TEXT NOT NULL,
+  etag TEXT,
+  calendar_data TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_sync_pending_collection ON sync_pending(owner, collection_id, id);
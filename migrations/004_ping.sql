@@ -0,0 +1,9 @@
+-- Ping: the last HeartbeatInterval and folder list each device asked to watch,
+-- reused when a Ping arrives without a body.
+CREATE TABLE IF NOT EXISTS ping_state (
+  owner TEXT NOT NULL,
+  device_id TEXT NOT NULL,
+  heartbeat_interval INTEGER NOT NULL,
+  folder_ids TEXT NOT NULL,
+  PRIMARY KEY(owner, device_id)
+);
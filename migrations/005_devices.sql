@@ -0,0 +1,10 @@
+-- Provision: policy keys per device. The pending key is the temporary key sent
+-- in the first Provision response; it becomes the policy key once acknowledged.
+CREATE TABLE IF NOT EXISTS devices (
+  owner TEXT NOT NULL,
+  device_id TEXT NOT NULL,
+  policy_key INTEGER,
+  pending_policy_key INTEGER,
+  provisioned_ts INTEGER,
+  PRIMARY KEY(owner, device_id)
+);
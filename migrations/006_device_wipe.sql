@@ -0,0 +1,5 @@
+-- Remote wipe: every device that connects gets a row, and its status moves
+-- from active to a pending wipe, then to blocked once the wipe is acknowledged.
+ALTER TABLE devices ADD COLUMN status TEXT NOT NULL DEFAULT 'active';
+ALTER TABLE devices ADD COLUMN wipe_requested_ts INTEGER;
+ALTER TABLE devices ADD COLUMN wipe_acked_ts INTEGER;
@@ -0,0 +1,79 @@
+-- Device registry: what each device reports about itself and when it was last
+-- seen. Devices that synced before the registry existed are added from
+-- folder_sync_state.
+ALTER TABLE devices ADD COLUMN device_type TEXT NOT NULL DEFAULT '';
+ALTER TABLE devices ADD COLUMN user_agent TEXT NOT NULL DEFAULT '';
+ALTER TABLE devices ADD COLUMN first_seen_ts INTEGER;
+ALTER TABLE devices ADD COLUMN last_seen_ts INTEGER;
+
+INSERT OR IGNORE INTO devices (owner, device_id) SELECT owner, device_id FROM folder_sync_state;
+UPDATE devices SET
+  first_seen_ts = COALESCE((SELECT last_sync_ts FROM folder_sync_state f WHERE f.owner = devices.owner AND f.device_id = devices.device_id), strftime('%s','now')),
+  last_seen_ts = COALESCE((SELECT last_sync_ts FROM folder_sync_state f WHERE f.owner = devices.owner AND f.device_id = devices.device_id), strftime('%s','now'));
+
+-- Sync state, queued changes and the record of what the client holds are kept
+-- per device. What was shared by an owner's devices is copied to each device
+-- that has synced its folders, so no device has to resync.
+CREATE TABLE sync_state_new (
+  owner TEXT NOT NULL,
+  device_id TEXT NOT NULL,
+  collection_id TEXT NOT NULL,
+  sync_key TEXT NOT NULL,
+  last_sync_token TEXT,
+  last_sync_ts INTEGER,
+  PRIMARY KEY(owner, device_id, collection_id)
+);
+INSERT INTO sync_state_new (owner, device_id, collection_id, sync_key, last_sync_token, last_sync_ts)
+  SELECT s.owner, f.device_id, s.collection_id, s.sync_key, s.last_sync_token, s.last_sync_ts
+  FROM sync_state s JOIN folder_sync_state f ON f.owner = s.owner;
+DROP TABLE sync_state;
+ALTER TABLE sync_state_new RENAME TO sync_state;
+
+CREATE TABLE sync_pending_new (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  owner TEXT NOT NULL,
+  device_id TEXT NOT NULL,
+  collection_id TEXT NOT NULL,
+  kind TEXT NOT NULL,
+  server_id TEXT NOT NULL,
+  resource_href TEXT NOT NULL,
+  etag TEXT,
+  calendar_data TEXT
+);
+INSERT INTO sync_pending_new (owner, device_id, collection_id, kind, server_id, resource_href, etag, calendar_data)
+  SELECT p.owner, f.device_id, p.collection_id, p.kind, p.server_id, p.resource_href, p.etag, p.calendar_data
+  FROM sync_pending p JOIN folder_sync_state f ON f.owner = p.owner
+  ORDER BY p.id;
+DROP TABLE sync_pending;
+ALTER TABLE sync_pending_new RENAME TO sync_pending;
+CREATE INDEX IF NOT EXISTS idx_sync_pending_collection ON sync_pending(owner, device_id, collection_id, id);
+
+-- Items written over EWS, which still carry the placeholder UID, belong to no
+-- device and keep an empty device_id.
+CREATE TABLE items_map_new (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  owner TEXT NOT NULL,
+  device_id TEXT NOT NULL,
+  caldav_href TEXT NOT NULL,
+  resource_href TEXT NOT NULL,
+  server_id TEXT NOT NULL,
+  item_id TEXT,
+  change_key TEXT,
+  uid TEXT,
+  etag TEXT,
+  sequence INTEGER DEFAULT 0,
+  last_sync INTEGER,
+  UNIQUE(owner, device_id, server_id)
+);
+INSERT INTO items_map_new (owner, device_id, caldav_href, resource_href, server_id, item_id, change_key, uid, etag, sequence, last_sync)
+  SELECT i.owner, f.device_id, i.caldav_href, i.resource_href, i.server_id, i.item_id, i.change_key, i.uid, i.etag, i.sequence, i.last_sync
+  FROM items_map i JOIN folder_sync_state f ON f.owner = i.owner
+  WHERE i.uid IS NOT 'uid-placeholder'
+  ORDER BY i.id;
+INSERT INTO items_map_new (owner, device_id, caldav_href, resource_href, server_id, item_id, change_key, uid, etag, sequence, last_sync)
+  SELECT owner, '', caldav_href, resource_href, server_id, item_id, change_key, uid, etag, sequence, last_sync
+  FROM items_map
+  WHERE uid IS 'uid-placeholder';
+DROP TABLE items_map;
+ALTER TABLE items_map_new RENAME TO items_map;
+CREATE INDEX IF NOT EXISTS idx_items_map_owner ON items_map(owner, device_id);
@@ -0,0 +1,3 @@
+-- Sync: the FilterType each device last asked for per collection, reused when a
+-- Sync or GetItemEstimate arrives without Options.
+ALTER TABLE sync_state ADD COLUMN filter_type INTEGER;
@@ -0,0 +1,9 @@
+-- Settings DeviceInformation: what a device reports about its hardware and
+-- software during setup.
+ALTER TABLE devices ADD COLUMN model TEXT;
+ALTER TABLE devices ADD COLUMN imei TEXT;
+ALTER TABLE devices ADD COLUMN friendly_name TEXT;
+ALTER TABLE devices ADD COLUMN os TEXT;
+ALTER TABLE devices ADD COLUMN os_language TEXT;
+ALTER TABLE devices ADD COLUMN phone_number TEXT;
+ALTER TABLE devices ADD COLUMN mobile_operator TEXT;
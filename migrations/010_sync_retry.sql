@@ -0,0 +1,5 @@
+-- Sync: the key each collection's last response answered and that response
+-- (WBXML), so a client retrying after a lost response gets it again instead of
+-- having its commands applied twice.
+ALTER TABLE sync_state ADD COLUMN previous_sync_key TEXT;
+ALTER TABLE sync_state ADD COLUMN last_response BLOB;
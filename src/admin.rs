@@ -0,0 +1,58 @@
+use crate::models::DeviceStatus;
+use crate::storage::Storage;
+use anyhow::{Result, bail};
+
+const USAGE: &str = "usage: exchange_gateway [--config <path>] [devices <owner> | wipe <owner> <device_id> | account-wipe <owner> <device_id> | unblock <owner> <device_id>]";
+
+/// Run an administrative subcommand against the state database and exit.
+pub async fn run(storage: &Storage, args: &[String]) -> Result<()> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["devices", owner] => {
+            let devices = storage.list_devices(owner).await?;
+            if devices.is_empty() {
+                println!("no devices for {}", owner);
+            }
+            let ts = |t: Option<i64>| t.map(|t| t.to_string()).unwrap_or_else(|| "-".into());
+            let text = |t: &Option<String>| t.clone().unwrap_or_else(|| "-".into());
+            for d in devices {
+                println!(
+                    "{}\t{}\t{}\tfirst_seen={}\tlast_seen={}\tpolicy_key={}\twipe_requested={}\twipe_acked={}\t{}",
+                    d.device_id,
+                    d.device_type,
+                    d.status.as_str(),
+                    ts(d.first_seen_ts),
+                    ts(d.last_seen_ts),
+                    d.policy_key.map(|k| k.to_string()).unwrap_or_else(|| "-".into()),
+                    ts(d.wipe_requested_ts),
+                    ts(d.wipe_acked_ts),
+                    d.user_agent,
+                );
+                let info = &d.information;
+                println!(
+                    "\tmodel={}\tos={}\tos_language={}\tfriendly_name={}\timei={}\tphone={}\toperator={}",
+                    text(&info.model),
+                    text(&info.os),
+                    text(&info.os_language),
+                    text(&info.friendly_name),
+                    text(&info.imei),
+                    text(&info.phone_number),
+                    text(&info.mobile_operator),
+                );
+            }
+        }
+        ["wipe", owner, device] => set_status(storage, owner, device, DeviceStatus::WipePending).await?,
+        ["account-wipe", owner, device] => set_status(storage, owner, device, DeviceStatus::AccountWipePending).await?,
+        ["unblock", owner, device] => set_status(storage, owner, device, DeviceStatus::Active).await?,
+        _ => bail!(USAGE),
+    }
+    Ok(())
+}
+
+async fn set_status(storage: &Storage, owner: &str, device: &str, status: DeviceStatus) -> Result<()> {
+    if !storage.set_device_status(owner, device, status).await? {
+        bail!("unknown device {} for {}", device, owner);
+    }
+    println!("{} of {} is now {}", device, owner, status.as_str());
+    Ok(())
+}
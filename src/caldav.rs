@@ -1,6 +1,10 @@
 use crate::config::Config;
+use crate::wbxml::escape_xml;
 use anyhow::Result;
-use reqwest::Client;
+use quick_xml::events::Event;
+use quick_xml::reader::NsReader;
+use reqwest::{Client, StatusCode, Url};
+use std::collections::{HashMap, HashSet};
 
 pub struct CaldavClient {
     base: String,
@@ -13,9 +17,13 @@ impl CaldavClient {
         CaldavClient { base: cfg.caldav_base.clone(), client }
     }
 
+    /// Stalwart calendar home: `{base}/cal/{username}/`.
+    fn calendar_home(&self, username: &str) -> String {
+        format!("{}/cal/{}/", self.base.trim_end_matches('/'), username)
+    }
+
     pub async fn find_user_calendars(&self, username: &str, password: &str) -> Result<Vec<String>> {
-        // Convention: Stalwart calendar home at {base}/{username}/calendar/
-        let url = format!("{}cal/{}", self.base.trim_end_matches('/'), username);
+        let url = self.calendar_home(username);
         let resp = self.client.get(&url).basic_auth(username, Some(password)).send().await?;
         if resp.status().is_success() {
             Ok(vec![url])
@@ -24,7 +32,113 @@ impl CaldavClient {
         }
     }
 
-    pub async fn query_events(&self, collection_href: &str, start: &str, end: &str, username: &str, password: &str) -> Result<String> {
+    /// Check a user's credentials with a PROPFIND of their calendar home, which
+    /// only they may read. Ok(false) when the server rejects them.
+    pub async fn authenticate(&self, username: &str, password: &str) -> Result<bool> {
+        let body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:propfind xmlns:D="DAV:">
+  <D:prop>
+    <D:current-user-principal/>
+  </D:prop>
+</D:propfind>"#;
+        let url = self.calendar_home(username);
+        let resp = self.client.request(reqwest::Method::from_bytes(b"PROPFIND")?, &url)
+            .basic_auth(username, Some(password))
+            .header("Depth", "0")
+            .header("Content-Type", "application/xml")
+            .body(body)
+            .send().await?;
+        match resp.status() {
+            s if s.is_success() => Ok(true),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::NOT_FOUND => Ok(false),
+            s => Err(anyhow::anyhow!("PROPFIND {} failed: {}", url, s)),
+        }
+    }
+
+    /// Enumerate the event calendars in the user's calendar home (PROPFIND Depth 1).
+    /// Collections that only hold tasks or journals are skipped.
+    pub async fn list_calendars(&self, username: &str, password: &str) -> Result<Vec<CalendarInfo>> {
+        let home = self.calendar_home(username);
+        let body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:propfind xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop>
+    <D:displayname/>
+    <D:resourcetype/>
+    <C:supported-calendar-component-set/>
+  </D:prop>
+</D:propfind>"#;
+        let resp = self.client.request(reqwest::Method::from_bytes(b"PROPFIND")?, &home)
+            .basic_auth(username, Some(password))
+            .header("Depth", "1")
+            .header("Content-Type", "application/xml")
+            .body(body)
+            .send().await?;
+        if !resp.status().is_success() {
+            return Err(anyhow::anyhow!("failed to list calendars: {}", resp.status()));
+        }
+        let home_url = Url::parse(&home)?;
+        let mut calendars = Vec::new();
+        for r in parse_multistatus(&resp.text().await?)? {
+            if !r.is_event_calendar() {
+                continue;
+            }
+            let href = home_url.join(&r.href)?.to_string();
+            let fallback = href.trim_end_matches('/').rsplit('/').next().unwrap_or_default().to_string();
+            let display_name = r.props.get("displayname").filter(|n| !n.trim().is_empty()).cloned().unwrap_or(fallback);
+            calendars.push(CalendarInfo { href, display_name });
+        }
+        Ok(calendars)
+    }
+
+    /// Change tokens of the event calendars in the user's home, keyed by collection
+    /// URL: the DAV sync-token, or the CalendarServer ctag when there is none. A
+    /// token changes whenever anything in the collection does.
+    pub async fn collection_tokens(&self, username: &str, password: &str) -> Result<HashMap<String, Option<String>>> {
+        self.propfind_tokens(&self.calendar_home(username), "1", username, password).await
+    }
+
+    /// Change token of one calendar collection, as in `collection_tokens`.
+    pub async fn collection_token(&self, collection_href: &str, username: &str, password: &str) -> Result<Option<String>> {
+        let tokens = self.propfind_tokens(collection_href, "0", username, password).await?;
+        Ok(tokens.into_values().next().flatten())
+    }
+
+    async fn propfind_tokens(&self, url: &str, depth: &str, username: &str, password: &str) -> Result<HashMap<String, Option<String>>> {
+        let body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:propfind xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav" xmlns:CS="http://calendarserver.org/ns/">
+  <D:prop>
+    <D:resourcetype/>
+    <C:supported-calendar-component-set/>
+    <D:sync-token/>
+    <CS:getctag/>
+  </D:prop>
+</D:propfind>"#;
+        let resp = self.client.request(reqwest::Method::from_bytes(b"PROPFIND")?, url)
+            .basic_auth(username, Some(password))
+            .header("Depth", depth)
+            .header("Content-Type", "application/xml")
+            .body(body)
+            .send().await?;
+        if !resp.status().is_success() {
+            return Err(anyhow::anyhow!("failed to read collection tokens: {}", resp.status()));
+        }
+        let base = Url::parse(url)?;
+        let mut tokens = HashMap::new();
+        for r in parse_multistatus(&resp.text().await?)? {
+            if !r.is_event_calendar() {
+                continue;
+            }
+            let token = r.props.get("sync-token").or_else(|| r.props.get("getctag"))
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty());
+            tokens.insert(base.join(&r.href)?.to_string(), token);
+        }
+        Ok(tokens)
+    }
+
+    /// Events overlapping a time range; without a start the range is open into the past.
+    pub async fn query_events(&self, collection_href: &str, start: Option<&str>, end: &str, username: &str, password: &str) -> Result<String> {
+        let start = start.map(|s| format!(r#"start="{}" "#, s)).unwrap_or_default();
         let report = format!(r#"<?xml version="1.0" encoding="utf-8" ?>
 <C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
   <D:prop>
@@ -34,7 +148,7 @@ impl CaldavClient {
   <C:filter>
     <C:comp-filter name="VCALENDAR">
       <C:comp-filter name="VEVENT">
-        <C:time-range start="{start}" end="{end}" />
+        <C:time-range {start}end="{end}" />
       </C:comp-filter>
     </C:comp-filter>
   </C:filter>
@@ -45,25 +159,414 @@ impl CaldavClient {
             .header("Content-Type","application/xml")
             .body(report)
             .send().await?;
+        if !resp.status().is_success() {
+            return Err(anyhow::anyhow!("calendar query failed: {}", resp.status()));
+        }
         let txt = resp.text().await?;
+        if txt.trim().is_empty() {
+            return Err(anyhow::anyhow!("calendar query returned an empty body"));
+        }
         Ok(txt)
     }
 
+    /// Events whose `property` (SUMMARY, LOCATION, ...) contains `text`, matched
+    /// case-insensitively and optionally limited to a time range.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn search_events(&self, collection_href: &str, property: &str, text: &str, start: Option<&str>, end: Option<&str>, username: &str, password: &str) -> Result<String> {
+        let time_range = match (start, end) {
+            (None, None) => String::new(),
+            (start, end) => format!(
+                "\n        <C:time-range {}{}/>",
+                start.map(|s| format!(r#"start="{}" "#, s)).unwrap_or_default(),
+                end.map(|e| format!(r#"end="{}" "#, e)).unwrap_or_default(),
+            ),
+        };
+        let report = format!(r#"<?xml version="1.0" encoding="utf-8" ?>
+<C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop>
+    <D:getetag/>
+    <C:calendar-data/>
+  </D:prop>
+  <C:filter>
+    <C:comp-filter name="VCALENDAR">
+      <C:comp-filter name="VEVENT">{time_range}
+        <C:prop-filter name="{property}">
+          <C:text-match>{text}</C:text-match>
+        </C:prop-filter>
+      </C:comp-filter>
+    </C:comp-filter>
+  </C:filter>
+</C:calendar-query>"#, time_range = time_range, property = property, text = escape_xml(text));
+
+        let resp = self.client.request(reqwest::Method::from_bytes(b"REPORT")?, collection_href)
+            .basic_auth(username, Some(password))
+            .header("Depth", "1")
+            .header("Content-Type", "application/xml")
+            .body(report)
+            .send().await?;
+        if !resp.status().is_success() {
+            return Err(anyhow::anyhow!("calendar search failed: {}", resp.status()));
+        }
+        Ok(resp.text().await?)
+    }
+
+    /// Free/busy time of another account between `start` and `end` as a
+    /// VFREEBUSY (RFC 4791 `free-busy-query` over their whole calendar home).
+    pub async fn free_busy(&self, account: &str, start: &str, end: &str, username: &str, password: &str) -> Result<String> {
+        let report = format!(r#"<?xml version="1.0" encoding="utf-8" ?>
+<C:free-busy-query xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <C:time-range start="{start}" end="{end}"/>
+</C:free-busy-query>"#, start = start, end = end);
+        let resp = self.client.request(reqwest::Method::from_bytes(b"REPORT")?, self.calendar_home(account))
+            .basic_auth(username, Some(password))
+            .header("Depth", "infinity")
+            .header("Content-Type", "application/xml")
+            .body(report)
+            .send().await?;
+        if !resp.status().is_success() {
+            return Err(anyhow::anyhow!("free/busy query for {} failed: {}", account, resp.status()));
+        }
+        Ok(resp.text().await?)
+    }
+
+    /// URLs of all resources in a collection (PROPFIND Depth 1), regardless of
+    /// their time range.
+    pub async fn list_resources(&self, collection_href: &str, username: &str, password: &str) -> Result<HashSet<String>> {
+        let body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:propfind xmlns:D="DAV:">
+  <D:prop>
+    <D:getetag/>
+  </D:prop>
+</D:propfind>"#;
+        let resp = self.client.request(reqwest::Method::from_bytes(b"PROPFIND")?, collection_href)
+            .basic_auth(username, Some(password))
+            .header("Depth", "1")
+            .header("Content-Type", "application/xml")
+            .body(body)
+            .send().await?;
+        if !resp.status().is_success() {
+            return Err(anyhow::anyhow!("failed to list resources: {}", resp.status()));
+        }
+        let base = Url::parse(collection_href)?;
+        let mut hrefs = HashSet::new();
+        for r in parse_multistatus(&resp.text().await?)? {
+            hrefs.insert(base.join(&r.href)?.to_string());
+        }
+        Ok(hrefs)
+    }
+
     pub async fn get_event(&self, resource_href: &str, username: &str, password: &str) -> Result<String> {
         let resp = self.client.get(resource_href).basic_auth(username, Some(password)).send().await?;
+        if !resp.status().is_success() {
+            return Err(anyhow::anyhow!("get failed: {}", resp.status()));
+        }
         let txt = resp.text().await?;
         Ok(txt)
     }
 
+    /// Download a linked attachment. Only links into the CalDAV server are
+    /// followed, since they are sent the user's credentials.
+    pub async fn get_attachment(&self, url: &str, username: &str, password: &str) -> Result<Vec<u8>> {
+        let target = Url::parse(url)?;
+        let base = Url::parse(&self.base)?;
+        if target.origin() != base.origin() {
+            return Err(anyhow::anyhow!("attachment {} is not on the CalDAV server", url));
+        }
+        let resp = self.client.get(target).basic_auth(username, Some(password)).send().await?;
+        if !resp.status().is_success() {
+            return Err(anyhow::anyhow!("attachment download failed: {}", resp.status()));
+        }
+        Ok(resp.bytes().await?.to_vec())
+    }
+
     pub async fn put_event(&self, collection_href: &str, resource_name: &str, ics: &str, username: &str, password: &str) -> Result<String> {
         let url = format!("{}/{}", collection_href.trim_end_matches('/'), resource_name);
-        let resp = self.client.put(&url).basic_auth(username, Some(password)).body(ics.to_string()).header("Content-Type","text/calendar; charset=utf-8").send().await?;
+        self.put(&url, ics, true, username, password).await
+    }
+
+    /// Store an attendee's copy of an event without the server sending the
+    /// organizer an implicit iTIP REPLY (RFC 6638 `Schedule-Reply: F`), for when
+    /// the reply goes out through the outbox instead.
+    pub async fn put_event_without_reply(&self, resource_href: &str, ics: &str, username: &str, password: &str) -> Result<String> {
+        self.put(resource_href, ics, false, username, password).await
+    }
+
+    async fn put(&self, url: &str, ics: &str, schedule_reply: bool, username: &str, password: &str) -> Result<String> {
+        let mut req = self.client.put(url).basic_auth(username, Some(password)).body(ics.to_string()).header("Content-Type","text/calendar; charset=utf-8");
+        if !schedule_reply {
+            req = req.header("Schedule-Reply", "F");
+        }
+        let resp = req.send().await?;
         let etag = resp.headers().get("ETag").map(|v| v.to_str().unwrap_or("").to_string()).unwrap_or_default();
         if resp.status().is_success() { Ok(etag) } else { Err(anyhow::anyhow!("put failed: {}", resp.status())) }
     }
 
+    /// The user's scheduling outbox (RFC 6638): `schedule-outbox-URL` of the
+    /// calendar home or, when the home does not carry it, of the user's principal.
+    pub async fn schedule_outbox(&self, username: &str, password: &str) -> Result<Option<String>> {
+        let body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:propfind xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop>
+    <D:current-user-principal/>
+    <C:schedule-outbox-URL/>
+  </D:prop>
+</D:propfind>"#;
+        let home = Url::parse(&self.calendar_home(username))?;
+        let props = self.propfind_props(home.as_str(), body, username, password).await?;
+        if let Some(outbox) = props.get("schedule-outbox-URL").map(|o| o.trim()).filter(|o| !o.is_empty()) {
+            return Ok(Some(home.join(outbox)?.to_string()));
+        }
+        let Some(principal) = principal_url(&home, &props)? else {
+            return Ok(None);
+        };
+        let props = self.propfind_props(principal.as_str(), body, username, password).await?;
+        match props.get("schedule-outbox-URL").map(|o| o.trim()).filter(|o| !o.is_empty()) {
+            Some(outbox) => Ok(Some(principal.join(outbox)?.to_string())),
+            None => Ok(None),
+        }
+    }
+
+    /// The user's display name and email addresses: `displayname` and the
+    /// `mailto:` entries of `calendar-user-address-set` (RFC 6638) on their
+    /// principal.
+    pub async fn user_identity(&self, username: &str, password: &str) -> Result<(Option<String>, Vec<String>)> {
+        let body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:propfind xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop>
+    <D:current-user-principal/>
+    <D:displayname/>
+    <C:calendar-user-address-set/>
+  </D:prop>
+</D:propfind>"#;
+        let home = Url::parse(&self.calendar_home(username))?;
+        let props = self.propfind_props(home.as_str(), body, username, password).await?;
+        let Some(principal) = principal_url(&home, &props)? else {
+            return Ok((None, Vec::new()));
+        };
+        let props = self.propfind_props(principal.as_str(), body, username, password).await?;
+        let display_name = props.get("displayname").map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+        Ok((display_name, mailto_addresses(&props)))
+    }
+
+    /// Principals whose display name or calendar user address contains `query`
+    /// (RFC 3744 `principal-property-search`), searched in the principal
+    /// collection the user's principal names, or else its parent collection.
+    pub async fn search_principals(&self, query: &str, username: &str, password: &str) -> Result<Vec<PrincipalInfo>> {
+        let body = r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:propfind xmlns:D="DAV:">
+  <D:prop>
+    <D:current-user-principal/>
+    <D:principal-collection-set/>
+  </D:prop>
+</D:propfind>"#;
+        let home = Url::parse(&self.calendar_home(username))?;
+        let props = self.propfind_props(home.as_str(), body, username, password).await?;
+        let collection = match props.get("principal-collection-set").and_then(|set| set.lines().map(str::trim).find(|c| !c.is_empty())) {
+            Some(collection) => home.join(collection)?,
+            None => match principal_url(&home, &props)? {
+                Some(principal) => principal.join("..")?,
+                None => return Ok(Vec::new()),
+            },
+        };
+        let query = escape_xml(query);
+        let report = format!(r#"<?xml version="1.0" encoding="utf-8" ?>
+<D:principal-property-search xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav" test="anyof">
+  <D:property-search>
+    <D:prop><D:displayname/></D:prop>
+    <D:match>{query}</D:match>
+  </D:property-search>
+  <D:property-search>
+    <D:prop><C:calendar-user-address-set/></D:prop>
+    <D:match>{query}</D:match>
+  </D:property-search>
+  <D:prop>
+    <D:displayname/>
+    <C:calendar-user-address-set/>
+  </D:prop>
+</D:principal-property-search>"#, query = query);
+        let resp = self.client.request(reqwest::Method::from_bytes(b"REPORT")?, collection.as_str())
+            .basic_auth(username, Some(password))
+            .header("Depth", "0")
+            .header("Content-Type", "application/xml")
+            .body(report)
+            .send().await?;
+        if !resp.status().is_success() {
+            return Err(anyhow::anyhow!("principal search failed: {}", resp.status()));
+        }
+        let mut principals = Vec::new();
+        for r in parse_multistatus(&resp.text().await?)? {
+            let href = collection.join(&r.href)?;
+            principals.push(PrincipalInfo {
+                name: href.path_segments().and_then(|mut s| s.rfind(|s| !s.is_empty())).map(String::from),
+                display_name: r.props.get("displayname").map(|n| n.trim().to_string()).filter(|n| !n.is_empty()),
+                addresses: mailto_addresses(&r.props),
+            });
+        }
+        Ok(principals)
+    }
+
+    /// Properties of one resource (PROPFIND Depth 0).
+    async fn propfind_props(&self, url: &str, body: &str, username: &str, password: &str) -> Result<HashMap<String, String>> {
+        let resp = self.client.request(reqwest::Method::from_bytes(b"PROPFIND")?, url)
+            .basic_auth(username, Some(password))
+            .header("Depth", "0")
+            .header("Content-Type", "application/xml")
+            .body(body.to_string())
+            .send().await?;
+        if !resp.status().is_success() {
+            return Err(anyhow::anyhow!("PROPFIND {} failed: {}", url, resp.status()));
+        }
+        Ok(parse_multistatus(&resp.text().await?)?.into_iter().next().map(|r| r.props).unwrap_or_default())
+    }
+
+    /// POST an iTIP REPLY from an attendee to the organizer through the outbox.
+    pub async fn send_reply(&self, outbox: &str, attendee: &str, organizer: &str, itip: &str, username: &str, password: &str) -> Result<()> {
+        let resp = self.client.post(outbox)
+            .basic_auth(username, Some(password))
+            .header("Content-Type", "text/calendar; charset=utf-8; method=REPLY")
+            .header("Originator", format!("mailto:{}", attendee))
+            .header("Recipient", format!("mailto:{}", organizer))
+            .body(itip.to_string())
+            .send().await?;
+        if resp.status().is_success() { Ok(()) } else { Err(anyhow::anyhow!("outbox POST failed: {}", resp.status())) }
+    }
+
     pub async fn delete_event(&self, resource_href: &str, username: &str, password: &str) -> Result<()> {
         let resp = self.client.delete(resource_href).basic_auth(username, Some(password)).send().await?;
         if resp.status().is_success() || resp.status().as_u16() == 204 { Ok(()) } else { Err(anyhow::anyhow!("delete failed: {}", resp.status())) }
     }
 }
+
+/// The `current-user-principal` among PROPFIND properties, resolved against `base`.
+fn principal_url(base: &Url, props: &HashMap<String, String>) -> Result<Option<Url>> {
+    match props.get("current-user-principal").map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        Some(principal) => Ok(Some(base.join(principal)?)),
+        None => Ok(None),
+    }
+}
+
+/// The `mailto:` entries of a `calendar-user-address-set` among PROPFIND
+/// properties, without the scheme.
+fn mailto_addresses(props: &HashMap<String, String>) -> Vec<String> {
+    props.get("calendar-user-address-set").into_iter()
+        .flat_map(|set| set.lines())
+        .filter_map(|a| a.trim().get(..7).filter(|p| p.eq_ignore_ascii_case("mailto:")).map(|_| a.trim()[7..].to_string()))
+        .collect()
+}
+
+/// A principal found by `search_principals`.
+#[derive(Clone, Debug)]
+pub struct PrincipalInfo {
+    /// Last segment of the principal URL, the account name on Stalwart.
+    pub name: Option<String>,
+    pub display_name: Option<String>,
+    pub addresses: Vec<String>,
+}
+
+/// A calendar collection found in the user's calendar home.
+#[derive(Clone, Debug)]
+pub struct CalendarInfo {
+    pub href: String,
+    pub display_name: String,
+}
+
+/// One `<D:response>` of a WebDAV multistatus body.
+#[derive(Clone, Debug, Default)]
+pub struct DavResponse {
+    pub href: String,
+    /// Text of the properties returned with a 200 status, keyed by local name
+    /// (`displayname`, `getetag`, `calendar-data`, ...). A property holding
+    /// `<D:href>`s has them as its text, one per line.
+    pub props: HashMap<String, String>,
+    /// Local names of the `<D:resourcetype>` children, e.g. `collection`, `calendar`.
+    pub resource_types: Vec<String>,
+    /// Component names from `<C:supported-calendar-component-set>`.
+    pub components: Vec<String>,
+}
+
+impl DavResponse {
+    /// A calendar collection that can hold events; collections that declare no
+    /// component set accept everything.
+    fn is_event_calendar(&self) -> bool {
+        self.resource_types.iter().any(|t| t == "calendar")
+            && (self.components.is_empty() || self.components.iter().any(|c| c == "VEVENT"))
+    }
+}
+
+/// Parse a WebDAV multistatus response body.
+pub fn parse_multistatus(xml: &str) -> Result<Vec<DavResponse>> {
+    let mut reader = NsReader::from_str(xml);
+    let mut responses = Vec::new();
+    let mut current = DavResponse::default();
+    let mut propstat: HashMap<String, String> = HashMap::new();
+    let mut status = String::new();
+    let mut path: Vec<String> = Vec::new();
+    let mut text = String::new();
+    let mut hrefs: Vec<String> = Vec::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                text.clear();
+                let name = String::from_utf8(e.local_name().into_inner().to_vec())?;
+                if path.last().is_some_and(|p| p == "resourcetype") {
+                    current.resource_types.push(name.clone());
+                }
+                path.push(name);
+            }
+            Event::Empty(e) => {
+                let name = String::from_utf8(e.local_name().into_inner().to_vec())?;
+                match path.last().map(String::as_str) {
+                    Some("resourcetype") => current.resource_types.push(name),
+                    Some("supported-calendar-component-set") if name == "comp" => {
+                        if let Some(attr) = e.try_get_attribute("name")? {
+                            current.components.push(attr.unescape_value()?.to_string());
+                        }
+                    }
+                    Some("prop") => { propstat.insert(name, String::new()); }
+                    _ => {}
+                }
+            }
+            Event::Text(t) => text.push_str(&t.decode()?),
+            Event::CData(t) => text.push_str(&t.decode()?),
+            Event::GeneralRef(r) => {
+                if let Some(c) = r.resolve_char_ref()? {
+                    text.push(c);
+                } else {
+                    let name = r.decode()?;
+                    let resolved = quick_xml::escape::resolve_predefined_entity(&name)
+                        .ok_or_else(|| anyhow::anyhow!("unknown entity &{};", name))?;
+                    text.push_str(resolved);
+                }
+            }
+            Event::End(_) => {
+                let name = path.pop().unwrap_or_default();
+                match (name.as_str(), path.last().map(String::as_str)) {
+                    ("href", Some("response")) => current.href = text.trim().to_string(),
+                    // Hrefs inside a property (current-user-principal, ...) are its value
+                    ("href", Some(_)) => hrefs.push(text.trim().to_string()),
+                    ("status", Some("propstat")) => status = text.trim().to_string(),
+                    ("propstat", _) => {
+                        // A missing status is treated as success; only 2xx values are kept.
+                        if status.is_empty() || status.split_whitespace().nth(1).is_some_and(|c| c.starts_with('2')) {
+                            current.props.extend(propstat.drain());
+                        }
+                        propstat.clear();
+                        hrefs.clear();
+                        status.clear();
+                    }
+                    ("response", _) => responses.push(std::mem::take(&mut current)),
+                    (_, Some("prop")) => {
+                        let value = if hrefs.is_empty() { std::mem::take(&mut text) } else { std::mem::take(&mut hrefs).join("\n") };
+                        propstat.insert(name, value);
+                    }
+                    _ => {}
+                }
+                text.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(responses)
+}
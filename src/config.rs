@@ -1,22 +1,107 @@
 use serde::Deserialize;
 use std::fs;
+use crate::eas_request::ProtocolVersion;
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct Config {
+    // TLS listener settings; only the plain HTTP listener is served today.
+    #[allow(dead_code)]
     pub bind: String,
     pub http_bind: String,
+    #[allow(dead_code)]
     pub tls_cert: String,
+    #[allow(dead_code)]
     pub tls_key: String,
     pub caldav_base: String,
+    /// JMAP session resource, used for out-of-office settings. Defaults to
+    /// `/.well-known/jmap` on the CalDAV server.
+    pub jmap_url: Option<String>,
     pub db_path: String,
     pub hmac_secret: String,
     pub log_level: Option<String>,
+    /// ActiveSync protocol versions to advertise and accept, e.g. `["14.1", "16.1"]`.
+    /// Defaults to every version the gateway supports.
+    pub eas_versions: Option<Vec<String>>,
+    /// Shortest and longest Ping HeartbeatInterval accepted, in seconds.
+    pub ping_min_heartbeat: Option<u32>,
+    pub ping_max_heartbeat: Option<u32>,
+    /// Device policy sent in Provision responses. When set, every command except
+    /// Provision requires the device's current policy key.
+    pub policy: Option<PolicyConfig>,
+    /// Where GAL lookups and recipient resolution go. Defaults to the CalDAV
+    /// server's principals.
+    pub directory: Option<DirectoryConfig>,
+}
+
+/// The `[policy]` table: device security requirements for ActiveSync clients.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct PolicyConfig {
+    #[serde(default)]
+    pub password_required: bool,
+    pub min_password_length: Option<u32>,
+    #[serde(default)]
+    pub alphanumeric_password_required: bool,
+    #[serde(default)]
+    pub require_device_encryption: bool,
+    /// Seconds of inactivity before the device locks.
+    pub max_inactivity_lock: Option<u32>,
+    /// Failed unlock attempts before the device wipes itself.
+    pub max_password_failed_attempts: Option<u32>,
+}
+
+/// The `[directory]` table: the source of the global address list.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct DirectoryConfig {
+    #[serde(default)]
+    pub source: DirectorySource,
+    /// Base URL of Stalwart's management API; defaults to the CalDAV server's
+    /// origin.
+    pub url: Option<String>,
+    /// API key sent as a bearer token. Without one the user's own credentials
+    /// are used, which only works for accounts allowed to list principals.
+    pub api_key: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DirectorySource {
+    /// RFC 3744 principal-property-search on the CalDAV server, with the
+    /// user's credentials.
+    #[default]
+    Caldav,
+    /// Stalwart's principal list (`/api/principal`).
+    Stalwart,
+}
+
+impl Config {
+    /// ActiveSync versions to advertise and accept, lowest first.
+    pub fn eas_versions(&self) -> Vec<ProtocolVersion> {
+        let mut versions = match &self.eas_versions {
+            Some(list) => list.iter().filter_map(|v| ProtocolVersion::parse(v).ok()).collect(),
+            None => ProtocolVersion::SUPPORTED.to_vec(),
+        };
+        versions.sort();
+        versions.dedup();
+        versions
+    }
+
+    /// Accepted Ping HeartbeatInterval range in seconds; defaults to 60 through
+    /// 3540, the limits Exchange uses.
+    pub fn ping_heartbeat_range(&self) -> (u32, u32) {
+        let min = self.ping_min_heartbeat.unwrap_or(60);
+        (min, self.ping_max_heartbeat.unwrap_or(3540).max(min))
+    }
 }
 
 impl Config {
     pub fn load(path: &str) -> anyhow::Result<Self> {
         let s = fs::read_to_string(path)?;
         let cfg: Config = toml::from_str(&s)?;
+        for v in cfg.eas_versions.iter().flatten() {
+            if !ProtocolVersion::SUPPORTED.contains(&ProtocolVersion::parse(v)?) {
+                anyhow::bail!("eas_versions: unsupported ActiveSync version {}", v);
+            }
+        }
         Ok(cfg)
     }
 }
@@ -0,0 +1,103 @@
+use crate::caldav::CaldavClient;
+use crate::config::{Config, DirectorySource};
+use anyhow::{anyhow, Result};
+use reqwest::{Client, Url};
+use serde_json::Value;
+
+/// Most entries looked at when resolving a recipient.
+const MAX_CANDIDATES: usize = 100;
+
+/// A person or group in the global address list.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DirectoryEntry {
+    /// Account name.
+    pub alias: Option<String>,
+    pub display_name: Option<String>,
+    /// Email addresses, the primary one first.
+    pub email_addresses: Vec<String>,
+}
+
+/// The global address list, read from the source `[directory]` configures.
+pub struct Directory {
+    source: DirectorySource,
+    api_url: String,
+    api_key: Option<String>,
+    caldav: CaldavClient,
+    client: Client,
+}
+
+impl Directory {
+    pub fn new(cfg: &Config) -> Self {
+        let client = Client::builder().build().unwrap();
+        let directory = cfg.directory.clone().unwrap_or_default();
+        let api_url = directory.url.unwrap_or_else(|| {
+            Url::parse(&cfg.caldav_base).map(|u| u.origin().ascii_serialization()).unwrap_or_else(|_| cfg.caldav_base.clone())
+        });
+        Directory { source: directory.source, api_url, api_key: directory.api_key, caldav: CaldavClient::new(cfg), client }
+    }
+
+    /// Entries whose name or address contains `query`, at most `limit` of them.
+    pub async fn search(&self, query: &str, limit: usize, username: &str, password: &str) -> Result<Vec<DirectoryEntry>> {
+        let mut entries = match self.source {
+            DirectorySource::Caldav => self.caldav.search_principals(query, username, password).await?.into_iter()
+                .map(|p| DirectoryEntry { alias: p.name, display_name: p.display_name, email_addresses: p.addresses })
+                .collect(),
+            DirectorySource::Stalwart => self.stalwart_principals(query, limit, username, password).await?,
+        };
+        entries.retain(|e| !e.email_addresses.is_empty());
+        entries.sort_by_key(|e| e.display_name.clone().or_else(|| e.alias.clone()).unwrap_or_default().to_lowercase());
+        entries.truncate(limit);
+        Ok(entries)
+    }
+
+    /// Entries a recipient may refer to: only the entry with exactly that
+    /// address or account name if there is one, else every entry containing
+    /// it. `Name <address>` is looked up by the address.
+    pub async fn resolve(&self, recipient: &str, username: &str, password: &str) -> Result<Vec<DirectoryEntry>> {
+        let recipient = match recipient.rsplit_once('<') {
+            Some((_, address)) => address.trim_end().trim_end_matches('>').trim(),
+            None => recipient.trim(),
+        };
+        let recipient = recipient.strip_prefix("mailto:").unwrap_or(recipient);
+        if recipient.is_empty() {
+            return Ok(Vec::new());
+        }
+        let entries = self.search(recipient, MAX_CANDIDATES, username, password).await?;
+        let exact = entries.iter().find(|e| {
+            e.email_addresses.iter().any(|a| a.eq_ignore_ascii_case(recipient))
+                || e.alias.as_deref().is_some_and(|a| a.eq_ignore_ascii_case(recipient))
+        });
+        Ok(match exact {
+            Some(entry) => vec![entry.clone()],
+            None => entries,
+        })
+    }
+
+    /// Individuals and groups from Stalwart's management API. Stalwart calls
+    /// the display name `description`; `emails` is a list, or a string when
+    /// there is only one.
+    async fn stalwart_principals(&self, query: &str, limit: usize, username: &str, password: &str) -> Result<Vec<DirectoryEntry>> {
+        let url = format!("{}/api/principal", self.api_url.trim_end_matches('/'));
+        let limit = limit.to_string();
+        let req = self.client.get(&url).query(&[("filter", query), ("types", "individual,group"), ("limit", &limit)]);
+        let req = match &self.api_key {
+            Some(key) => req.bearer_auth(key),
+            None => req.basic_auth(username, Some(password)),
+        };
+        let resp = req.send().await?;
+        if !resp.status().is_success() {
+            return Err(anyhow!("principal list failed: {}", resp.status()));
+        }
+        let body: Value = resp.json().await?;
+        let items = body["data"]["items"].as_array().ok_or_else(|| anyhow!("unexpected principal list"))?;
+        Ok(items.iter().map(|item| {
+            let text = |key: &str| item[key].as_str().map(str::trim).filter(|v| !v.is_empty()).map(String::from);
+            let email_addresses = match &item["emails"] {
+                Value::String(email) => vec![email.clone()],
+                Value::Array(emails) => emails.iter().filter_map(|e| e.as_str().map(String::from)).collect(),
+                _ => Vec::new(),
+            };
+            DirectoryEntry { alias: text("name"), display_name: text("description"), email_addresses }
+        }).collect())
+    }
+}
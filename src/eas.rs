@@ -1,58 +1,543 @@
-use axum::{extract::Extension, http::StatusCode, response::IntoResponse};
-use axum::http::HeaderMap;
+use axum::{extract::{Extension, RawQuery}, http::StatusCode, response::{IntoResponse, Response}};
+use axum::http::{header, HeaderMap};
 use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::Engine;
 use bytes::Bytes;
 use std::sync::Arc;
 use crate::models::AppState;
-use crate::wbxml::Wbxml;
-use crate::sync;
+use crate::caldav::CaldavClient;
+use serde::Serialize;
+use crate::codepages::{AIRSYNC, FIND, FOLDER_HIERARCHY, GET_ITEM_ESTIMATE, ITEM_OPERATIONS, MEETING_RESPONSE, PING, PROVISION, RESOLVE_RECIPIENTS, SEARCH, SETTINGS};
+use crate::eas_models::{FetchProperties, FetchResponse, FindRequest, FolderSyncRequest, FolderSyncResponse, GetItemEstimateRequest, GetItemEstimateResponse, ItemOperationsRequest, ItemOperationsResponse, ItemOperationsResults, MeetingResponseRequest, MeetingResponseResponse, PingChangedFolders, PingRequest, PingResponse, ProvisionRequest, ProvisionResponse, ResolveRecipientsRequest, ResolveRecipientsResponse, SearchRequest, SearchResponse, SettingsRequest, SettingsResponse, SyncRequest, SyncResponse, SyncResponseCollections};
+use crate::eas_request::{ProtocolVersion, RequestLine};
+use crate::wbxml::{Element, Wbxml};
+use crate::wbxml_serde::{from_element, to_element};
+use crate::sync::{self, PingResult, SyncSession};
+use crate::provision::{self, Admission};
+use crate::meeting;
+use crate::item_operations;
+use crate::resolve_recipients;
+use crate::search;
+use crate::settings;
+use std::time::Duration;
+
+const WBXML_CONTENT_TYPE: &str = "application/vnd.ms-sync.wbxml";
+const MULTIPART_CONTENT_TYPE: &str = "application/vnd.ms-sync.multipart";
+
+/// Most folders one Ping may watch.
+const MAX_PING_FOLDERS: u32 = 300;
+
+/// ActiveSync commands the gateway implements. This registry drives both request
+/// dispatch and the command list advertised to clients in the OPTIONS response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    FolderSync,
+    Sync,
+    GetItemEstimate,
+    ItemOperations,
+    MeetingResponse,
+    Ping,
+    Provision,
+    ResolveRecipients,
+    Search,
+    Settings,
+    Find,
+}
+
+impl Command {
+    pub const ALL: &'static [Command] = &[Command::FolderSync, Command::Sync, Command::GetItemEstimate, Command::ItemOperations, Command::MeetingResponse, Command::Ping, Command::Provision, Command::ResolveRecipients, Command::Search, Command::Settings, Command::Find];
+
+    /// Name as used in the `Cmd` request parameter and `MS-ASProtocolCommands`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Command::FolderSync => "FolderSync",
+            Command::Sync => "Sync",
+            Command::GetItemEstimate => "GetItemEstimate",
+            Command::ItemOperations => "ItemOperations",
+            Command::MeetingResponse => "MeetingResponse",
+            Command::Ping => "Ping",
+            Command::Provision => "Provision",
+            Command::ResolveRecipients => "ResolveRecipients",
+            Command::Search => "Search",
+            Command::Settings => "Settings",
+            Command::Find => "Find",
+        }
+    }
+
+    /// Lowest protocol version in which the command exists.
+    fn min_version(self) -> ProtocolVersion {
+        match self {
+            Command::ItemOperations | Command::Settings => ProtocolVersion::V12_0,
+            Command::Find => ProtocolVersion::V16_1,
+            _ => ProtocolVersion::V2_5,
+        }
+    }
+
+    /// Code page and tag of the command's request root element.
+    fn root(self) -> (u8, &'static str) {
+        match self {
+            Command::FolderSync => (FOLDER_HIERARCHY, "FolderSync"),
+            Command::Sync => (AIRSYNC, "Sync"),
+            Command::GetItemEstimate => (GET_ITEM_ESTIMATE, "GetItemEstimate"),
+            Command::ItemOperations => (ITEM_OPERATIONS, "ItemOperations"),
+            Command::MeetingResponse => (MEETING_RESPONSE, "MeetingResponse"),
+            Command::Ping => (PING, "Ping"),
+            Command::Provision => (PROVISION, "Provision"),
+            Command::ResolveRecipients => (RESOLVE_RECIPIENTS, "ResolveRecipients"),
+            Command::Search => (SEARCH, "Search"),
+            Command::Settings => (SETTINGS, "Settings"),
+            Command::Find => (FIND, "Find"),
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Command> {
+        Command::ALL.iter().copied().find(|c| c.name() == name)
+    }
+}
 
 fn parse_basic_auth(headers: &HeaderMap) -> Option<(String,String)> {
-    if let Some(v) = headers.get("authorization") {
-        if let Ok(s) = v.to_str() {
-            let s = s.trim();
-            if s.to_lowercase().starts_with("basic ") {
-                let b64 = s[6..].trim();
-                let mut out = Vec::new();
-                if BASE64.decode_vec(b64.as_bytes(), &mut out).is_ok() {
-                    if let Ok(creds) = String::from_utf8(out) {
-                        if let Some(idx) = creds.find(':') {
-                            let user = creds[..idx].to_string();
-                            let pass = creds[idx+1..].to_string();
-                            return Some((user, pass));
-                        }
-                    }
-                }
-            }
-        }
-    }
-    None
-}
-
-pub async fn handle_activesync(Extension(state): Extension<Arc<AppState>>, headers: HeaderMap, body: Bytes) -> impl IntoResponse {
-    let payload = body.to_vec();
+    let s = headers.get("authorization")?.to_str().ok()?.trim();
+    if !s.to_lowercase().starts_with("basic ") {
+        return None;
+    }
+    let creds = String::from_utf8(BASE64.decode(s[6..].trim()).ok()?).ok()?;
+    let (user, pass) = creds.split_once(':')?;
+    Some((user.to_string(), pass.to_string()))
+}
+
+/// Whether the User of a request line names the signed-in user. Case is
+/// ignored, as is a `DOMAIN\` prefix on either name.
+fn same_user(line_user: &str, username: &str) -> bool {
+    let account = |name: &str| name.rsplit('\\').next().unwrap_or(name).to_string();
+    account(line_user).eq_ignore_ascii_case(&account(username))
+}
+
+/// 401 asking for Basic credentials.
+fn unauthorized() -> Response {
+    (StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, "Basic realm=\"ActiveSync\"")], "Authentication required").into_response()
+}
+
+/// Encode a command response model as WBXML with the ActiveSync content type.
+fn wbxml_response<T: Serialize>(wbxml: &Wbxml, resp: &T) -> Response {
+    match to_element(resp).and_then(|root| wbxml.encode(&root)) {
+        Ok(body) => (StatusCode::OK, [(header::CONTENT_TYPE, WBXML_CONTENT_TYPE)], body).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("WBXML encode error: {}", e)).into_response(),
+    }
+}
+
+fn version_list(versions: &[ProtocolVersion]) -> String {
+    versions.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",")
+}
+
+/// Pick the protocol version for a request: the `MS-ASProtocolVersion` header, or
+/// the version byte of a base64 request line. Clients that send neither are
+/// treated as the lowest advertised version.
+fn negotiate_version(headers: &HeaderMap, line: &RequestLine, advertised: &[ProtocolVersion]) -> Option<ProtocolVersion> {
+    let requested = match headers.get("ms-asprotocolversion").and_then(|v| v.to_str().ok()) {
+        Some(v) => ProtocolVersion::parse(v).ok(),
+        None => line.protocol_version.or_else(|| advertised.first().copied()),
+    };
+    requested.filter(|v| advertised.contains(v))
+}
+
+/// OPTIONS discovery: clients probe this before account setup to learn which
+/// protocol versions and commands the server supports.
+pub async fn handle_options(Extension(state): Extension<Arc<AppState>>) -> Response {
+    let advertised = state.cfg.eas_versions();
+    let latest = advertised.last().copied().unwrap_or(ProtocolVersion::V14_1);
+    let commands = Command::ALL.iter().filter(|c| c.min_version() <= latest).map(|c| c.name()).collect::<Vec<_>>().join(",");
+    let versions = version_list(&advertised);
+    (
+        StatusCode::OK,
+        [
+            ("Allow", "OPTIONS,POST".to_string()),
+            ("Public", "OPTIONS,POST".to_string()),
+            ("MS-Server-ActiveSync", latest.to_string()),
+            ("MS-ASProtocolVersions", versions),
+            ("MS-ASProtocolCommands", commands),
+        ],
+    ).into_response()
+}
+
+pub async fn handle_activesync(Extension(state): Extension<Arc<AppState>>, RawQuery(query): RawQuery, headers: HeaderMap, body: Bytes) -> Response {
+    let line = match RequestLine::parse(query.as_deref().unwrap_or_default()) {
+        Ok(l) => l,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid request line: {}", e)).into_response(),
+    };
+    let Some(cmd) = Command::from_name(&line.cmd) else {
+        return (StatusCode::NOT_IMPLEMENTED, format!("Unsupported ActiveSync command: {}", line.cmd)).into_response();
+    };
+
+    let advertised = state.cfg.eas_versions();
+    let Some(version) = negotiate_version(&headers, &line, &advertised) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            [("MS-ASProtocolVersions", version_list(&advertised))],
+            "Unsupported ActiveSync protocol version",
+        ).into_response();
+    };
+    if version < cmd.min_version() {
+        return (StatusCode::BAD_REQUEST, format!("{} requires protocol version {} or later", line.cmd, cmd.min_version())).into_response();
+    }
+
+    // Nothing about the device is looked at or recorded before its user has
+    // signed in with credentials the CalDAV server accepts.
+    let Some((username, password)) = parse_basic_auth(&headers).filter(|(user, _)| !user.is_empty()) else {
+        return unauthorized();
+    };
+    match CaldavClient::new(&state.cfg).authenticate(&username, &password).await {
+        Ok(true) => {}
+        Ok(false) => {
+            tracing::info!("ActiveSync {} for {} rejected: invalid credentials", line.cmd, username);
+            return unauthorized();
+        }
+        Err(e) => return (StatusCode::SERVICE_UNAVAILABLE, format!("Could not verify credentials: {}", e)).into_response(),
+    }
+    if !line.user.is_empty() && !same_user(&line.user, &username) {
+        tracing::warn!("ActiveSync {} for {} rejected: request line names {}", line.cmd, username, line.user);
+        return (StatusCode::FORBIDDEN, "Request line user does not match the credentials").into_response();
+    }
+    let owner = username.as_str();
+
     let wbxml = Wbxml::new();
-    let xml = match wbxml.decode(&payload) {
-        Ok(s) => s,
-        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid WBXML: {}", e)).into_response(),
-    };
-
-    let (username, password) = parse_basic_auth(&headers).unwrap_or((String::new(), String::new()));
-
-    // Minimal parse to route command: look for <FolderSync> or <Sync>
-    if xml.contains("<FolderSync") {
-        let resp = r#"<?xml version="1.0" encoding="utf-8"?><FolderSync><Status>1</Status><SyncKey>0</SyncKey><Folders><Folder><ServerId>1</ServerId><ParentId>0</ParentId><DisplayName>Calendar</DisplayName><Type>8</Type></Folder></Folders></FolderSync>"#;
-        return (StatusCode::OK, resp.to_string()).into_response();
-    } else if xml.contains("<Sync") {
-        // call sync engine
-        let owner = if !username.is_empty() { username.as_str() } else { "demo" };
-        let collection_id = "1";
-        match sync::perform_sync(state, owner, collection_id, "0", 100, &username, &password).await {
-            Ok(resp_xml) => return (StatusCode::OK, resp_xml).into_response(),
+    let req = if body.is_empty() {
+        None
+    } else {
+        match wbxml.decode(&body) {
+            Ok(e) => Some(e),
+            Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid WBXML: {}", e)).into_response(),
+        }
+    };
+    if let Some(req) = &req {
+        if (req.page, req.tag) != cmd.root() {
+            return (StatusCode::BAD_REQUEST, format!("Request body does not match command {}", line.cmd)).into_response();
+        }
+        tracing::debug!("ActiveSync {} request of {} bytes", line.cmd, body.len());
+        tracing::trace!("ActiveSync {} request: {}", line.cmd, req.to_xml());
+    }
+
+    tracing::info!("ActiveSync {} {} from {} on {} device {}", version, line.cmd, owner, line.device_type, line.device_id);
+
+    let session = SyncSession { owner, device_id: &line.device_id, username: &username, password: &password, version };
+    let user_agent = headers.get(header::USER_AGENT).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    if let Err(e) = state.storage.register_device(owner, &line.device_id, &line.device_type, user_agent).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("Device registration failed: {}", e)).into_response();
+    }
+    let policy_key = headers.get("X-MS-PolicyKey")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u32>().ok())
+        .or(line.policy_key);
+    match provision::admit(&state, &session, cmd == Command::Provision, policy_key).await {
+        Ok(Admission::Allowed) => {}
+        Ok(Admission::MustProvision) => {
+            tracing::info!("device {} of {} must provision before {}", line.device_id, owner, line.cmd);
+            return (StatusCode::from_u16(449).unwrap(), "Retry after sending a PROVISION command").into_response();
+        }
+        Ok(Admission::Forbidden) => {
+            tracing::info!("refusing {} from blocked device {} of {}", line.cmd, line.device_id, owner);
+            return (StatusCode::FORBIDDEN, "Device is blocked").into_response();
+        }
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Device check failed: {}", e)).into_response(),
+    }
+    match cmd {
+        Command::FolderSync => handle_folder_sync(state, &wbxml, req.as_ref(), &session).await,
+        Command::Sync => handle_sync(state, &wbxml, req.as_ref(), &session).await,
+        Command::GetItemEstimate => handle_get_item_estimate(state, &wbxml, req.as_ref(), &session).await,
+        Command::ItemOperations => {
+            let multipart = line.accept_multipart
+                || headers.get("ms-asacceptmultipart").and_then(|v| v.to_str().ok()).is_some_and(|v| v.trim().eq_ignore_ascii_case("T"));
+            handle_item_operations(state, &wbxml, req.as_ref(), &session, multipart).await
+        }
+        Command::MeetingResponse => handle_meeting_response(state, &wbxml, req.as_ref(), &session).await,
+        Command::Ping => handle_ping(state, &wbxml, req.as_ref(), &session).await,
+        Command::Provision => handle_provision(state, &wbxml, req.as_ref(), &session).await,
+        Command::ResolveRecipients => handle_resolve_recipients(state, &wbxml, req.as_ref(), &session).await,
+        Command::Search => handle_search(state, &wbxml, req.as_ref(), &session).await,
+        Command::Settings => handle_settings(state, &wbxml, req.as_ref(), &session).await,
+        Command::Find => handle_find(state, &wbxml, req.as_ref(), &session).await,
+    }
+}
+
+async fn handle_folder_sync(state: Arc<AppState>, wbxml: &Wbxml, req: Option<&Element>, session: &SyncSession<'_>) -> Response {
+    let Some(req) = req else {
+        return (StatusCode::BAD_REQUEST, "FolderSync requires a request body").into_response();
+    };
+    let fs_req: FolderSyncRequest = match from_element(req) {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid FolderSync request: {}", e)).into_response(),
+    };
+    tracing::debug!("FolderSync for {} on {} from sync key {}", session.owner, session.device_id, fs_req.sync_key);
+    let resp = match sync::perform_folder_sync(state, session, &fs_req.sync_key).await {
+        Ok(r) => r,
+        Err(e) => {
+            tracing::error!("FolderSync failed for {}: {}", session.owner, e);
+            FolderSyncResponse { status: sync::FOLDER_STATUS_SERVER_ERROR, ..Default::default() }
+        }
+    };
+    wbxml_response(wbxml, &resp)
+}
+
+async fn handle_sync(state: Arc<AppState>, wbxml: &Wbxml, req: Option<&Element>, session: &SyncSession<'_>) -> Response {
+    // An empty body is a valid Sync request that repeats the previous one
+    let sync_req: SyncRequest = match req.map(from_element).transpose() {
+        Ok(r) => r.unwrap_or_default(),
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid Sync request: {}", e)).into_response(),
+    };
+    let mut collections = SyncResponseCollections::default();
+    for coll in sync_req.collections.map(|c| c.collection).unwrap_or_default() {
+        let window_size = coll.window_size.or(sync_req.window_size).unwrap_or(sync::DEFAULT_WINDOW_SIZE);
+        let window_size = window_size.clamp(1, sync::MAX_WINDOW_SIZE) as usize;
+        match sync::perform_sync(state.clone(), session, &coll, window_size).await {
+            Ok(resp_coll) => collections.collection.push(resp_coll),
             Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Sync error: {}", e)).into_response(),
         }
     }
+    wbxml_response(wbxml, &SyncResponse { status: None, collections: Some(collections) })
+}
+
+async fn handle_get_item_estimate(state: Arc<AppState>, wbxml: &Wbxml, req: Option<&Element>, session: &SyncSession<'_>) -> Response {
+    let Some(req) = req else {
+        return (StatusCode::BAD_REQUEST, "GetItemEstimate requires a request body").into_response();
+    };
+    let est_req: GetItemEstimateRequest = match from_element(req) {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid GetItemEstimate request: {}", e)).into_response(),
+    };
+    let mut resp = GetItemEstimateResponse::default();
+    for coll in &est_req.collections.collection {
+        match sync::estimate_changes(&state, session, coll).await {
+            Ok(r) => resp.response.push(r),
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("GetItemEstimate error: {}", e)).into_response(),
+        }
+    }
+    wbxml_response(wbxml, &resp)
+}
+
+/// ItemOperations Fetch. Attachment data goes inline as OPAQUE, or, when the
+/// client accepts multipart responses, in parts of its own after the WBXML.
+async fn handle_item_operations(state: Arc<AppState>, wbxml: &Wbxml, req: Option<&Element>, session: &SyncSession<'_>, multipart: bool) -> Response {
+    let Some(req) = req else {
+        return (StatusCode::BAD_REQUEST, "ItemOperations requires a request body").into_response();
+    };
+    let io_req: ItemOperationsRequest = match from_element(req) {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid ItemOperations request: {}", e)).into_response(),
+    };
+    let mut results = ItemOperationsResults::default();
+    for fetch in &io_req.fetch {
+        let resp = item_operations::perform_fetch(&state, session, fetch).await.unwrap_or_else(|e| {
+            tracing::error!("ItemOperations Fetch failed for {}: {}", session.owner, e);
+            FetchResponse { status: item_operations::ITEMOPS_STATUS_SERVER_ERROR, ..Default::default() }
+        });
+        results.fetch.push(resp);
+    }
+    let mut resp = ItemOperationsResponse { status: 1, response: Some(results) };
+    if !multipart {
+        return wbxml_response(wbxml, &resp);
+    }
+
+    let mut parts = Vec::new();
+    for fetch in resp.response.iter_mut().flat_map(|r| r.fetch.iter_mut()) {
+        if let Some(FetchProperties::Attachment(props)) = &mut fetch.properties
+            && let Some(data) = props.data.take()
+        {
+            parts.push(data.0);
+            props.part = Some(parts.len() as u32);
+        }
+    }
+    match to_element(&resp).and_then(|root| wbxml.encode(&root)) {
+        Ok(body) => {
+            parts.insert(0, body);
+            (StatusCode::OK, [(header::CONTENT_TYPE, MULTIPART_CONTENT_TYPE)], multipart_body(&parts)).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("WBXML encode error: {}", e)).into_response(),
+    }
+}
+
+/// Lay out a multipart response body: the part count, an offset and length for
+/// each part, then the parts, all integers 32-bit little-endian.
+fn multipart_body(parts: &[Vec<u8>]) -> Vec<u8> {
+    let mut offset = 4 + 8 * parts.len();
+    let mut body = Vec::with_capacity(offset + parts.iter().map(Vec::len).sum::<usize>());
+    body.extend_from_slice(&(parts.len() as i32).to_le_bytes());
+    for part in parts {
+        body.extend_from_slice(&(offset as i32).to_le_bytes());
+        body.extend_from_slice(&(part.len() as i32).to_le_bytes());
+        offset += part.len();
+    }
+    for part in parts {
+        body.extend_from_slice(part);
+    }
+    body
+}
+
+async fn handle_meeting_response(state: Arc<AppState>, wbxml: &Wbxml, req: Option<&Element>, session: &SyncSession<'_>) -> Response {
+    let Some(req) = req else {
+        return (StatusCode::BAD_REQUEST, "MeetingResponse requires a request body").into_response();
+    };
+    let mr_req: MeetingResponseRequest = match from_element(req) {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid MeetingResponse request: {}", e)).into_response(),
+    };
+    let mut resp = MeetingResponseResponse::default();
+    for item in &mr_req.request {
+        match meeting::perform_meeting_response(&state, session, item).await {
+            Ok(r) => resp.result.push(r),
+            Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("MeetingResponse error: {}", e)).into_response(),
+        }
+    }
+    wbxml_response(wbxml, &resp)
+}
+
+/// Ping holds the request open until a watched folder changes or the heartbeat
+/// runs out. A Ping without a body, or without one of its parameters, reuses what
+/// the device sent last time.
+async fn handle_ping(state: Arc<AppState>, wbxml: &Wbxml, req: Option<&Element>, session: &SyncSession<'_>) -> Response {
+    let ping_req: PingRequest = match req.map(from_element).transpose() {
+        Ok(r) => r.unwrap_or_default(),
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid Ping request: {}", e)).into_response(),
+    };
+    let status = |status: u32| PingResponse { status, ..Default::default() };
+
+    let previous = match state.storage.get_ping_state(session.owner, session.device_id).await {
+        Ok(p) => p,
+        Err(e) => {
+            tracing::error!("Ping state unavailable for {}: {}", session.owner, e);
+            return wbxml_response(wbxml, &status(sync::PING_STATUS_SERVER_ERROR));
+        }
+    };
+    let heartbeat = ping_req.heartbeat_interval.or(previous.as_ref().map(|p| p.0));
+    let folders = ping_req.folders.map(|f| f.folder.into_iter().map(|f| f.id).collect::<Vec<_>>())
+        .or(previous.map(|p| p.1))
+        .filter(|f| !f.is_empty());
+    let (Some(heartbeat), Some(folders)) = (heartbeat, folders) else {
+        return wbxml_response(wbxml, &status(sync::PING_STATUS_MISSING_PARAMETERS));
+    };
+
+    let (min, max) = state.cfg.ping_heartbeat_range();
+    if heartbeat < min || heartbeat > max {
+        let resp = PingResponse { heartbeat_interval: Some(heartbeat.clamp(min, max)), ..status(sync::PING_STATUS_BAD_HEARTBEAT) };
+        return wbxml_response(wbxml, &resp);
+    }
+    if folders.len() > MAX_PING_FOLDERS as usize {
+        let resp = PingResponse { max_folders: Some(MAX_PING_FOLDERS), ..status(sync::PING_STATUS_TOO_MANY_FOLDERS) };
+        return wbxml_response(wbxml, &resp);
+    }
+    if let Err(e) = state.storage.set_ping_state(session.owner, session.device_id, heartbeat, &folders).await {
+        tracing::error!("failed to store Ping state for {}: {}", session.owner, e);
+        return wbxml_response(wbxml, &status(sync::PING_STATUS_SERVER_ERROR));
+    }
 
-    (StatusCode::BAD_REQUEST, "Unsupported ActiveSync command").into_response()
+    tracing::debug!("Ping for {} on {}: {} folders, {}s heartbeat", session.owner, session.device_id, folders.len(), heartbeat);
+    let resp = match sync::watch_folders(state.clone(), session, &folders, Duration::from_secs(heartbeat as u64)).await {
+        Ok(PingResult::Expired) => status(sync::PING_STATUS_EXPIRED),
+        Ok(PingResult::Changed(folder)) => PingResponse { folders: Some(PingChangedFolders { folder }), ..status(sync::PING_STATUS_CHANGES) },
+        Ok(PingResult::HierarchyChanged) => status(sync::PING_STATUS_HIERARCHY_CHANGED),
+        Err(e) => {
+            tracing::error!("Ping failed for {}: {}", session.owner, e);
+            status(sync::PING_STATUS_SERVER_ERROR)
+        }
+    };
+    wbxml_response(wbxml, &resp)
+}
+
+async fn handle_provision(state: Arc<AppState>, wbxml: &Wbxml, req: Option<&Element>, session: &SyncSession<'_>) -> Response {
+    let Some(req) = req else {
+        return (StatusCode::BAD_REQUEST, "Provision requires a request body").into_response();
+    };
+    let prov_req: ProvisionRequest = match from_element(req) {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid Provision request: {}", e)).into_response(),
+    };
+    let resp = match provision::perform_provision(&state, session, &prov_req).await {
+        Ok(r) => r,
+        Err(e) => {
+            tracing::error!("Provision failed for {} on {}: {}", session.owner, session.device_id, e);
+            ProvisionResponse { status: provision::PROVISION_STATUS_SERVER_ERROR, ..Default::default() }
+        }
+    };
+    wbxml_response(wbxml, &resp)
+}
+
+async fn handle_resolve_recipients(state: Arc<AppState>, wbxml: &Wbxml, req: Option<&Element>, session: &SyncSession<'_>) -> Response {
+    let Some(req) = req else {
+        return (StatusCode::BAD_REQUEST, "ResolveRecipients requires a request body").into_response();
+    };
+    let resolve_req: ResolveRecipientsRequest = match from_element(req) {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid ResolveRecipients request: {}", e)).into_response(),
+    };
+    let resp = match resolve_recipients::perform_resolve_recipients(&state, session, &resolve_req).await {
+        Ok(r) => r,
+        Err(e) => {
+            tracing::error!("ResolveRecipients failed for {} on {}: {}", session.owner, session.device_id, e);
+            ResolveRecipientsResponse { status: resolve_recipients::RESOLVE_STATUS_SERVER_ERROR, response: Vec::new() }
+        }
+    };
+    wbxml_response(wbxml, &resp)
+}
+
+async fn handle_search(state: Arc<AppState>, wbxml: &Wbxml, req: Option<&Element>, session: &SyncSession<'_>) -> Response {
+    let Some(req) = req else {
+        return (StatusCode::BAD_REQUEST, "Search requires a request body").into_response();
+    };
+    let search_req: SearchRequest = match from_element(req) {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid Search request: {}", e)).into_response(),
+    };
+    let resp = match search::perform_search(&state, session, &search_req).await {
+        Ok(r) => r,
+        Err(e) => {
+            tracing::error!("Search failed for {} on {}: {}", session.owner, session.device_id, e);
+            SearchResponse { status: search::SEARCH_STATUS_SERVER_ERROR, response: None }
+        }
+    };
+    wbxml_response(wbxml, &resp)
+}
+
+async fn handle_settings(state: Arc<AppState>, wbxml: &Wbxml, req: Option<&Element>, session: &SyncSession<'_>) -> Response {
+    let Some(req) = req else {
+        return (StatusCode::BAD_REQUEST, "Settings requires a request body").into_response();
+    };
+    let settings_req: SettingsRequest = match from_element(req) {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid Settings request: {}", e)).into_response(),
+    };
+    let resp = match settings::perform_settings(&state, session, &settings_req).await {
+        Ok(r) => r,
+        Err(e) => {
+            tracing::error!("Settings failed for {} on {}: {}", session.owner, session.device_id, e);
+            SettingsResponse { status: settings::SETTINGS_STATUS_SERVER_UNAVAILABLE, ..Default::default() }
+        }
+    };
+    wbxml_response(wbxml, &resp)
+}
+
+/// Find searches mailbox mail or the GAL. GAL searches are answered from the
+/// directory as with Search; the gateway has no mail, so mailbox searches are
+/// refused.
+async fn handle_find(state: Arc<AppState>, wbxml: &Wbxml, req: Option<&Element>, session: &SyncSession<'_>) -> Response {
+    let Some(req) = req else {
+        return (StatusCode::BAD_REQUEST, "Find requires a request body").into_response();
+    };
+    let find_req: FindRequest = match from_element(req) {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid Find request: {}", e)).into_response(),
+    };
+    let resp = search::perform_find(&state, session, &find_req).await;
+    wbxml_response(wbxml, &resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_request_line_user_to_credentials() {
+        assert!(same_user("alice", "alice"));
+        assert!(same_user("CORP\\Alice", "alice"));
+        assert!(same_user("alice", "corp\\ALICE"));
+        assert!(!same_user("bob", "alice"));
+        assert!(!same_user("CORP\\bob", "CORP\\alice"));
+    }
 }
@@ -0,0 +1,1314 @@
+use anyhow::{Result, anyhow};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use icalendar::{Alarm, Calendar, CalendarComponent, CalendarDateTime, Component, DatePerhapsTime, Event, EventLike, Property, Trigger};
+use crate::eas_models::{ApplicationData, Attendee, Attendees, Body, Categories, Exception, Exceptions, Recurrence};
+use crate::eas_timezone::TimeZoneInfo;
+use std::collections::HashMap;
+
+/// ActiveSync date-time format for calendar properties.
+pub const EAS_DATE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+// BusyStatus values
+const BUSY_FREE: u8 = 0;
+const BUSY_TENTATIVE: u8 = 1;
+const BUSY_BUSY: u8 = 2;
+const BUSY_OOF: u8 = 3;
+const BUSY_ELSEWHERE: u8 = 4;
+
+// Sensitivity values
+const SENSITIVITY_NORMAL: u8 = 0;
+const SENSITIVITY_PERSONAL: u8 = 1;
+const SENSITIVITY_PRIVATE: u8 = 2;
+const SENSITIVITY_CONFIDENTIAL: u8 = 3;
+
+// MeetingStatus values
+const MEETING_NONE: u8 = 0;
+const MEETING_ORGANIZER: u8 = 1;
+const MEETING_RECEIVED: u8 = 3;
+const MEETING_CANCELLED: u8 = 5;
+const MEETING_CANCELLED_RECEIVED: u8 = 7;
+
+// AttendeeStatus values
+const ATTENDEE_TENTATIVE: u8 = 2;
+const ATTENDEE_ACCEPTED: u8 = 3;
+const ATTENDEE_DECLINED: u8 = 4;
+const ATTENDEE_NOT_RESPONDED: u8 = 5;
+
+// AttendeeType values
+const ATTENDEE_REQUIRED: u8 = 1;
+const ATTENDEE_OPTIONAL: u8 = 2;
+const ATTENDEE_RESOURCE: u8 = 3;
+
+// Recurrence Type values
+const RECUR_DAILY: u8 = 0;
+const RECUR_WEEKLY: u8 = 1;
+const RECUR_MONTHLY: u8 = 2;
+const RECUR_MONTHLY_NTH: u8 = 3;
+const RECUR_YEARLY: u8 = 5;
+const RECUR_YEARLY_NTH: u8 = 6;
+
+// MeetingResponse UserResponse values
+const RESPONSE_ACCEPTED: u8 = 1;
+const RESPONSE_TENTATIVE: u8 = 2;
+const RESPONSE_DECLINED: u8 = 3;
+
+/// WeekOfMonth value meaning "the last one".
+const LAST_WEEK: u8 = 5;
+
+/// Weekdays in ActiveSync DayOfWeek bit order (Sunday = 1 ... Saturday = 64)
+/// with their RRULE abbreviations.
+const WEEKDAYS: [(Weekday, &str); 7] = [
+    (Weekday::Sun, "SU"), (Weekday::Mon, "MO"), (Weekday::Tue, "TU"), (Weekday::Wed, "WE"),
+    (Weekday::Thu, "TH"), (Weekday::Fri, "FR"), (Weekday::Sat, "SA"),
+];
+
+/// Parse an ActiveSync date-time, compact (`20250601T100000Z`) or extended
+/// (`2025-06-01T10:00:00.000Z`).
+pub fn parse_eas_date(s: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(s, EAS_DATE_FORMAT).ok().map(|t| t.and_utc())
+        .or_else(|| DateTime::parse_from_rfc3339(s).ok().map(|t| t.with_timezone(&Utc)))
+}
+
+pub fn format_eas_date(dt: DateTime<Utc>) -> String {
+    dt.format(EAS_DATE_FORMAT).to_string()
+}
+
+/// Rules of the VTIMEZONE components in a calendar object, by TZID.
+type Zones = HashMap<String, TimeZoneInfo>;
+
+fn vtimezones(cal: &Calendar) -> Zones {
+    cal.components.iter().filter_map(|c| match c {
+        CalendarComponent::Other(o) if o.component_kind().eq_ignore_ascii_case("VTIMEZONE") => {
+            Some((o.property_value("TZID")?.to_string(), TimeZoneInfo::from_vtimezone(o)?))
+        }
+        _ => None,
+    }).collect()
+}
+
+/// Rules for a TZID in `year`: the IANA zone of that name, else the calendar's
+/// own VTIMEZONE.
+fn zone_for(tzid: &str, zones: &Zones, year: i32) -> Option<TimeZoneInfo> {
+    tzid.parse::<Tz>().ok().map(|tz| TimeZoneInfo::from_tz(tz, year)).or_else(|| zones.get(tzid).cloned())
+}
+
+/// Absolute time of an iCalendar date or date-time. Dates are midnight UTC;
+/// floating times and TZIDs that cannot be resolved are taken as UTC.
+fn to_utc(dt: &DatePerhapsTime, zones: &Zones) -> DateTime<Utc> {
+    match dt {
+        DatePerhapsTime::Date(d) => d.and_time(NaiveTime::MIN).and_utc(),
+        DatePerhapsTime::DateTime(CalendarDateTime::Utc(t)) => *t,
+        DatePerhapsTime::DateTime(CalendarDateTime::Floating(t)) => t.and_utc(),
+        DatePerhapsTime::DateTime(cdt @ CalendarDateTime::WithTimezone { date_time, tzid }) => {
+            cdt.try_into_utc()
+                .or_else(|| zones.get(tzid).map(|z| z.to_utc(*date_time)))
+                .unwrap_or_else(|| {
+                    tracing::debug!("unknown TZID {}, treating time as UTC", tzid);
+                    date_time.and_utc()
+                })
+        }
+    }
+}
+
+/// Timezone blob for an event starting at `start`: its TZID's rules, or UTC for
+/// dates, floating and UTC times.
+fn event_timezone(start: Option<&DatePerhapsTime>, zones: &Zones) -> TimeZoneInfo {
+    match start {
+        Some(DatePerhapsTime::DateTime(CalendarDateTime::WithTimezone { date_time, tzid })) => {
+            zone_for(tzid, zones, date_time.year()).unwrap_or_default()
+        }
+        _ => TimeZoneInfo::default(),
+    }
+}
+
+/// Every date in a multi-valued date property such as EXDATE.
+fn property_dates(p: &Property) -> Vec<DatePerhapsTime> {
+    p.value().split(',').filter_map(|v| {
+        let mut single = Property::new(p.key(), v.trim());
+        for param in p.params().values() {
+            single.append_parameter(param.clone());
+        }
+        DatePerhapsTime::from_property(&single)
+    }).collect()
+}
+
+/// Strip the `mailto:` scheme from a calendar user address.
+fn cal_address(value: &str) -> String {
+    let v = value.trim();
+    v.get(..7).filter(|p| p.eq_ignore_ascii_case("mailto:")).map(|_| &v[7..]).unwrap_or(v).to_string()
+}
+
+/// Whether a calendar address belongs to `user`, which may be a full address or
+/// only the local part.
+fn is_user(address: &str, user: &str) -> bool {
+    !user.is_empty() && (address.eq_ignore_ascii_case(user)
+        || address.split('@').next().is_some_and(|local| local.eq_ignore_ascii_case(user)))
+}
+
+/// Quote a parameter value that contains separators; the serializer only quotes
+/// values containing colons.
+fn param_value(v: &str) -> String {
+    let v = v.replace('"', "");
+    if (v.contains(';') || v.contains(',')) && !v.contains(':') { format!("\"{}\"", v) } else { v }
+}
+
+/// Minutes in an iCalendar DURATION such as `-PT15M`, `-P1DT2H` or `-PT900S`.
+fn duration_minutes(s: &str) -> Option<i64> {
+    let (negative, rest) = match s.trim().strip_prefix('-') {
+        Some(r) => (true, r),
+        None => (false, s.trim().trim_start_matches('+')),
+    };
+    let rest = rest.strip_prefix('P')?;
+    let mut seconds = 0i64;
+    let mut number = String::new();
+    for c in rest.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {}
+            'W' | 'D' | 'H' | 'M' | 'S' => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                seconds += n * match c { 'W' => 604_800, 'D' => 86_400, 'H' => 3_600, 'M' => 60, _ => 1 };
+            }
+            _ => return None,
+        }
+    }
+    Some(if negative { -seconds / 60 } else { seconds / 60 })
+}
+
+fn busy_status(event: &Event) -> Option<u8> {
+    if let Some(cdo) = event.property_value("X-MICROSOFT-CDO-BUSYSTATUS") {
+        return match cdo.to_ascii_uppercase().as_str() {
+            "FREE" => Some(BUSY_FREE),
+            "TENTATIVE" => Some(BUSY_TENTATIVE),
+            "OOF" => Some(BUSY_OOF),
+            "WORKINGELSEWHERE" => Some(BUSY_ELSEWHERE),
+            _ => Some(BUSY_BUSY),
+        };
+    }
+    match event.property_value("TRANSP") {
+        Some(t) if t.eq_ignore_ascii_case("TRANSPARENT") => Some(BUSY_FREE),
+        _ if event.property_value("STATUS").is_some_and(|s| s.eq_ignore_ascii_case("TENTATIVE")) => Some(BUSY_TENTATIVE),
+        _ => Some(BUSY_BUSY),
+    }
+}
+
+fn sensitivity(event: &Event) -> Option<u8> {
+    event.property_value("CLASS").map(|c| match c.to_ascii_uppercase().as_str() {
+        "PRIVATE" => SENSITIVITY_PRIVATE,
+        "CONFIDENTIAL" => SENSITIVITY_CONFIDENTIAL,
+        _ => SENSITIVITY_NORMAL,
+    })
+}
+
+/// Minutes before the start of the first relative VALARM.
+fn reminder(event: &Event) -> Option<u32> {
+    event.components().iter()
+        .filter(|c| c.component_kind() == "VALARM")
+        .filter_map(|a| a.property_value("TRIGGER"))
+        .find_map(duration_minutes)
+        .filter(|m| *m <= 0)
+        .map(|m| (-m) as u32)
+}
+
+fn attendees(event: &Event) -> Option<Attendees> {
+    let props = event.multi_properties().get("ATTENDEE")?;
+    let attendee = props.iter().map(|p| {
+        let param = |k: &str| p.params().get(k).map(|v| v.value().to_ascii_uppercase());
+        let attendee_status = match param("PARTSTAT").as_deref() {
+            Some("ACCEPTED") => ATTENDEE_ACCEPTED,
+            Some("DECLINED") => ATTENDEE_DECLINED,
+            Some("TENTATIVE") => ATTENDEE_TENTATIVE,
+            _ => ATTENDEE_NOT_RESPONDED,
+        };
+        let is_resource = matches!(param("CUTYPE").as_deref(), Some("RESOURCE") | Some("ROOM"));
+        let attendee_type = match param("ROLE").as_deref() {
+            _ if is_resource => ATTENDEE_RESOURCE,
+            Some("OPT-PARTICIPANT") | Some("NON-PARTICIPANT") => ATTENDEE_OPTIONAL,
+            _ => ATTENDEE_REQUIRED,
+        };
+        Attendee {
+            email: cal_address(p.value()),
+            name: p.params().get("CN").map(|v| v.value().to_string()),
+            attendee_status: Some(attendee_status),
+            attendee_type: Some(attendee_type),
+        }
+    }).collect::<Vec<_>>();
+    (!attendee.is_empty()).then_some(Attendees { attendee })
+}
+
+fn categories(event: &Event) -> Option<Categories> {
+    let category = event.multi_properties().get("CATEGORIES")?.iter()
+        .flat_map(|p| p.value().split(',').map(|c| c.trim().to_string()).collect::<Vec<_>>())
+        .filter(|c| !c.is_empty())
+        .collect::<Vec<_>>();
+    (!category.is_empty()).then_some(Categories { category })
+}
+
+fn day_mask(days: &[Weekday]) -> u8 {
+    WEEKDAYS.iter().enumerate().filter(|(_, (d, _))| days.contains(d)).fold(0, |m, (i, _)| m | (1 << i))
+}
+
+/// Parse RRULE BYDAY entries such as `MO`, `2TU` or `-1FR` into (ordinal, weekday).
+fn by_day(value: &str) -> Vec<(Option<i8>, Weekday)> {
+    value.split(',').filter_map(|entry| {
+        let entry = entry.trim();
+        let split = entry.char_indices().nth_back(1)?.0;
+        let day = WEEKDAYS.iter().find(|(_, abbr)| entry[split..].eq_ignore_ascii_case(abbr))?.0;
+        let ordinal = entry[..split].parse::<i8>().ok();
+        Some((ordinal, day))
+    }).collect()
+}
+
+fn recurrence(rrule: &str, start: DateTime<Utc>) -> Option<Recurrence> {
+    let parts: Vec<(String, &str)> = rrule.split(';')
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| (k.trim().to_ascii_uppercase(), v.trim()))
+        .collect();
+    let get = |k: &str| parts.iter().find(|(key, _)| key == k).map(|(_, v)| *v);
+
+    let days = get("BYDAY").map(by_day).unwrap_or_default();
+    let set_pos = get("BYSETPOS").and_then(|v| v.parse::<i8>().ok());
+    let ordinal = days.iter().find_map(|(o, _)| *o).or(set_pos);
+    let week_of_month = ordinal.map(|o| if o < 0 { LAST_WEEK } else { o.min(LAST_WEEK as i8) as u8 });
+    let day_mask_or_start = || {
+        let mask = day_mask(&days.iter().map(|(_, d)| *d).collect::<Vec<_>>());
+        if mask == 0 { day_mask(&[start.weekday()]) } else { mask }
+    };
+    // EAS has a single day of the month and cannot count from its end
+    let month_day = match get("BYMONTHDAY").map(|v| v.parse::<u8>()) {
+        None => start.day() as u8,
+        Some(Ok(day @ 1..=31)) => day,
+        Some(_) => {
+            tracing::debug!("unsupported recurrence {}", rrule);
+            return None;
+        }
+    };
+    let month = get("BYMONTH").and_then(|v| v.parse::<u8>().ok()).unwrap_or(start.month() as u8);
+
+    let mut r = Recurrence {
+        interval: get("INTERVAL").and_then(|v| v.parse().ok()),
+        occurrences: get("COUNT").and_then(|v| v.parse().ok()),
+        until: get("UNTIL").map(|u| {
+            NaiveDate::parse_from_str(u, "%Y%m%d").ok()
+                .map(|d| format_eas_date(d.and_time(NaiveTime::MIN).and_utc()))
+                .or_else(|| NaiveDateTime::parse_from_str(u.trim_end_matches('Z'), "%Y%m%dT%H%M%S").ok().map(|t| format_eas_date(t.and_utc())))
+                .unwrap_or_else(|| u.to_string())
+        }),
+        first_day_of_week: get("WKST").and_then(|w| WEEKDAYS.iter().position(|(_, a)| w.eq_ignore_ascii_case(a))).map(|i| i as u8),
+        ..Default::default()
+    };
+    match get("FREQ")?.to_ascii_uppercase().as_str() {
+        "DAILY" => {
+            r.r#type = RECUR_DAILY;
+            if !days.is_empty() {
+                r.day_of_week = Some(day_mask_or_start());
+            }
+        }
+        "WEEKLY" => {
+            r.r#type = RECUR_WEEKLY;
+            r.day_of_week = Some(day_mask_or_start());
+        }
+        // Every given weekday of the month or year has no EAS equivalent
+        "MONTHLY" | "YEARLY" if !days.is_empty() && week_of_month.is_none() => {
+            tracing::debug!("unsupported recurrence {}", rrule);
+            return None;
+        }
+        "MONTHLY" if week_of_month.is_some() => {
+            r.r#type = RECUR_MONTHLY_NTH;
+            r.week_of_month = week_of_month;
+            r.day_of_week = Some(day_mask_or_start());
+        }
+        "MONTHLY" => {
+            r.r#type = RECUR_MONTHLY;
+            r.day_of_month = Some(month_day);
+        }
+        "YEARLY" if week_of_month.is_some() => {
+            r.r#type = RECUR_YEARLY_NTH;
+            r.week_of_month = week_of_month;
+            r.day_of_week = Some(day_mask_or_start());
+            r.month_of_year = Some(month);
+        }
+        "YEARLY" => {
+            r.r#type = RECUR_YEARLY;
+            r.day_of_month = Some(month_day);
+            r.month_of_year = Some(month);
+        }
+        _ => return None,
+    }
+    Some(r)
+}
+
+fn meeting_status(event: &Event, has_attendees: bool, user: &str) -> u8 {
+    let organizer = event.property_value("ORGANIZER").map(cal_address);
+    if organizer.is_none() && !has_attendees {
+        return MEETING_NONE;
+    }
+    let is_organizer = organizer.as_deref().is_none_or(|o| is_user(o, user));
+    let cancelled = event.property_value("STATUS").is_some_and(|s| s.eq_ignore_ascii_case("CANCELLED"));
+    match (cancelled, is_organizer) {
+        (true, true) => MEETING_CANCELLED,
+        (true, false) => MEETING_CANCELLED_RECEIVED,
+        (false, true) => MEETING_ORGANIZER,
+        (false, false) => MEETING_RECEIVED,
+    }
+}
+
+fn body(event: &Event) -> Option<Body> {
+    event.get_description().map(|d| Body {
+        r#type: 1,
+        estimated_data_size: Some(d.len() as u32),
+        data: Some(d.to_string()),
+        ..Default::default()
+    })
+}
+
+/// Where the data of an ATTACH is.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AttachmentContent {
+    /// Inline base64 data (`VALUE=BINARY`).
+    Inline(Vec<u8>),
+    /// A link, such as a managed attachment (RFC 8607) on the CalDAV server.
+    Uri(String),
+}
+
+/// One ATTACH of an event.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventAttachment {
+    pub display_name: String,
+    pub content_type: Option<String>,
+    /// Size in bytes: of the inline data, or from the SIZE parameter of a link.
+    pub size: Option<u32>,
+    pub content: AttachmentContent,
+}
+
+fn attachment(p: &Property, position: usize) -> Option<EventAttachment> {
+    let param = |key: &str| p.params().get(key).map(|v| v.value().trim_matches('"').to_string()).filter(|v| !v.is_empty());
+    let binary = param("VALUE").is_some_and(|v| v.eq_ignore_ascii_case("BINARY"))
+        || param("ENCODING").is_some_and(|v| v.eq_ignore_ascii_case("BASE64"));
+    let content = if binary {
+        let data: String = p.value().split_whitespace().collect();
+        AttachmentContent::Inline(BASE64.decode(data).ok()?)
+    } else {
+        AttachmentContent::Uri(p.value().trim().to_string())
+    };
+    let (size, link_name) = match &content {
+        AttachmentContent::Inline(data) => (Some(data.len() as u32), None),
+        AttachmentContent::Uri(uri) => (
+            param("SIZE").and_then(|s| s.parse().ok()),
+            uri.split(['?', '#']).next().and_then(|u| u.rsplit('/').next()).filter(|n| !n.is_empty()).map(str::to_string),
+        ),
+    };
+    let display_name = param("FILENAME").or_else(|| param("X-FILENAME")).or_else(|| param("X-APPLE-FILENAME"))
+        .or(link_name)
+        .unwrap_or_else(|| format!("attachment-{}", position + 1));
+    Some(EventAttachment { display_name, content_type: param("FMTTYPE"), size, content })
+}
+
+/// The ATTACHs of an iCalendar object's master VEVENT, in order. Inline data
+/// that does not decode is skipped.
+pub fn event_attachments(ics: &str) -> Result<Vec<EventAttachment>> {
+    let cal: Calendar = ics.parse().map_err(|e| anyhow!("invalid iCalendar: {}", e))?;
+    let master = cal.components.iter().filter_map(|c| c.as_event())
+        .find(|e| e.get_recurrence_id().is_none())
+        .ok_or_else(|| anyhow!("no VEVENT in calendar object"))?;
+    Ok(master.multi_properties().get("ATTACH").into_iter().flatten().enumerate()
+        .filter_map(|(i, p)| attachment(p, i))
+        .collect())
+}
+
+/// Carry what a client cannot send back from the stored copy of an event into
+/// the one rebuilt from its Change: the ATTACHs of the master and, when the
+/// client left the Body out because it only holds a truncated one, the
+/// DESCRIPTION.
+pub fn keep_server_properties(ics: &str, stored: &str, keep_description: bool) -> Result<String> {
+    let stored: Calendar = stored.parse().map_err(|e| anyhow!("invalid iCalendar: {}", e))?;
+    let Some(old) = stored.components.iter().filter_map(|c| c.as_event()).find(|e| e.get_recurrence_id().is_none()) else {
+        return Ok(ics.to_string());
+    };
+    let mut cal: Calendar = ics.parse().map_err(|e| anyhow!("invalid iCalendar: {}", e))?;
+    let master = cal.components.iter_mut().find_map(|c| match c {
+        CalendarComponent::Event(e) if e.get_recurrence_id().is_none() => Some(e),
+        _ => None,
+    }).ok_or_else(|| anyhow!("no VEVENT in calendar object"))?;
+    for p in old.multi_properties().get("ATTACH").into_iter().flatten() {
+        master.append_multi_property(p.clone());
+    }
+    if keep_description
+        && master.get_description().is_none()
+        && let Some(description) = old.get_description()
+    {
+        master.description(description);
+    }
+    Ok(cal.to_string())
+}
+
+/// Convert an iCalendar object to Calendar ApplicationData. The master VEVENT
+/// provides the item; EXDATEs and RECURRENCE-ID overrides become Exceptions.
+/// `user` decides whether the user organizes a meeting or was invited to it.
+pub fn ics_to_application_data(ics: &str, user: &str) -> Result<ApplicationData> {
+    let cal: Calendar = ics.parse().map_err(|e| anyhow!("invalid iCalendar: {}", e))?;
+    let zones = vtimezones(&cal);
+    let to_utc = |dt: &DatePerhapsTime| to_utc(dt, &zones);
+    let events: Vec<&Event> = cal.components.iter().filter_map(|c| c.as_event()).collect();
+    let master = events.iter().copied()
+        .find(|e| e.get_recurrence_id().is_none())
+        .ok_or_else(|| anyhow!("no VEVENT in calendar object"))?;
+
+    let start = master.get_start();
+    let start_utc = start.as_ref().map(to_utc);
+    let timezone = event_timezone(start.as_ref(), &zones);
+    let all_day = matches!(start, Some(DatePerhapsTime::Date(_)));
+    let master_attendees = attendees(master);
+    let organizer = master.properties().get("ORGANIZER");
+
+    let mut data = ApplicationData {
+        timezone: Some(timezone.encode()),
+        subject: master.get_summary().map(str::to_string),
+        location: master.get_location().map(str::to_string),
+        uid: master.get_uid().map(str::to_string),
+        start_time: start_utc.map(format_eas_date),
+        end_time: master.get_end().as_ref().map(|e| format_eas_date(to_utc(e))),
+        dt_stamp: master.get_timestamp().map(format_eas_date),
+        all_day_event: Some(all_day),
+        body: body(master),
+        busy_status: busy_status(master),
+        sensitivity: sensitivity(master),
+        reminder: reminder(master),
+        organizer_email: organizer.map(|o| cal_address(o.value())),
+        organizer_name: organizer.and_then(|o| o.params().get("CN")).map(|cn| cn.value().to_string()),
+        meeting_status: Some(meeting_status(master, master_attendees.is_some(), user)),
+        attendees: master_attendees,
+        categories: categories(master),
+        recurrence: master.property_value("RRULE").zip(start_utc).and_then(|(r, s)| recurrence(r, s)),
+        ..Default::default()
+    };
+
+    if data.recurrence.is_some() {
+        let mut exception = Vec::new();
+        for ex in master.multi_properties().get("EXDATE").into_iter().flatten().flat_map(property_dates) {
+            exception.push(Exception {
+                deleted: Some(true),
+                exception_start_time: Some(format_eas_date(to_utc(&ex))),
+                ..Default::default()
+            });
+        }
+        for e in events.iter().filter(|e| e.get_recurrence_id().is_some()) {
+            exception.push(Exception {
+                exception_start_time: e.get_recurrence_id().map(|r| format_eas_date(to_utc(&r))),
+                subject: e.get_summary().map(str::to_string),
+                start_time: e.get_start().map(|s| format_eas_date(to_utc(&s))),
+                end_time: e.get_end().map(|s| format_eas_date(to_utc(&s))),
+                location: e.get_location().map(str::to_string),
+                body: body(e),
+                all_day_event: Some(matches!(e.get_start(), Some(DatePerhapsTime::Date(_)))),
+                busy_status: busy_status(e),
+                reminder: reminder(e),
+                sensitivity: sensitivity(e),
+                meeting_status: e.property_value("STATUS")
+                    .filter(|s| s.eq_ignore_ascii_case("CANCELLED"))
+                    .map(|_| if data.meeting_status == Some(MEETING_ORGANIZER) { MEETING_CANCELLED } else { MEETING_CANCELLED_RECEIVED }),
+                attendees: attendees(e),
+                dt_stamp: e.get_timestamp().map(format_eas_date),
+                ..Default::default()
+            });
+        }
+        data.exceptions = (!exception.is_empty()).then_some(Exceptions { exception });
+    }
+    Ok(data)
+}
+
+fn rrule(r: &Recurrence, all_day: bool, zone: Option<&ClientZone>) -> String {
+    let days = |mask: u8| WEEKDAYS.iter().enumerate()
+        .filter(|(i, _)| mask & (1 << i) != 0)
+        .map(|(_, (_, abbr))| *abbr)
+        .collect::<Vec<_>>();
+    let nth = |week: u8, mask: u8| {
+        let ordinal = if week >= LAST_WEEK { -1 } else { week as i8 };
+        days(mask).iter().map(|d| format!("{}{}", ordinal, d)).collect::<Vec<_>>().join(",")
+    };
+    let mut parts = Vec::new();
+    match r.r#type {
+        RECUR_DAILY => {
+            parts.push("FREQ=DAILY".to_string());
+            if let Some(mask) = r.day_of_week.filter(|m| *m != 0) {
+                parts.push(format!("BYDAY={}", days(mask).join(",")));
+            }
+        }
+        RECUR_WEEKLY => {
+            parts.push("FREQ=WEEKLY".to_string());
+            if let Some(mask) = r.day_of_week.filter(|m| *m != 0) {
+                parts.push(format!("BYDAY={}", days(mask).join(",")));
+            }
+        }
+        RECUR_MONTHLY => {
+            parts.push("FREQ=MONTHLY".to_string());
+            if let Some(day) = r.day_of_month {
+                parts.push(format!("BYMONTHDAY={}", day));
+            }
+        }
+        RECUR_MONTHLY_NTH => {
+            parts.push("FREQ=MONTHLY".to_string());
+            parts.push(format!("BYDAY={}", nth(r.week_of_month.unwrap_or(1), r.day_of_week.unwrap_or(0))));
+        }
+        RECUR_YEARLY => {
+            parts.push("FREQ=YEARLY".to_string());
+            if let Some(month) = r.month_of_year {
+                parts.push(format!("BYMONTH={}", month));
+            }
+            if let Some(day) = r.day_of_month {
+                parts.push(format!("BYMONTHDAY={}", day));
+            }
+        }
+        _ => {
+            parts.push("FREQ=YEARLY".to_string());
+            if let Some(month) = r.month_of_year {
+                parts.push(format!("BYMONTH={}", month));
+            }
+            parts.push(format!("BYDAY={}", nth(r.week_of_month.unwrap_or(1), r.day_of_week.unwrap_or(0))));
+        }
+    }
+    if let Some(interval) = r.interval.filter(|i| *i > 1) {
+        parts.push(format!("INTERVAL={}", interval));
+    }
+    if let Some(count) = r.occurrences {
+        parts.push(format!("COUNT={}", count));
+    } else if let Some(until) = r.until.as_deref().and_then(parse_eas_date) {
+        // UNTIL takes the value type of DTSTART; date-times are given in UTC
+        let until = match event_time(until, all_day, zone) {
+            DatePerhapsTime::Date(d) => d.format("%Y%m%d").to_string(),
+            _ => format_eas_date(until),
+        };
+        parts.push(format!("UNTIL={}", until));
+    }
+    if let Some(wkst) = r.first_day_of_week.and_then(|i| WEEKDAYS.get(i as usize)) {
+        parts.push(format!("WKST={}", wkst.1));
+    }
+    parts.join(";")
+}
+
+/// Write the properties shared by master events and overrides.
+fn set_common(event: &mut Event, subject: Option<&str>, location: Option<&str>, body: Option<&Body>, busy: Option<u8>, sensitivity: Option<u8>, reminder: Option<u32>) {
+    if let Some(subject) = subject {
+        event.summary(subject);
+    }
+    if let Some(location) = location {
+        event.location(location);
+    }
+    if let Some(text) = body.and_then(|b| b.data.as_deref()) {
+        event.description(text);
+    }
+    if let Some(busy) = busy {
+        let (transp, cdo) = match busy {
+            BUSY_FREE => ("TRANSPARENT", "FREE"),
+            BUSY_TENTATIVE => ("OPAQUE", "TENTATIVE"),
+            BUSY_OOF => ("OPAQUE", "OOF"),
+            BUSY_ELSEWHERE => ("OPAQUE", "WORKINGELSEWHERE"),
+            _ => ("OPAQUE", "BUSY"),
+        };
+        event.add_property("TRANSP", transp);
+        event.add_property("X-MICROSOFT-CDO-BUSYSTATUS", cdo);
+    }
+    match sensitivity {
+        Some(SENSITIVITY_PERSONAL) | Some(SENSITIVITY_PRIVATE) => { event.add_property("CLASS", "PRIVATE"); }
+        Some(SENSITIVITY_CONFIDENTIAL) => { event.add_property("CLASS", "CONFIDENTIAL"); }
+        Some(_) => { event.add_property("CLASS", "PUBLIC"); }
+        None => {}
+    }
+    if let Some(minutes) = reminder {
+        event.alarm(Alarm::display("Reminder", Trigger::before_start(Duration::minutes(minutes as i64))));
+    }
+}
+
+fn add_attendees(event: &mut Event, attendees: &Attendees) {
+    for a in &attendees.attendee {
+        let mut p = Property::new("ATTENDEE", format!("mailto:{}", a.email));
+        if let Some(name) = &a.name {
+            p.add_parameter("CN", &param_value(name));
+        }
+        let partstat = match a.attendee_status {
+            Some(ATTENDEE_ACCEPTED) => "ACCEPTED",
+            Some(ATTENDEE_DECLINED) => "DECLINED",
+            Some(ATTENDEE_TENTATIVE) => "TENTATIVE",
+            _ => "NEEDS-ACTION",
+        };
+        p.add_parameter("PARTSTAT", partstat);
+        match a.attendee_type {
+            Some(ATTENDEE_OPTIONAL) => { p.add_parameter("ROLE", "OPT-PARTICIPANT"); }
+            Some(ATTENDEE_RESOURCE) => { p.add_parameter("CUTYPE", "RESOURCE").add_parameter("ROLE", "NON-PARTICIPANT"); }
+            _ => { p.add_parameter("ROLE", "REQ-PARTICIPANT"); }
+        }
+        event.append_multi_property(p);
+    }
+}
+
+/// The zone a client's times are written in, from its Timezone blob.
+struct ClientZone {
+    info: TimeZoneInfo,
+    tzid: String,
+}
+
+impl ClientZone {
+    /// None for a missing, invalid or UTC blob, whose times stay in UTC.
+    fn from_blob(blob: Option<&str>) -> Option<Self> {
+        let info = TimeZoneInfo::decode(blob?)
+            .map_err(|e| tracing::warn!("ignoring Timezone: {}", e))
+            .ok()
+            .filter(|z| !z.is_utc())?;
+        Some(ClientZone { tzid: info.tzid(), info })
+    }
+}
+
+/// Date or date-time property value for an event that is or is not all-day.
+/// Times are written in the client's zone; all-day dates are its local date.
+fn event_time(dt: DateTime<Utc>, all_day: bool, zone: Option<&ClientZone>) -> DatePerhapsTime {
+    match zone {
+        Some(z) if all_day => DatePerhapsTime::Date(z.info.to_local(dt).date()),
+        None if all_day => DatePerhapsTime::Date(dt.date_naive()),
+        Some(z) => DatePerhapsTime::DateTime(CalendarDateTime::WithTimezone { date_time: z.info.to_local(dt), tzid: z.tzid.clone() }),
+        None => DatePerhapsTime::DateTime(CalendarDateTime::Utc(dt)),
+    }
+}
+
+/// Build an iCalendar object from Calendar ApplicationData. Exceptions become
+/// EXDATEs (deleted occurrences) or RECURRENCE-ID overrides. Times are written
+/// with the client's Timezone as TZID and a matching VTIMEZONE.
+pub fn application_data_to_ics(data: &ApplicationData, uid: &str) -> Result<String> {
+    let start = data.start_time.as_deref().and_then(parse_eas_date)
+        .ok_or_else(|| anyhow!("missing or invalid StartTime"))?;
+    let end = data.end_time.as_deref().and_then(parse_eas_date).unwrap_or(start);
+    let all_day = data.all_day_event == Some(true);
+    let zone = ClientZone::from_blob(data.timezone.as_deref());
+    let zone = zone.as_ref();
+
+    let mut master = Event::new();
+    master.uid(uid).timestamp(data.dt_stamp.as_deref().and_then(parse_eas_date).unwrap_or_else(Utc::now));
+    master.starts(event_time(start, all_day, zone)).ends(event_time(end, all_day, zone));
+    set_common(&mut master, data.subject.as_deref(), data.location.as_deref(), data.body.as_ref(), data.busy_status, data.sensitivity, data.reminder);
+    if let Some(email) = &data.organizer_email {
+        let mut p = Property::new("ORGANIZER", format!("mailto:{}", email));
+        if let Some(name) = &data.organizer_name {
+            p.add_parameter("CN", &param_value(name));
+        }
+        master.append_property(p);
+    }
+    if let Some(attendees) = &data.attendees {
+        add_attendees(&mut master, attendees);
+    }
+    if let Some(categories) = &data.categories {
+        for c in &categories.category {
+            master.append_multi_property(Property::new("CATEGORIES", c));
+        }
+    }
+    if matches!(data.meeting_status, Some(MEETING_CANCELLED) | Some(MEETING_CANCELLED_RECEIVED)) {
+        master.add_property("STATUS", "CANCELLED");
+    }
+    if let Some(r) = &data.recurrence {
+        master.add_property("RRULE", rrule(r, all_day, zone));
+    }
+
+    let mut overrides = Vec::new();
+    for ex in data.exceptions.iter().flat_map(|e| e.exception.iter()) {
+        let Some(original) = ex.exception_start_time.as_deref().and_then(parse_eas_date) else { continue };
+        if ex.deleted == Some(true) {
+            master.append_multi_property(event_time(original, all_day, zone).to_property("EXDATE"));
+            continue;
+        }
+        let ex_all_day = ex.all_day_event.unwrap_or(all_day);
+        let ex_start = ex.start_time.as_deref().and_then(parse_eas_date).unwrap_or(original);
+        let ex_end = ex.end_time.as_deref().and_then(parse_eas_date).unwrap_or(ex_start + (end - start));
+        let mut e = Event::new();
+        e.uid(uid).timestamp(ex.dt_stamp.as_deref().and_then(parse_eas_date).unwrap_or_else(Utc::now));
+        e.append_property(event_time(original, all_day, zone).to_property("RECURRENCE-ID"));
+        e.starts(event_time(ex_start, ex_all_day, zone)).ends(event_time(ex_end, ex_all_day, zone));
+        set_common(
+            &mut e,
+            ex.subject.as_deref().or(data.subject.as_deref()),
+            ex.location.as_deref().or(data.location.as_deref()),
+            ex.body.as_ref().or(data.body.as_ref()),
+            ex.busy_status.or(data.busy_status),
+            ex.sensitivity.or(data.sensitivity),
+            ex.reminder.or(data.reminder),
+        );
+        if let Some(attendees) = ex.attendees.as_ref().or(data.attendees.as_ref()) {
+            add_attendees(&mut e, attendees);
+        }
+        if matches!(ex.meeting_status, Some(MEETING_CANCELLED) | Some(MEETING_CANCELLED_RECEIVED)) {
+            e.add_property("STATUS", "CANCELLED");
+        }
+        overrides.push(e.done());
+    }
+
+    let mut cal = Calendar::new();
+    if let Some(z) = zone {
+        let wrapper = format!("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{}END:VCALENDAR\r\n", z.info.to_vtimezone(&z.tzid));
+        let parsed: Calendar = wrapper.parse().map_err(|e| anyhow!("invalid VTIMEZONE: {}", e))?;
+        for c in parsed.components {
+            cal.push(c);
+        }
+    }
+    cal.push(master.done());
+    for e in overrides {
+        cal.push(e);
+    }
+    Ok(cal.done().to_string())
+}
+
+/// A time of one occurrence of `master`, written as an all-day date or in the
+/// zone of its DTSTART, as EXDATE and RECURRENCE-ID values must be.
+fn occurrence_time(master: &Event, at: DateTime<Utc>, zones: &Zones) -> DatePerhapsTime {
+    let start = master.get_start();
+    let all_day = matches!(start, Some(DatePerhapsTime::Date(_)));
+    let zone = match start {
+        Some(DatePerhapsTime::DateTime(CalendarDateTime::WithTimezone { tzid, .. })) => {
+            zone_for(&tzid, zones, at.year()).map(|info| ClientZone { info, tzid })
+        }
+        _ => None,
+    };
+    event_time(at, all_day, zone.as_ref())
+}
+
+/// Cancel one occurrence of a recurring event by adding an EXDATE to its master,
+/// in the same zone as its DTSTART.
+pub fn exclude_instance(ics: &str, instance_id: &str) -> Result<String> {
+    let instance = parse_eas_date(instance_id).ok_or_else(|| anyhow!("invalid InstanceId {}", instance_id))?;
+    let mut cal: Calendar = ics.parse().map_err(|e| anyhow!("invalid iCalendar: {}", e))?;
+    let zones = vtimezones(&cal);
+    let master = cal.components.iter_mut().find_map(|c| match c {
+        CalendarComponent::Event(e) if e.get_recurrence_id().is_none() => Some(e),
+        _ => None,
+    }).ok_or_else(|| anyhow!("no VEVENT in calendar object"))?;
+    let exdate = occurrence_time(master, instance, &zones).to_property("EXDATE");
+    master.append_multi_property(exdate);
+    Ok(cal.to_string())
+}
+
+/// An invitee's answer to a meeting: the updated calendar object and the iTIP
+/// REPLY that tells the organizer.
+pub struct MeetingReply {
+    pub ics: String,
+    pub reply: String,
+    /// Address of the replying attendee.
+    pub attendee: String,
+    /// Address of the organizer the reply goes to.
+    pub organizer: String,
+}
+
+/// Set `user`'s PARTSTAT on their ATTENDEE of `event`, dropping the RSVP
+/// request. Returns the updated property, or None if `user` is not invited.
+fn set_partstat(event: &mut Event, user: &str, partstat: &str) -> Option<Property> {
+    let mut attendees = event.multi_properties().get("ATTENDEE").cloned().unwrap_or_default();
+    let mut mine = None;
+    for p in attendees.iter_mut().filter(|p| is_user(&cal_address(p.value()), user)) {
+        let mut updated = Property::new("ATTENDEE", p.value());
+        for (key, param) in p.params() {
+            if !key.eq_ignore_ascii_case("PARTSTAT") && !key.eq_ignore_ascii_case("RSVP") {
+                updated.append_parameter(param.clone());
+            }
+        }
+        updated.add_parameter("PARTSTAT", partstat);
+        *p = updated.clone();
+        mine = Some(updated);
+    }
+    let mine = mine?;
+    event.remove_multi_property("ATTENDEE");
+    for p in attendees {
+        event.append_multi_property(p);
+    }
+    Some(mine)
+}
+
+/// An override for the occurrence of `master` at `at`: a copy of the series
+/// without its recurrence rules, moved to that occurrence.
+fn occurrence_override(master: &Event, at: DateTime<Utc>, zones: &Zones) -> Event {
+    let mut e = master.clone();
+    for key in ["RRULE", "RDATE", "EXDATE", "EXRULE"] {
+        e.remove_property(key);
+        e.remove_multi_property(key);
+    }
+    let start = occurrence_time(master, at, zones);
+    if let (Some(s), Some(end)) = (master.get_start(), master.get_end()) {
+        let length = to_utc(&end, zones) - to_utc(&s, zones);
+        e.append_property(occurrence_time(master, at + length, zones).to_property("DTEND"));
+    }
+    e.append_property(start.clone().to_property("DTSTART"));
+    e.append_property(start.to_property("RECURRENCE-ID"));
+    e
+}
+
+/// Record `user`'s answer to a meeting invitation (UserResponse 1 accepted,
+/// 2 tentative, 3 declined). Without `instance_id` it applies to the whole
+/// series; with one it applies to that occurrence, which gets an override if it
+/// has none yet. The REPLY carries the attendee's new PARTSTAT for the event or
+/// occurrence answered, and `comment` as its COMMENT.
+pub fn respond_to_invitation(ics: &str, user: &str, response: u8, instance_id: Option<&str>, comment: Option<&str>) -> Result<MeetingReply> {
+    let partstat = match response {
+        RESPONSE_ACCEPTED => "ACCEPTED",
+        RESPONSE_TENTATIVE => "TENTATIVE",
+        RESPONSE_DECLINED => "DECLINED",
+        other => return Err(anyhow!("invalid UserResponse {}", other)),
+    };
+    let instance = instance_id
+        .map(|id| parse_eas_date(id).ok_or_else(|| anyhow!("invalid InstanceId {}", id)))
+        .transpose()?;
+    let mut cal: Calendar = ics.parse().map_err(|e| anyhow!("invalid iCalendar: {}", e))?;
+    let zones = vtimezones(&cal);
+    let master = cal.components.iter().find_map(|c| match c {
+        CalendarComponent::Event(e) if e.get_recurrence_id().is_none() => Some(e.clone()),
+        _ => None,
+    }).ok_or_else(|| anyhow!("no VEVENT in calendar object"))?;
+    let organizer = master.property_value("ORGANIZER").map(cal_address)
+        .filter(|o| !is_user(o, user))
+        .ok_or_else(|| anyhow!("{} was not invited to this event by someone else", user))?;
+
+    // The components whose PARTSTAT changes; the first one is answered
+    let mut targets = Vec::new();
+    match instance {
+        Some(at) => {
+            if master.property_value("RRULE").is_none() && master.multi_properties().get("RDATE").is_none() {
+                return Err(anyhow!("InstanceId given for an event that does not recur"));
+            }
+            let existing = cal.components.iter().position(|c| matches!(c,
+                CalendarComponent::Event(e) if e.get_recurrence_id().is_some_and(|r| to_utc(&r, &zones) == at)));
+            let index = existing.unwrap_or_else(|| {
+                cal.components.push(CalendarComponent::Event(occurrence_override(&master, at, &zones)));
+                cal.components.len() - 1
+            });
+            targets.push(index);
+        }
+        None => targets.extend(cal.components.iter().enumerate()
+            .filter(|(_, c)| matches!(c, CalendarComponent::Event(_)))
+            .map(|(i, _)| i)),
+    }
+    // Answer the series first
+    targets.sort_by_key(|i| matches!(&cal.components[*i], CalendarComponent::Event(e) if e.get_recurrence_id().is_some()));
+
+    let mut answered = None;
+    for i in targets {
+        let CalendarComponent::Event(event) = &mut cal.components[i] else { continue };
+        if let Some(attendee) = set_partstat(event, user, partstat) {
+            answered.get_or_insert((event.clone(), attendee));
+        }
+    }
+    let (event, attendee) = answered.ok_or_else(|| anyhow!("{} is not an attendee of this event", user))?;
+
+    let mut reply = Event::new();
+    reply.timestamp(Utc::now());
+    for key in ["UID", "SEQUENCE", "SUMMARY", "DTSTART", "DTEND", "DURATION", "RECURRENCE-ID", "ORGANIZER"] {
+        if let Some(p) = event.properties().get(key) {
+            reply.append_property(p.clone());
+        }
+    }
+    if let Some(text) = comment.map(str::trim).filter(|c| !c.is_empty()) {
+        reply.add_property("COMMENT", text);
+    }
+    let address = cal_address(attendee.value());
+    reply.append_multi_property(attendee);
+    let mut itip = Calendar::new();
+    itip.append_property(Property::new("METHOD", "REPLY"));
+    for c in cal.components.iter().filter(|c| matches!(c, CalendarComponent::Other(o) if o.component_kind().eq_ignore_ascii_case("VTIMEZONE"))) {
+        itip.push(c.clone());
+    }
+    itip.push(reply.done());
+
+    Ok(MeetingReply { ics: cal.to_string(), reply: itip.done().to_string(), attendee: address, organizer })
+}
+
+/// Minutes covered by one digit of a MergedFreeBusy string.
+const FREE_BUSY_SLOT_MINUTES: i64 = 30;
+
+/// The busy status a FREEBUSY period's FBTYPE stands for.
+fn fb_type_status(p: &Property) -> u8 {
+    match p.params().get("FBTYPE").map(|t| t.value().to_ascii_uppercase()).as_deref() {
+        Some("FREE") => BUSY_FREE,
+        Some("BUSY-TENTATIVE") => BUSY_TENTATIVE,
+        Some("BUSY-UNAVAILABLE") => BUSY_OOF,
+        _ => BUSY_BUSY,
+    }
+}
+
+/// The MergedFreeBusy string for `start` to `end` from the VFREEBUSY of a
+/// CalDAV free-busy-query: one digit per 30 minutes, the busy status of the
+/// busiest FREEBUSY period overlapping it (`0` free, `1` tentative, `2` busy,
+/// `3` out of office).
+pub fn merged_free_busy(ics: &str, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<String> {
+    let cal: Calendar = ics.parse().map_err(|e| anyhow!("invalid iCalendar: {}", e))?;
+    let free_busy = cal.components.iter()
+        .find_map(|c| match c {
+            CalendarComponent::Other(o) if o.component_kind().eq_ignore_ascii_case("VFREEBUSY") => Some(o),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow!("no VFREEBUSY in free/busy response"))?;
+    let slot = Duration::minutes(FREE_BUSY_SLOT_MINUTES);
+    let count = ((end - start).num_minutes() + FREE_BUSY_SLOT_MINUTES - 1).max(0) / FREE_BUSY_SLOT_MINUTES;
+    let mut slots = vec![BUSY_FREE; count as usize];
+    for p in free_busy.multi_properties().get("FREEBUSY").into_iter().flatten() {
+        let status = fb_type_status(p);
+        for period in p.value().split(',') {
+            let Some((from, to)) = period.trim().split_once('/') else {
+                continue;
+            };
+            let Some(from) = parse_eas_date(from) else {
+                continue;
+            };
+            let Some(to) = parse_eas_date(to).or_else(|| duration_minutes(to).map(|m| from + Duration::minutes(m))) else {
+                continue;
+            };
+            for (i, s) in slots.iter_mut().enumerate() {
+                let slot_start = start + slot * i as i32;
+                if from < slot_start + slot && to > slot_start {
+                    *s = (*s).max(status);
+                }
+            }
+        }
+    }
+    Ok(slots.iter().map(|s| char::from(b'0' + s)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Convert ApplicationData to iCalendar and back again.
+    fn round_trip(data: &ApplicationData, user: &str) -> ApplicationData {
+        let ics = application_data_to_ics(data, "uid-1").unwrap();
+        ics_to_application_data(&ics, user).unwrap()
+    }
+
+    fn timed(subject: &str) -> ApplicationData {
+        ApplicationData {
+            subject: Some(subject.to_string()),
+            start_time: Some("20250602T090000Z".to_string()),
+            end_time: Some("20250602T100000Z".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn timed_event_round_trip() {
+        let mut data = timed("Planning; Q3, draft");
+        data.location = Some("Room 1".to_string());
+        data.body = Some(Body { r#type: 1, data: Some("Line one\nLine two".to_string()), ..Default::default() });
+        data.categories = Some(Categories { category: vec!["Work".to_string(), "Project".to_string()] });
+
+        let back = round_trip(&data, "alice");
+        assert_eq!(back.uid.as_deref(), Some("uid-1"));
+        assert_eq!(back.subject, data.subject);
+        assert_eq!(back.location, data.location);
+        assert_eq!(back.start_time, data.start_time);
+        assert_eq!(back.end_time, data.end_time);
+        assert_eq!(back.all_day_event, Some(false));
+        assert_eq!(back.body.and_then(|b| b.data).as_deref(), Some("Line one\nLine two"));
+        assert_eq!(back.categories.map(|c| c.category), Some(vec!["Work".to_string(), "Project".to_string()]));
+        assert_eq!(back.meeting_status, Some(MEETING_NONE));
+    }
+
+    #[test]
+    fn all_day_event_round_trip() {
+        let data = ApplicationData {
+            subject: Some("Holiday".to_string()),
+            start_time: Some("20251225T000000Z".to_string()),
+            end_time: Some("20251226T000000Z".to_string()),
+            all_day_event: Some(true),
+            ..Default::default()
+        };
+        let ics = application_data_to_ics(&data, "uid-1").unwrap();
+        assert!(ics.contains("DTSTART;VALUE=DATE:20251225"));
+
+        let back = ics_to_application_data(&ics, "alice").unwrap();
+        assert_eq!(back.all_day_event, Some(true));
+        assert_eq!(back.start_time, data.start_time);
+        assert_eq!(back.end_time, data.end_time);
+    }
+
+    #[test]
+    fn reminder_busy_status_and_sensitivity_round_trip() {
+        for (busy, sensitivity, reminder) in [(BUSY_FREE, SENSITIVITY_NORMAL, 0), (BUSY_TENTATIVE, SENSITIVITY_PRIVATE, 15), (BUSY_OOF, SENSITIVITY_CONFIDENTIAL, 1440), (BUSY_ELSEWHERE, SENSITIVITY_NORMAL, 5)] {
+            let mut data = timed("Focus");
+            data.busy_status = Some(busy);
+            data.sensitivity = Some(sensitivity);
+            data.reminder = Some(reminder);
+
+            let back = round_trip(&data, "alice");
+            assert_eq!(back.busy_status, Some(busy));
+            assert_eq!(back.sensitivity, Some(sensitivity));
+            assert_eq!(back.reminder, Some(reminder));
+        }
+    }
+
+    #[test]
+    fn meeting_round_trip() {
+        let mut data = timed("Review");
+        data.organizer_email = Some("alice@example.com".to_string());
+        data.organizer_name = Some("Smith, Alice".to_string());
+        data.attendees = Some(Attendees {
+            attendee: vec![
+                Attendee { email: "bob@example.com".to_string(), name: Some("Bob".to_string()), attendee_status: Some(ATTENDEE_ACCEPTED), attendee_type: Some(ATTENDEE_REQUIRED) },
+                Attendee { email: "carol@example.com".to_string(), name: None, attendee_status: Some(ATTENDEE_TENTATIVE), attendee_type: Some(ATTENDEE_OPTIONAL) },
+                Attendee { email: "room@example.com".to_string(), name: Some("Room 1".to_string()), attendee_status: Some(ATTENDEE_NOT_RESPONDED), attendee_type: Some(ATTENDEE_RESOURCE) },
+            ],
+        });
+
+        let back = round_trip(&data, "alice");
+        assert_eq!(back.organizer_email.as_deref(), Some("alice@example.com"));
+        assert_eq!(back.organizer_name.as_deref(), Some("Smith, Alice"));
+        assert_eq!(back.meeting_status, Some(MEETING_ORGANIZER));
+        assert_eq!(format!("{:?}", back.attendees), format!("{:?}", data.attendees));
+
+        assert_eq!(round_trip(&data, "bob@example.com").meeting_status, Some(MEETING_RECEIVED));
+
+        data.meeting_status = Some(MEETING_CANCELLED_RECEIVED);
+        assert_eq!(round_trip(&data, "bob").meeting_status, Some(MEETING_CANCELLED_RECEIVED));
+        assert_eq!(round_trip(&data, "alice").meeting_status, Some(MEETING_CANCELLED));
+    }
+
+    #[test]
+    fn weekly_recurrence_with_exceptions_round_trip() {
+        let mut data = timed("Standup");
+        data.recurrence = Some(Recurrence {
+            r#type: RECUR_WEEKLY,
+            interval: Some(2),
+            day_of_week: Some(2 | 8 | 32),
+            until: Some("20251231T090000Z".to_string()),
+            first_day_of_week: Some(1),
+            ..Default::default()
+        });
+        data.exceptions = Some(Exceptions {
+            exception: vec![
+                Exception { deleted: Some(true), exception_start_time: Some("20250604T090000Z".to_string()), ..Default::default() },
+                Exception {
+                    exception_start_time: Some("20250606T090000Z".to_string()),
+                    subject: Some("Standup (moved)".to_string()),
+                    start_time: Some("20250606T110000Z".to_string()),
+                    end_time: Some("20250606T113000Z".to_string()),
+                    ..Default::default()
+                },
+            ],
+        });
+
+        let back = round_trip(&data, "alice");
+        assert_eq!(format!("{:?}", back.recurrence), format!("{:?}", data.recurrence));
+        let exceptions = back.exceptions.unwrap().exception;
+        assert_eq!(exceptions.len(), 2);
+        assert_eq!(exceptions[0].deleted, Some(true));
+        assert_eq!(exceptions[0].exception_start_time.as_deref(), Some("20250604T090000Z"));
+        assert_eq!(exceptions[1].exception_start_time.as_deref(), Some("20250606T090000Z"));
+        assert_eq!(exceptions[1].subject.as_deref(), Some("Standup (moved)"));
+        assert_eq!(exceptions[1].start_time.as_deref(), Some("20250606T110000Z"));
+        assert_eq!(exceptions[1].end_time.as_deref(), Some("20250606T113000Z"));
+    }
+
+    #[test]
+    fn nth_weekday_recurrences_round_trip() {
+        for recurrence in [
+            Recurrence { r#type: RECUR_MONTHLY_NTH, week_of_month: Some(LAST_WEEK), day_of_week: Some(32), occurrences: Some(10), ..Default::default() },
+            Recurrence { r#type: RECUR_MONTHLY, day_of_month: Some(2), interval: Some(3), ..Default::default() },
+            Recurrence { r#type: RECUR_YEARLY_NTH, week_of_month: Some(2), day_of_week: Some(2), month_of_year: Some(6), ..Default::default() },
+            Recurrence { r#type: RECUR_YEARLY, day_of_month: Some(2), month_of_year: Some(6), ..Default::default() },
+            Recurrence { r#type: RECUR_DAILY, occurrences: Some(5), ..Default::default() },
+        ] {
+            let mut data = timed("Recurring");
+            data.recurrence = Some(recurrence);
+            let back = round_trip(&data, "alice");
+            assert_eq!(format!("{:?}", back.recurrence), format!("{:?}", data.recurrence));
+        }
+    }
+
+    #[test]
+    fn reads_by_day_rules() {
+        let start = parse_eas_date("20250602T080000Z").unwrap();
+        assert_eq!(by_day("MO,-1FR, 2tu"), [(None, Weekday::Mon), (Some(-1), Weekday::Fri), (Some(2), Weekday::Tue)]);
+        assert_eq!(by_day("é,1Мо,€"), []);
+
+        let r = recurrence("FREQ=MONTHLY;BYDAY=-1FR", start).unwrap();
+        assert_eq!((r.r#type, r.week_of_month, r.day_of_week), (RECUR_MONTHLY_NTH, Some(LAST_WEEK), Some(32)));
+        let r = recurrence("FREQ=WEEKLY;BYDAY=MO,WE", start).unwrap();
+        assert_eq!((r.r#type, r.day_of_week), (RECUR_WEEKLY, Some(10)));
+        assert!(recurrence("FREQ=MONTHLY;BYDAY=MO", start).is_none());
+        assert!(recurrence("FREQ=YEARLY;BYMONTH=6;BYDAY=MO", start).is_none());
+    }
+
+    #[test]
+    fn reads_by_month_day_rules() {
+        let start = parse_eas_date("20250602T080000Z").unwrap();
+        let r = recurrence("FREQ=MONTHLY", start).unwrap();
+        assert_eq!((r.r#type, r.day_of_month), (RECUR_MONTHLY, Some(2)));
+        let r = recurrence("FREQ=MONTHLY;BYMONTHDAY=15", start).unwrap();
+        assert_eq!((r.r#type, r.day_of_month), (RECUR_MONTHLY, Some(15)));
+        assert!(recurrence("FREQ=MONTHLY;BYMONTHDAY=-1", start).is_none());
+        assert!(recurrence("FREQ=MONTHLY;BYMONTHDAY=1,15", start).is_none());
+        assert!(recurrence("FREQ=YEARLY;BYMONTH=6;BYMONTHDAY=-1", start).is_none());
+    }
+
+    #[test]
+    fn reads_caldav_events() {
+        let ics = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\n\
+            BEGIN:VEVENT\r\nUID:abc\r\nDTSTAMP:20250101T000000Z\r\n\
+            DTSTART;TZID=Europe/Amsterdam:20250602T100000\r\nDTEND;TZID=Europe/Amsterdam:20250602T110000\r\n\
+            SUMMARY:Sprint review\r\nRRULE:FREQ=MONTHLY;BYDAY=MO;BYSETPOS=1\r\n\
+            EXDATE;TZID=Europe/Amsterdam:20250707T100000,20250804T100000\r\n\
+            ORGANIZER;CN=Bob:mailto:bob@example.com\r\n\
+            ATTENDEE;PARTSTAT=NEEDS-ACTION;ROLE=REQ-PARTICIPANT:mailto:alice@example.com\r\n\
+            BEGIN:VALARM\r\nACTION:DISPLAY\r\nDESCRIPTION:Reminder\r\nTRIGGER:-PT900S\r\nEND:VALARM\r\n\
+            END:VEVENT\r\nEND:VCALENDAR\r\n";
+        let data = ics_to_application_data(ics, "alice@example.com").unwrap();
+        assert_eq!(data.start_time.as_deref(), Some("20250602T080000Z"));
+        assert_eq!(data.end_time.as_deref(), Some("20250602T090000Z"));
+        assert_eq!(data.reminder, Some(15));
+        assert_eq!(data.meeting_status, Some(MEETING_RECEIVED));
+        assert_eq!(data.busy_status, Some(BUSY_BUSY));
+        let recurrence = data.recurrence.unwrap();
+        assert_eq!((recurrence.r#type, recurrence.week_of_month, recurrence.day_of_week), (RECUR_MONTHLY_NTH, Some(1), Some(2)));
+        let deleted: Vec<_> = data.exceptions.unwrap().exception.into_iter().filter_map(|e| e.exception_start_time).collect();
+        assert_eq!(deleted, ["20250707T080000Z", "20250804T080000Z"]);
+    }
+
+    #[test]
+    fn excludes_instances() {
+        let mut data = timed("Standup");
+        data.recurrence = Some(Recurrence { r#type: RECUR_DAILY, ..Default::default() });
+        let ics = application_data_to_ics(&data, "uid-1").unwrap();
+        let ics = exclude_instance(&ics, "2025-06-03T09:00:00.000Z").unwrap();
+        let exceptions = ics_to_application_data(&ics, "alice").unwrap().exceptions.unwrap().exception;
+        assert_eq!(exceptions[0].deleted, Some(true));
+        assert_eq!(exceptions[0].exception_start_time.as_deref(), Some("20250603T090000Z"));
+    }
+
+    const INVITATION: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//test//EN\r\n\
+        BEGIN:VEVENT\r\nUID:abc\r\nDTSTAMP:20250101T000000Z\r\nSEQUENCE:2\r\n\
+        DTSTART;TZID=Europe/Amsterdam:20250602T100000\r\nDTEND;TZID=Europe/Amsterdam:20250602T110000\r\n\
+        SUMMARY:Weekly sync\r\nRRULE:FREQ=WEEKLY\r\n\
+        ORGANIZER;CN=Bob:mailto:bob@example.com\r\n\
+        ATTENDEE;PARTSTAT=NEEDS-ACTION;RSVP=TRUE:mailto:alice@example.com\r\n\
+        ATTENDEE;PARTSTAT=ACCEPTED:mailto:carol@example.com\r\n\
+        END:VEVENT\r\nEND:VCALENDAR\r\n";
+
+    #[test]
+    fn accepts_invitations() {
+        let reply = respond_to_invitation(INVITATION, "alice", RESPONSE_ACCEPTED, None, Some("See you")).unwrap();
+        assert_eq!((reply.attendee.as_str(), reply.organizer.as_str()), ("alice@example.com", "bob@example.com"));
+        let attendees = ics_to_application_data(&reply.ics, "alice").unwrap().attendees.unwrap().attendee;
+        let status: Vec<_> = attendees.iter().map(|a| (a.email.as_str(), a.attendee_status)).collect();
+        assert!(status.contains(&("alice@example.com", Some(ATTENDEE_ACCEPTED))));
+        assert!(status.contains(&("carol@example.com", Some(ATTENDEE_ACCEPTED))));
+        assert!(!reply.ics.contains("RSVP"));
+        assert!(reply.reply.contains("METHOD:REPLY"));
+        assert!(reply.reply.contains("SEQUENCE:2"));
+        assert!(reply.reply.contains("COMMENT:See you"));
+        assert!(reply.reply.contains("PARTSTAT=ACCEPTED:mailto:alice@example.com"));
+        assert!(!reply.reply.contains("carol"));
+
+        assert!(respond_to_invitation(INVITATION, "bob", RESPONSE_ACCEPTED, None, None).is_err());
+        assert!(respond_to_invitation(INVITATION, "dave", RESPONSE_ACCEPTED, None, None).is_err());
+        assert!(respond_to_invitation(INVITATION, "alice", 9, None, None).is_err());
+    }
+
+    #[test]
+    fn declines_one_occurrence() {
+        let reply = respond_to_invitation(INVITATION, "alice", RESPONSE_DECLINED, Some("20250609T080000Z"), None).unwrap();
+        let data = ics_to_application_data(&reply.ics, "alice").unwrap();
+        let series = data.attendees.unwrap().attendee;
+        assert!(series.iter().any(|a| a.email == "alice@example.com" && a.attendee_status == Some(ATTENDEE_NOT_RESPONDED)));
+        assert!(reply.ics.contains("RECURRENCE-ID;TZID=Europe/Amsterdam:20250609T100000"));
+        assert!(reply.ics.contains("DTEND;TZID=Europe/Amsterdam:20250609T110000"));
+        assert_eq!(reply.ics.matches("RRULE").count(), 1);
+        assert!(reply.reply.contains("RECURRENCE-ID;TZID=Europe/Amsterdam:20250609T100000"));
+        assert!(reply.reply.contains("PARTSTAT=DECLINED"));
+        assert!(reply.reply.contains("BEGIN:VTIMEZONE") == reply.ics.contains("BEGIN:VTIMEZONE"));
+    }
+
+    #[test]
+    fn client_timezone_round_trip() {
+        let berlin = TimeZoneInfo::from_tz(Tz::Europe__Berlin, 2025);
+        let mut data = timed("Lunch");
+        data.timezone = Some(berlin.encode());
+        data.recurrence = Some(Recurrence { r#type: RECUR_DAILY, ..Default::default() });
+        data.exceptions = Some(Exceptions {
+            exception: vec![Exception { deleted: Some(true), exception_start_time: Some("20250603T090000Z".to_string()), ..Default::default() }],
+        });
+
+        let ics = application_data_to_ics(&data, "uid-1").unwrap();
+        assert!(ics.contains("BEGIN:VTIMEZONE"));
+        assert!(ics.contains("DTSTART;TZID=Europe/Berlin:20250602T110000"));
+        assert!(ics.contains("EXDATE;TZID=Europe/Berlin:20250603T110000"));
+
+        let back = ics_to_application_data(&ics, "alice").unwrap();
+        assert_eq!(back.start_time, data.start_time);
+        assert_eq!(back.exceptions.unwrap().exception[0].exception_start_time.as_deref(), Some("20250603T090000Z"));
+        assert_eq!(back.timezone.as_deref().map(|t| TimeZoneInfo::decode(t).unwrap()), Some(berlin));
+    }
+
+    #[test]
+    fn all_day_event_uses_client_local_date() {
+        let data = ApplicationData {
+            start_time: Some("20251224T230000Z".to_string()),
+            end_time: Some("20251225T230000Z".to_string()),
+            all_day_event: Some(true),
+            timezone: Some(TimeZoneInfo::from_tz(Tz::Europe__Berlin, 2025).encode()),
+            ..Default::default()
+        };
+        let ics = application_data_to_ics(&data, "uid-1").unwrap();
+        assert!(ics.contains("DTSTART;VALUE=DATE:20251225"));
+        assert!(ics.contains("DTEND;VALUE=DATE:20251226"));
+    }
+
+    #[test]
+    fn reads_custom_vtimezone() {
+        let zone = TimeZoneInfo::from_tz(Tz::America__New_York, 2025).to_vtimezone("Eastern");
+        let ics = format!("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{zone}BEGIN:VEVENT\r\nUID:abc\r\nDTSTAMP:20250101T000000Z\r\n\
+            DTSTART;TZID=Eastern:20250715T090000\r\nDTEND;TZID=Eastern:20250715T100000\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n");
+        let data = ics_to_application_data(&ics, "alice").unwrap();
+        assert_eq!(data.start_time.as_deref(), Some("20250715T130000Z"));
+        let timezone = TimeZoneInfo::decode(data.timezone.as_deref().unwrap()).unwrap();
+        assert_eq!(timezone.resolve(), Some(Tz::America__New_York));
+    }
+
+    #[test]
+    fn lists_attachments_and_keeps_them_on_change() {
+        let ics = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VEVENT\r\nUID:abc\r\nDTSTAMP:20250101T000000Z\r\n\
+            DTSTART:20250715T090000Z\r\nDTEND:20250715T100000Z\r\nDESCRIPTION:Full agenda\r\n\
+            ATTACH;FMTTYPE=text/plain;ENCODING=BASE64;VALUE=BINARY;X-FILENAME=notes.txt:aGVsbG8=\r\n\
+            ATTACH;FMTTYPE=application/pdf;SIZE=2048:https://dav.example.com/attachments/slides.pdf\r\n\
+            END:VEVENT\r\nEND:VCALENDAR\r\n";
+        let attachments = event_attachments(ics).unwrap();
+        assert_eq!(attachments.len(), 2);
+        assert_eq!(attachments[0].display_name, "notes.txt");
+        assert_eq!(attachments[0].content, AttachmentContent::Inline(b"hello".to_vec()));
+        assert_eq!(attachments[0].size, Some(5));
+        assert_eq!(attachments[1].display_name, "slides.pdf");
+        assert_eq!(attachments[1].content_type.as_deref(), Some("application/pdf"));
+        assert_eq!(attachments[1].size, Some(2048));
+
+        let changed = application_data_to_ics(&timed("Renamed"), "abc").unwrap();
+        let kept = keep_server_properties(&changed, ics, true).unwrap();
+        assert_eq!(event_attachments(&kept).unwrap(), attachments);
+        assert!(kept.contains("DESCRIPTION:Full agenda"));
+        assert!(!keep_server_properties(&changed, ics, false).unwrap().contains("DESCRIPTION"));
+    }
+
+    #[test]
+    fn merges_free_busy_periods() {
+        let ics = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:test\r\nBEGIN:VFREEBUSY\r\n\
+DTSTART:20250602T080000Z\r\nDTEND:20250602T120000Z\r\n\
+FREEBUSY:20250602T090000Z/20250602T100000Z\r\n\
+FREEBUSY;FBTYPE=BUSY-TENTATIVE:20250602T094500Z/PT45M,20250602T110000Z/PT15M\r\n\
+FREEBUSY;FBTYPE=BUSY-UNAVAILABLE:20250602T113000Z/20250602T130000Z\r\n\
+END:VFREEBUSY\r\nEND:VCALENDAR\r\n";
+        let start = parse_eas_date("20250602T080000Z").unwrap();
+        let end = parse_eas_date("20250602T120000Z").unwrap();
+        assert_eq!(merged_free_busy(ics, start, end).unwrap(), "00221013");
+        assert_eq!(merged_free_busy(ics, start, start + Duration::minutes(75)).unwrap(), "002");
+    }
+
+    #[test]
+    fn parses_durations_and_dates() {
+        assert_eq!(duration_minutes("-PT15M"), Some(-15));
+        assert_eq!(duration_minutes("-P1DT2H"), Some(-1560));
+        assert_eq!(duration_minutes("-P1W"), Some(-10080));
+        assert_eq!(duration_minutes("PT0S"), Some(0));
+        assert_eq!(duration_minutes("garbage"), None);
+        assert_eq!(parse_eas_date("2025-06-01T10:00:00.000Z"), parse_eas_date("20250601T100000Z"));
+    }
+}
@@ -0,0 +1,1117 @@
+use serde::{Deserialize, Serialize};
+use crate::wbxml_serde::Opaque;
+
+// Typed ActiveSync command models, (de)serialized to WBXML element trees by
+// `wbxml_serde`. Element names follow the serde names: root structs carry their
+// namespace, fields inherit the enclosing element's code page unless qualified.
+// Fields are declared in the order MS-ASCMD lists the elements.
+
+// ---------------------------------------------------------------------------
+// FolderSync (FolderHierarchy code page)
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "FolderHierarchy:FolderSync", rename_all = "PascalCase")]
+pub struct FolderSyncRequest {
+    pub sync_key: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "FolderHierarchy:FolderSync", rename_all = "PascalCase")]
+pub struct FolderSyncResponse {
+    pub status: u32,
+    pub sync_key: Option<String>,
+    pub changes: Option<FolderChanges>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FolderChanges {
+    pub count: u32,
+    #[serde(default)]
+    pub update: Vec<Folder>,
+    #[serde(default)]
+    pub delete: Vec<FolderDelete>,
+    #[serde(default)]
+    pub add: Vec<Folder>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Folder {
+    pub server_id: String,
+    pub parent_id: String,
+    pub display_name: String,
+    pub r#type: u32,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FolderDelete {
+    pub server_id: String,
+}
+
+// ---------------------------------------------------------------------------
+// Sync (AirSync code page)
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "AirSync:Sync", rename_all = "PascalCase")]
+pub struct SyncRequest {
+    pub collections: Option<SyncRequestCollections>,
+    pub wait: Option<u32>,
+    pub heartbeat_interval: Option<u32>,
+    pub window_size: Option<u32>,
+    pub partial: Option<()>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SyncRequestCollections {
+    #[serde(default)]
+    pub collection: Vec<SyncRequestCollection>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SyncRequestCollection {
+    pub class: Option<String>,
+    pub sync_key: String,
+    pub collection_id: String,
+    pub deletes_as_moves: Option<bool>,
+    pub get_changes: Option<bool>,
+    pub window_size: Option<u32>,
+    #[serde(default)]
+    pub options: Vec<SyncOptions>,
+    pub commands: Option<ClientCommands>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SyncOptions {
+    pub class: Option<String>,
+    pub filter_type: Option<u8>,
+    #[serde(rename = "AirSyncBase:BodyPreference", default)]
+    pub body_preference: Vec<BodyPreference>,
+    pub conflict: Option<u8>,
+    #[serde(rename = "MIMESupport")]
+    pub mime_support: Option<u8>,
+    #[serde(rename = "MIMETruncation")]
+    pub mime_truncation: Option<u8>,
+    pub max_items: Option<u32>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BodyPreference {
+    pub r#type: u8,
+    pub truncation_size: Option<u32>,
+    pub all_or_none: Option<bool>,
+    pub preview: Option<u32>,
+}
+
+/// Commands a client uploads in a Sync request.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ClientCommands {
+    #[serde(default)]
+    pub add: Vec<ClientAdd>,
+    #[serde(default)]
+    pub change: Vec<ItemChange>,
+    #[serde(default)]
+    pub delete: Vec<ItemRef>,
+    #[serde(default)]
+    pub fetch: Vec<ItemRef>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ClientAdd {
+    pub class: Option<String>,
+    pub client_id: String,
+    pub application_data: ApplicationData,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ItemChange {
+    pub server_id: String,
+    pub application_data: ApplicationData,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ItemRef {
+    pub server_id: String,
+    /// Targets a single occurrence of a recurring series (16.0+).
+    #[serde(rename = "AirSyncBase:InstanceId")]
+    pub instance_id: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "AirSync:Sync", rename_all = "PascalCase")]
+pub struct SyncResponse {
+    pub status: Option<u32>,
+    pub collections: Option<SyncResponseCollections>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SyncResponseCollections {
+    #[serde(default)]
+    pub collection: Vec<SyncResponseCollection>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SyncResponseCollection {
+    pub class: Option<String>,
+    pub sync_key: String,
+    pub collection_id: String,
+    pub status: u32,
+    pub responses: Option<SyncResponses>,
+    pub more_available: Option<()>,
+    pub commands: Option<ServerCommands>,
+}
+
+/// Commands the server sends down in a Sync response.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ServerCommands {
+    #[serde(default)]
+    pub add: Vec<ServerItem>,
+    #[serde(default)]
+    pub change: Vec<ServerItem>,
+    #[serde(default)]
+    pub delete: Vec<ItemRef>,
+    #[serde(default)]
+    pub soft_delete: Vec<ItemRef>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ServerItem {
+    pub server_id: String,
+    pub application_data: ApplicationData,
+}
+
+/// Per-item results for the commands a client uploaded.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SyncResponses {
+    #[serde(default)]
+    pub change: Vec<ItemStatus>,
+    #[serde(default)]
+    pub add: Vec<AddResult>,
+    #[serde(default)]
+    pub delete: Vec<ItemStatus>,
+    #[serde(default)]
+    pub fetch: Vec<FetchResult>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct AddResult {
+    pub class: Option<String>,
+    pub client_id: String,
+    pub server_id: Option<String>,
+    pub status: u32,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ItemStatus {
+    pub server_id: String,
+    pub status: u32,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FetchResult {
+    pub server_id: String,
+    pub status: u32,
+    pub application_data: Option<ApplicationData>,
+}
+
+// ---------------------------------------------------------------------------
+// Calendar ApplicationData (Calendar and AirSyncBase code pages)
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ApplicationData {
+    #[serde(rename = "Calendar:Timezone")]
+    pub timezone: Option<String>,
+    #[serde(rename = "Calendar:AllDayEvent")]
+    pub all_day_event: Option<bool>,
+    #[serde(rename = "AirSyncBase:Body")]
+    pub body: Option<Body>,
+    /// Plain-text body used by protocol 2.5 in place of `AirSyncBase:Body`.
+    #[serde(rename = "Calendar:Body")]
+    pub legacy_body: Option<String>,
+    #[serde(rename = "Calendar:BodyTruncated")]
+    pub legacy_body_truncated: Option<bool>,
+    /// Files attached to the event (16.0+), fetched with ItemOperations.
+    #[serde(rename = "AirSyncBase:Attachments")]
+    pub attachments: Option<Attachments>,
+    #[serde(rename = "Calendar:BusyStatus")]
+    pub busy_status: Option<u8>,
+    #[serde(rename = "Calendar:OrganizerName")]
+    pub organizer_name: Option<String>,
+    #[serde(rename = "Calendar:OrganizerEmail")]
+    pub organizer_email: Option<String>,
+    #[serde(rename = "Calendar:DtStamp")]
+    pub dt_stamp: Option<String>,
+    #[serde(rename = "Calendar:EndTime")]
+    pub end_time: Option<String>,
+    #[serde(rename = "Calendar:Location")]
+    pub location: Option<String>,
+    /// Structured location that replaces `Calendar:Location` in 16.0+.
+    #[serde(rename = "AirSyncBase:Location")]
+    pub structured_location: Option<Location>,
+    #[serde(rename = "Calendar:Reminder")]
+    pub reminder: Option<u32>,
+    #[serde(rename = "Calendar:Sensitivity")]
+    pub sensitivity: Option<u8>,
+    #[serde(rename = "Calendar:Subject")]
+    pub subject: Option<String>,
+    #[serde(rename = "Calendar:StartTime")]
+    pub start_time: Option<String>,
+    #[serde(rename = "Calendar:UID")]
+    pub uid: Option<String>,
+    /// Client-generated UID, sent by 16.0+ clients instead of `Calendar:UID`.
+    #[serde(rename = "Calendar:ClientUid")]
+    pub client_uid: Option<String>,
+    /// Occurrence a 16.0+ client change applies to.
+    #[serde(rename = "AirSyncBase:InstanceId")]
+    pub instance_id: Option<String>,
+    #[serde(rename = "Calendar:MeetingStatus")]
+    pub meeting_status: Option<u8>,
+    #[serde(rename = "Calendar:Attendees")]
+    pub attendees: Option<Attendees>,
+    #[serde(rename = "Calendar:Categories")]
+    pub categories: Option<Categories>,
+    #[serde(rename = "Calendar:Recurrence")]
+    pub recurrence: Option<Recurrence>,
+    #[serde(rename = "Calendar:Exceptions")]
+    pub exceptions: Option<Exceptions>,
+    #[serde(rename = "Calendar:ResponseRequested")]
+    pub response_requested: Option<bool>,
+    #[serde(rename = "Calendar:DisallowNewTimeProposal")]
+    pub disallow_new_time_proposal: Option<bool>,
+    #[serde(rename = "Calendar:ResponseType")]
+    pub response_type: Option<u8>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Body {
+    pub r#type: u8,
+    pub estimated_data_size: Option<u32>,
+    pub truncated: Option<bool>,
+    pub data: Option<String>,
+    pub preview: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Attachments {
+    #[serde(default)]
+    pub attachment: Vec<Attachment>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Attachment {
+    pub display_name: Option<String>,
+    pub file_reference: String,
+    pub method: u8,
+    pub estimated_data_size: u32,
+    pub content_id: Option<String>,
+    pub content_location: Option<String>,
+    pub is_inline: Option<bool>,
+    pub content_type: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Location {
+    pub display_name: Option<String>,
+    pub annotation: Option<String>,
+    pub street: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>,
+    pub postal_code: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub location_uri: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Attendees {
+    #[serde(default)]
+    pub attendee: Vec<Attendee>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Attendee {
+    pub email: String,
+    pub name: Option<String>,
+    pub attendee_status: Option<u8>,
+    pub attendee_type: Option<u8>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Categories {
+    #[serde(default)]
+    pub category: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Recurrence {
+    pub r#type: u8,
+    pub occurrences: Option<u32>,
+    pub interval: Option<u32>,
+    pub week_of_month: Option<u8>,
+    pub day_of_week: Option<u8>,
+    pub month_of_year: Option<u8>,
+    pub until: Option<String>,
+    pub day_of_month: Option<u8>,
+    pub calendar_type: Option<u8>,
+    pub is_leap_month: Option<bool>,
+    pub first_day_of_week: Option<u8>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Exceptions {
+    #[serde(default)]
+    pub exception: Vec<Exception>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Exception {
+    pub deleted: Option<bool>,
+    /// Identifies the occurrence before 16.0; replaced by `InstanceId` since.
+    pub exception_start_time: Option<String>,
+    #[serde(rename = "AirSyncBase:InstanceId")]
+    pub instance_id: Option<String>,
+    pub subject: Option<String>,
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    pub location: Option<String>,
+    #[serde(rename = "AirSyncBase:Body")]
+    pub body: Option<Body>,
+    pub all_day_event: Option<bool>,
+    pub busy_status: Option<u8>,
+    pub reminder: Option<u32>,
+    pub sensitivity: Option<u8>,
+    pub meeting_status: Option<u8>,
+    pub attendees: Option<Attendees>,
+    pub dt_stamp: Option<String>,
+}
+
+// ---------------------------------------------------------------------------
+// GetItemEstimate (GetItemEstimate code page)
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "GetItemEstimate:GetItemEstimate", rename_all = "PascalCase")]
+pub struct GetItemEstimateRequest {
+    pub collections: EstimateRequestCollections,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EstimateRequestCollections {
+    #[serde(default)]
+    pub collection: Vec<EstimateRequestCollection>,
+}
+
+/// One collection to estimate. Protocol 12.1 and later send the filter in
+/// `Options`; 2.5 and 12.0 put `FilterType` directly in the collection.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EstimateRequestCollection {
+    #[serde(rename = "AirSync:SyncKey")]
+    pub sync_key: Option<String>,
+    pub class: Option<String>,
+    pub collection_id: String,
+    #[serde(rename = "AirSync:FilterType")]
+    pub filter_type: Option<u8>,
+    #[serde(rename = "AirSync:Options", default)]
+    pub options: Vec<SyncOptions>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "GetItemEstimate:GetItemEstimate", rename_all = "PascalCase")]
+pub struct GetItemEstimateResponse {
+    #[serde(default)]
+    pub response: Vec<EstimateResponse>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EstimateResponse {
+    pub status: u32,
+    pub collection: Option<EstimateResponseCollection>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EstimateResponseCollection {
+    pub collection_id: String,
+    pub estimate: u32,
+}
+
+// ---------------------------------------------------------------------------
+// ItemOperations (ItemOperations code page)
+// ---------------------------------------------------------------------------
+
+/// Only Fetch is supported; EmptyFolderContents and Move are ignored.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "ItemOperations:ItemOperations", rename_all = "PascalCase")]
+pub struct ItemOperationsRequest {
+    #[serde(default)]
+    pub fetch: Vec<ItemOperationsFetch>,
+}
+
+/// Fetch of an item (`CollectionId` and `ServerId`, or a Search result's
+/// `LongId`) or of an attachment (`FileReference`).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ItemOperationsFetch {
+    pub store: String,
+    #[serde(rename = "AirSync:CollectionId")]
+    pub collection_id: Option<String>,
+    #[serde(rename = "AirSync:ServerId")]
+    pub server_id: Option<String>,
+    #[serde(rename = "Search:LongId")]
+    pub long_id: Option<String>,
+    #[serde(rename = "AirSyncBase:FileReference")]
+    pub file_reference: Option<String>,
+    pub options: Option<FetchOptions>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FetchOptions {
+    /// Byte range of an attachment, `first-last` inclusive.
+    pub range: Option<String>,
+    #[serde(rename = "AirSyncBase:BodyPreference", default)]
+    pub body_preference: Vec<BodyPreference>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "ItemOperations:ItemOperations", rename_all = "PascalCase")]
+pub struct ItemOperationsResponse {
+    pub status: u32,
+    pub response: Option<ItemOperationsResults>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ItemOperationsResults {
+    #[serde(default)]
+    pub fetch: Vec<FetchResponse>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FetchResponse {
+    pub status: u32,
+    #[serde(rename = "AirSync:CollectionId")]
+    pub collection_id: Option<String>,
+    #[serde(rename = "AirSync:ServerId")]
+    pub server_id: Option<String>,
+    #[serde(rename = "Search:LongId")]
+    pub long_id: Option<String>,
+    #[serde(rename = "AirSyncBase:FileReference")]
+    pub file_reference: Option<String>,
+    #[serde(rename = "AirSync:Class")]
+    pub class: Option<String>,
+    pub properties: Option<FetchProperties>,
+}
+
+/// What a Fetch returns: the fields of an item, or the data of an attachment.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FetchProperties {
+    Item(Box<ApplicationData>),
+    Attachment(AttachmentProperties),
+}
+
+/// Attachment data, inline as OPAQUE or, in a multipart response, as the
+/// number of the `Part` that carries it.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct AttachmentProperties {
+    #[serde(rename = "AirSyncBase:ContentType")]
+    pub content_type: Option<String>,
+    pub range: Option<String>,
+    pub total: Option<u32>,
+    pub data: Option<Opaque>,
+    pub part: Option<u32>,
+}
+
+// ---------------------------------------------------------------------------
+// MeetingResponse (MeetingResponse code page)
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "MeetingResponse:MeetingResponse", rename_all = "PascalCase")]
+pub struct MeetingResponseRequest {
+    #[serde(default)]
+    pub request: Vec<MeetingResponseItem>,
+}
+
+/// One answer to a meeting. `RequestId` is the ServerId of the event in the
+/// calendar folder `CollectionId`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MeetingResponseItem {
+    pub user_response: u8,
+    pub collection_id: Option<String>,
+    pub request_id: String,
+    pub instance_id: Option<String>,
+    /// 16.0+: present when the organizer should be told.
+    pub send_response: Option<MeetingSendResponse>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MeetingSendResponse {
+    #[serde(rename = "AirSyncBase:Body")]
+    pub body: Option<Body>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "MeetingResponse:MeetingResponse", rename_all = "PascalCase")]
+pub struct MeetingResponseResponse {
+    #[serde(default)]
+    pub result: Vec<MeetingResponseResult>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MeetingResponseResult {
+    pub request_id: String,
+    pub status: u32,
+    pub calendar_id: Option<String>,
+}
+
+// ---------------------------------------------------------------------------
+// Ping (Ping code page)
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "Ping:Ping", rename_all = "PascalCase")]
+pub struct PingRequest {
+    pub heartbeat_interval: Option<u32>,
+    pub folders: Option<PingFolders>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PingFolders {
+    #[serde(default)]
+    pub folder: Vec<PingFolder>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PingFolder {
+    pub id: String,
+    pub class: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "Ping:Ping", rename_all = "PascalCase")]
+pub struct PingResponse {
+    pub status: u32,
+    pub folders: Option<PingChangedFolders>,
+    pub max_folders: Option<u32>,
+    pub heartbeat_interval: Option<u32>,
+}
+
+/// Ids of the folders with changes, listed as `<Folder>` text elements.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PingChangedFolders {
+    #[serde(default)]
+    pub folder: Vec<String>,
+}
+
+// ---------------------------------------------------------------------------
+// Provision (Provision code page)
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "Provision:Provision", rename_all = "PascalCase")]
+pub struct ProvisionRequest {
+    /// 14.1+ devices report themselves during the handshake.
+    #[serde(rename = "Settings:DeviceInformation")]
+    pub device_information: Option<DeviceInformationRequest>,
+    pub policies: Option<ProvisionPolicies>,
+    pub remote_wipe: Option<WipeAcknowledgment>,
+    pub account_only_remote_wipe: Option<WipeAcknowledgment>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "Provision:Provision", rename_all = "PascalCase")]
+pub struct ProvisionResponse {
+    pub status: u32,
+    #[serde(rename = "Settings:DeviceInformation")]
+    pub device_information: Option<SettingStatus>,
+    pub policies: Option<ProvisionPolicies>,
+    /// Present (empty) to order a wipe of the whole device.
+    pub remote_wipe: Option<()>,
+    /// Present (empty) to order removal of the account's data only (16.1).
+    pub account_only_remote_wipe: Option<()>,
+}
+
+/// A device's report that it carried out a wipe.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct WipeAcknowledgment {
+    pub status: u32,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ProvisionPolicies {
+    pub policy: ProvisionPolicy,
+}
+
+/// A policy request, acknowledgment or response. Requests carry only the type;
+/// acknowledgments add the temporary key and the client's status.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ProvisionPolicy {
+    pub policy_type: String,
+    pub status: Option<u32>,
+    pub policy_key: Option<String>,
+    pub data: Option<PolicyData>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PolicyData {
+    #[serde(rename = "EASProvisionDoc")]
+    pub eas_provision_doc: EasProvisionDoc,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EasProvisionDoc {
+    pub device_password_enabled: bool,
+    pub alphanumeric_device_password_required: Option<bool>,
+    pub require_storage_card_encryption: Option<bool>,
+    pub min_device_password_length: Option<u32>,
+    pub max_inactivity_time_device_lock: Option<u32>,
+    pub max_device_password_failed_attempts: Option<u32>,
+    pub allow_simple_device_password: Option<bool>,
+    pub require_device_encryption: Option<bool>,
+}
+
+// ---------------------------------------------------------------------------
+// Settings (Settings code page)
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "Settings:Settings", rename_all = "PascalCase")]
+pub struct SettingsRequest {
+    pub oof: Option<OofRequest>,
+    pub device_information: Option<DeviceInformationRequest>,
+    pub user_information: Option<UserInformationRequest>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct OofRequest {
+    pub get: Option<OofGet>,
+    pub set: Option<OofSettings>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct OofGet {
+    /// `Text` or `HTML`: the format the client wants the replies in.
+    pub body_type: String,
+}
+
+/// Out-of-office state: what a client sets, and what a Get returns.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct OofSettings {
+    pub oof_state: Option<u8>,
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    #[serde(default)]
+    pub oof_message: Vec<OofMessage>,
+}
+
+/// The reply for one audience, named by which `AppliesTo...` flag is present.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct OofMessage {
+    pub applies_to_internal: Option<()>,
+    pub applies_to_external_known: Option<()>,
+    pub applies_to_external_unknown: Option<()>,
+    pub enabled: Option<bool>,
+    pub reply_message: Option<String>,
+    pub body_type: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DeviceInformationRequest {
+    pub set: DeviceInformationSet,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DeviceInformationSet {
+    pub model: Option<String>,
+    #[serde(rename = "IMEI")]
+    pub imei: Option<String>,
+    pub friendly_name: Option<String>,
+    #[serde(rename = "OS")]
+    pub os: Option<String>,
+    #[serde(rename = "OSLanguage")]
+    pub os_language: Option<String>,
+    pub phone_number: Option<String>,
+    pub user_agent: Option<String>,
+    #[serde(rename = "EnableOutboundSMS")]
+    pub enable_outbound_sms: Option<bool>,
+    pub mobile_operator: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UserInformationRequest {
+    pub get: Option<()>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "Settings:Settings", rename_all = "PascalCase")]
+pub struct SettingsResponse {
+    pub status: u32,
+    pub oof: Option<OofResponse>,
+    pub device_information: Option<SettingStatus>,
+    pub user_information: Option<UserInformationResponse>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct OofResponse {
+    pub status: u32,
+    pub get: Option<OofSettings>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SettingStatus {
+    pub status: u32,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UserInformationResponse {
+    pub status: u32,
+    pub get: Option<UserInformation>,
+}
+
+/// The user's addresses: a flat list before 14.1, per account since.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UserInformation {
+    pub email_addresses: Option<EmailAddresses>,
+    pub accounts: Option<UserAccounts>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EmailAddresses {
+    #[serde(default)]
+    pub smtp_address: Vec<String>,
+    pub primary_smtp_address: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UserAccounts {
+    #[serde(default)]
+    pub account: Vec<UserAccount>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UserAccount {
+    pub account_id: Option<String>,
+    pub account_name: Option<String>,
+    pub user_display_name: Option<String>,
+    pub send_disabled: Option<bool>,
+    pub email_addresses: EmailAddresses,
+}
+
+// ---------------------------------------------------------------------------
+// ResolveRecipients (ResolveRecipients code page)
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "ResolveRecipients:ResolveRecipients", rename_all = "PascalCase")]
+pub struct ResolveRecipientsRequest {
+    #[serde(default)]
+    pub to: Vec<String>,
+    pub options: Option<ResolveRecipientsOptions>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ResolveRecipientsOptions {
+    /// 1 none, 2 full certificates, 3 mini certificates.
+    pub certificate_retrieval: Option<u8>,
+    pub max_certificates: Option<u32>,
+    pub max_ambiguous_recipients: Option<u32>,
+    pub availability: Option<AvailabilityRequest>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct AvailabilityRequest {
+    pub start_time: String,
+    pub end_time: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "ResolveRecipients:ResolveRecipients", rename_all = "PascalCase")]
+pub struct ResolveRecipientsResponse {
+    pub status: u32,
+    /// One per `To` of the request.
+    #[serde(default)]
+    pub response: Vec<RecipientResponse>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RecipientResponse {
+    pub to: String,
+    pub status: u32,
+    pub recipient_count: u32,
+    #[serde(default)]
+    pub recipient: Vec<Recipient>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Recipient {
+    /// 1 for a GAL entry, 2 for a contact.
+    pub r#type: u8,
+    pub display_name: Option<String>,
+    pub email_address: String,
+    pub availability: Option<Availability>,
+    pub certificates: Option<Certificates>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Availability {
+    pub status: u32,
+    pub merged_free_busy: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Certificates {
+    pub status: u32,
+    pub certificate_count: u32,
+}
+
+// ---------------------------------------------------------------------------
+// Search (Search code page)
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "Search:Search", rename_all = "PascalCase")]
+pub struct SearchRequest {
+    pub store: SearchStore,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SearchStore {
+    /// `GAL`, `Mailbox` or `DocumentLibrary`.
+    pub name: String,
+    pub query: SearchQuery,
+    pub options: Option<SearchOptions>,
+}
+
+/// The GAL is searched by the Query's text, the mailbox by an `And` of
+/// conditions.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SearchQuery {
+    #[serde(rename = "$text")]
+    pub text: Option<String>,
+    pub and: Option<SearchAnd>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SearchAnd {
+    #[serde(rename = "AirSync:Class", default)]
+    pub class: Vec<String>,
+    #[serde(rename = "AirSync:CollectionId")]
+    pub collection_id: Option<String>,
+    pub free_text: Option<String>,
+    pub greater_than: Option<SearchComparison>,
+    pub less_than: Option<SearchComparison>,
+}
+
+/// A date bound: `Email:DateReceived` compared with `Value`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SearchComparison {
+    #[serde(rename = "Email:DateReceived")]
+    pub date_received: Option<()>,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SearchOptions {
+    /// Results to return, `first-last` inclusive.
+    pub range: Option<String>,
+    pub deep_traversal: Option<()>,
+    pub rebuild_results: Option<()>,
+    #[serde(rename = "AirSyncBase:BodyPreference", default)]
+    pub body_preference: Vec<BodyPreference>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "Search:Search", rename_all = "PascalCase")]
+pub struct SearchResponse {
+    pub status: u32,
+    pub response: Option<SearchResults>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SearchResults {
+    pub store: SearchResponseStore,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SearchResponseStore {
+    pub status: u32,
+    /// One per match; a search without matches has a single empty Result.
+    #[serde(default)]
+    pub result: Vec<SearchResult>,
+    pub range: Option<String>,
+    pub total: Option<u32>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SearchResult {
+    #[serde(rename = "AirSync:Class")]
+    pub class: Option<String>,
+    pub long_id: Option<String>,
+    #[serde(rename = "AirSync:CollectionId")]
+    pub collection_id: Option<String>,
+    pub properties: Option<SearchProperties>,
+}
+
+/// What a Search result holds: the fields of an item, or a GAL entry.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SearchProperties {
+    Item(Box<ApplicationData>),
+    Gal(GalProperties),
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct GalProperties {
+    #[serde(rename = "GAL:DisplayName")]
+    pub display_name: Option<String>,
+    #[serde(rename = "GAL:Alias")]
+    pub alias: Option<String>,
+    #[serde(rename = "GAL:EmailAddress")]
+    pub email_address: Option<String>,
+}
+
+// ---------------------------------------------------------------------------
+// Find (Find code page, 16.1+)
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "Find:Find", rename_all = "PascalCase")]
+pub struct FindRequest {
+    pub search_id: String,
+    pub execute_search: ExecuteSearch,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ExecuteSearch {
+    #[serde(rename = "MailBoxSearchCriterion")]
+    pub mailbox_search_criterion: Option<FindCriterion>,
+    pub gal_search_criterion: Option<FindCriterion>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FindCriterion {
+    pub query: FindQuery,
+    pub options: Option<FindOptions>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FindQuery {
+    #[serde(rename = "AirSync:Class")]
+    pub class: Option<String>,
+    #[serde(rename = "AirSync:CollectionId")]
+    pub collection_id: Option<String>,
+    pub free_text: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FindOptions {
+    pub range: Option<String>,
+    pub deep_traversal: Option<()>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename = "Find:Find", rename_all = "PascalCase")]
+pub struct FindResponse {
+    pub status: u32,
+    pub response: Option<FindResponseStore>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FindResponseStore {
+    #[serde(rename = "ItemOperations:Store")]
+    pub store: String,
+    pub status: u32,
+    #[serde(default)]
+    pub result: Vec<FindResult>,
+    pub range: Option<String>,
+    pub total: Option<u32>,
+}
+
+/// One match of a GAL Find.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FindResult {
+    pub properties: Option<GalProperties>,
+}
@@ -0,0 +1,309 @@
+use anyhow::{anyhow, bail, Result};
+use base64::Engine;
+use std::fmt;
+
+/// An ActiveSync protocol version such as 2.5 or 14.1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProtocolVersion {
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl ProtocolVersion {
+    pub const V2_5: Self = Self::new(2, 5);
+    pub const V12_0: Self = Self::new(12, 0);
+    pub const V12_1: Self = Self::new(12, 1);
+    pub const V14_0: Self = Self::new(14, 0);
+    pub const V14_1: Self = Self::new(14, 1);
+    pub const V16_0: Self = Self::new(16, 0);
+    pub const V16_1: Self = Self::new(16, 1);
+
+    /// Every version the gateway can speak, lowest first.
+    pub const SUPPORTED: &'static [Self] = &[
+        Self::V2_5, Self::V12_0, Self::V12_1, Self::V14_0, Self::V14_1, Self::V16_0, Self::V16_1,
+    ];
+
+    pub const fn new(major: u8, minor: u8) -> Self {
+        Self { major, minor }
+    }
+
+    /// Parse the dotted form used in headers, e.g. `14.1`.
+    pub fn parse(s: &str) -> Result<Self> {
+        let (major, minor) = s.trim().split_once('.').ok_or_else(|| anyhow!("invalid protocol version {:?}", s))?;
+        Ok(Self::new(major.parse()?, minor.parse()?))
+    }
+
+    /// Whether this version uses AirSyncBase bodies instead of per-class Body elements.
+    pub fn has_airsyncbase(self) -> bool {
+        self >= Self::V12_0
+    }
+
+    /// Whether this version addresses recurrence instances by `InstanceId` (16.x).
+    pub fn has_instance_ids(self) -> bool {
+        self >= Self::V16_0
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Parameters from the ActiveSync request line (MS-ASHTTP 2.2.1.1.1).
+///
+/// Clients send them either as a plain query string
+/// (`?Cmd=Sync&User=jdoe&DeviceId=ABC123&DeviceType=iPhone`) or as a single
+/// base64-encoded binary blob that also carries the protocol version, locale and
+/// policy key otherwise sent as headers.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RequestLine {
+    pub cmd: String,
+    pub user: String,
+    pub device_id: String,
+    pub device_type: String,
+    /// Only present in the base64 form; plain requests use `MS-ASProtocolVersion`.
+    pub protocol_version: Option<ProtocolVersion>,
+    /// Only present in the base64 form; plain requests use `X-MS-PolicyKey`.
+    pub policy_key: Option<u32>,
+    pub locale: Option<u16>,
+    pub collection_id: Option<String>,
+    pub item_id: Option<String>,
+    pub long_id: Option<String>,
+    pub attachment_name: Option<String>,
+    pub occurrence: Option<String>,
+    pub save_in_sent: bool,
+    pub accept_multipart: bool,
+}
+
+/// Command names by their code in the base64 request line (MS-ASHTTP 2.2.1.1.1.1.2).
+const COMMAND_CODES: &[(u8, &str)] = &[
+    (0, "Sync"), (1, "SendMail"), (2, "SmartForward"), (3, "SmartReply"), (4, "GetAttachment"),
+    (9, "FolderSync"), (10, "FolderCreate"), (11, "FolderDelete"), (12, "FolderUpdate"),
+    (13, "MoveItems"), (14, "GetItemEstimate"), (15, "MeetingResponse"), (16, "Search"),
+    (17, "Settings"), (18, "Ping"), (19, "ItemOperations"), (20, "Provision"),
+    (21, "ResolveRecipients"), (22, "ValidateCert"),
+];
+
+// Command parameter tags in the base64 request line (MS-ASHTTP 2.2.1.1.1.1.3)
+const PARAM_ATTACHMENT_NAME: u8 = 0;
+const PARAM_COLLECTION_ID: u8 = 1;
+const PARAM_ITEM_ID: u8 = 3;
+const PARAM_LONG_ID: u8 = 4;
+const PARAM_OCCURRENCE: u8 = 6;
+const PARAM_OPTIONS: u8 = 7;
+const PARAM_USER: u8 = 8;
+
+// Bits of the Options parameter
+const OPTION_SAVE_IN_SENT: u8 = 0x01;
+const OPTION_ACCEPT_MULTIPART: u8 = 0x02;
+
+impl RequestLine {
+    /// Parse the raw query string of an ActiveSync request in either encoding.
+    pub fn parse(query: &str) -> Result<Self> {
+        // Base64 only ever carries '=' as trailing padding
+        if query.trim_end_matches('=').contains('=') {
+            Self::parse_plain(query)
+        } else {
+            Self::parse_base64(query)
+        }
+    }
+
+    fn parse_plain(query: &str) -> Result<Self> {
+        let mut line = RequestLine::default();
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode(&value.replace('+', " "))?;
+            match key {
+                "Cmd" => line.cmd = value,
+                "User" => line.user = value,
+                "DeviceId" => line.device_id = value,
+                "DeviceType" => line.device_type = value,
+                "CollectionId" => line.collection_id = Some(value),
+                "ItemId" => line.item_id = Some(value),
+                "LongId" => line.long_id = Some(value),
+                "AttachmentName" => line.attachment_name = Some(value),
+                "Occurrence" => line.occurrence = Some(value),
+                "SaveInSent" => line.save_in_sent = value.eq_ignore_ascii_case("T"),
+                _ => {}
+            }
+        }
+        if line.cmd.is_empty() {
+            bail!("missing Cmd parameter");
+        }
+        if line.user.is_empty() {
+            bail!("missing User parameter");
+        }
+        if line.device_id.is_empty() {
+            bail!("missing DeviceId parameter");
+        }
+        Ok(line)
+    }
+
+    fn parse_base64(query: &str) -> Result<Self> {
+        // '+' is a base64 digit here, not an encoded space
+        let encoded = percent_decode(query)?;
+        let bytes = base64::engine::general_purpose::STANDARD_NO_PAD.decode(encoded.trim_end_matches('='))
+            .map_err(|e| anyhow!("invalid base64 request line: {}", e))?;
+        let mut r = bytes.iter().copied();
+        let mut next = || r.next().ok_or_else(|| anyhow!("truncated base64 request line"));
+
+        let mut line = RequestLine::default();
+        let version = next()?;
+        line.protocol_version = Some(ProtocolVersion::new(version / 10, version % 10));
+        let code = next()?;
+        line.cmd = COMMAND_CODES.iter().find(|(c, _)| *c == code).map(|(_, n)| n.to_string())
+            .ok_or_else(|| anyhow!("unknown command code {}", code))?;
+        line.locale = Some(u16::from_le_bytes([next()?, next()?]));
+
+        let id_len = next()? as usize;
+        let device_id = (0..id_len).map(|_| next()).collect::<Result<Vec<u8>>>()?;
+        if device_id.is_empty() {
+            bail!("missing DeviceId");
+        }
+        // Clients send the same DeviceId as in the plain form; binary ones are
+        // kept as hex
+        line.device_id = match String::from_utf8(device_id) {
+            Ok(id) => id,
+            Err(e) => e.as_bytes().iter().map(|b| format!("{:02x}", b)).collect(),
+        };
+
+        let key_len = next()? as usize;
+        let key = (0..key_len).map(|_| next()).collect::<Result<Vec<u8>>>()?;
+        if key_len == 4 {
+            line.policy_key = Some(u32::from_le_bytes([key[0], key[1], key[2], key[3]]));
+        }
+
+        let type_len = next()? as usize;
+        let device_type = (0..type_len).map(|_| next()).collect::<Result<Vec<u8>>>()?;
+        line.device_type = String::from_utf8(device_type)?;
+
+        while let Ok(tag) = next() {
+            let len = next()? as usize;
+            let value = (0..len).map(|_| next()).collect::<Result<Vec<u8>>>()?;
+            match tag {
+                PARAM_OPTIONS => {
+                    let bits = value.first().copied().unwrap_or(0);
+                    line.save_in_sent = bits & OPTION_SAVE_IN_SENT != 0;
+                    line.accept_multipart = bits & OPTION_ACCEPT_MULTIPART != 0;
+                }
+                _ => {
+                    let value = String::from_utf8(value)?;
+                    match tag {
+                        PARAM_ATTACHMENT_NAME => line.attachment_name = Some(value),
+                        PARAM_COLLECTION_ID => line.collection_id = Some(value),
+                        PARAM_ITEM_ID => line.item_id = Some(value),
+                        PARAM_LONG_ID => line.long_id = Some(value),
+                        PARAM_OCCURRENCE => line.occurrence = Some(value),
+                        PARAM_USER => line.user = value,
+                        _ => {}
+                    }
+                }
+            }
+        }
+        Ok(line)
+    }
+}
+
+/// Decode `%XX` escapes in a query component. Form encoding's `+` for space is
+/// left to the caller.
+fn percent_decode(s: &str) -> Result<String> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = s.get(i + 1..i + 3).ok_or_else(|| anyhow!("truncated percent escape"))?;
+                if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    bail!("invalid percent escape %{}", hex);
+                }
+                out.push(u8::from_str_radix(hex, 16)?);
+                i += 3;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    Ok(String::from_utf8(out)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::STANDARD;
+
+    /// A base64 request line: version 14.1, the command code, en-US, then the
+    /// device id, policy key, device type and `params` as (tag, value).
+    fn base64_line(code: u8, device_id: &[u8], policy_key: &[u8], params: &[(u8, &[u8])]) -> String {
+        let mut bytes = vec![141, code, 0x09, 0x04, device_id.len() as u8];
+        bytes.extend_from_slice(device_id);
+        bytes.push(policy_key.len() as u8);
+        bytes.extend_from_slice(policy_key);
+        bytes.push(6);
+        bytes.extend_from_slice(b"iPhone");
+        for (tag, value) in params {
+            bytes.push(*tag);
+            bytes.push(value.len() as u8);
+            bytes.extend_from_slice(value);
+        }
+        STANDARD.encode(bytes)
+    }
+
+    #[test]
+    fn parses_plain_request_line() {
+        let line = RequestLine::parse("Cmd=Sync&User=j%40doe.example&DeviceId=ABC123&DeviceType=Windows+Phone&SaveInSent=T").unwrap();
+        assert_eq!(line.cmd, "Sync");
+        assert_eq!(line.user, "j@doe.example");
+        assert_eq!(line.device_id, "ABC123");
+        assert_eq!(line.device_type, "Windows Phone");
+        assert!(line.save_in_sent);
+        assert_eq!(line.protocol_version, None);
+
+        assert!(RequestLine::parse("User=jdoe&DeviceId=ABC123&DeviceType=iPhone").is_err());
+        assert!(RequestLine::parse("Cmd=Sync&DeviceId=ABC123&DeviceType=iPhone").is_err());
+        assert!(RequestLine::parse("Cmd=Sync&User=jdoe&DeviceType=iPhone").is_err());
+        assert!(RequestLine::parse("Cmd=Sync&User=jdoe&DeviceId=&DeviceType=iPhone").is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_percent_escapes() {
+        assert_eq!(percent_decode("a%2Bb+c").unwrap(), "a+b+c");
+        assert!(percent_decode("%+1").is_err());
+        assert!(percent_decode("%-1").is_err());
+        assert!(percent_decode("%zz").is_err());
+        assert!(percent_decode("%4").is_err());
+    }
+
+    #[test]
+    fn parses_base64_request_line() {
+        let query = base64_line(19, b"Appl1234", &1234u32.to_le_bytes(), &[(PARAM_USER, b"jdoe"), (PARAM_LONG_ID, b"work:1.ics"), (PARAM_OPTIONS, &[OPTION_ACCEPT_MULTIPART])]);
+        let line = RequestLine::parse(&query).unwrap();
+        assert_eq!(line.protocol_version, Some(ProtocolVersion::V14_1));
+        assert_eq!(line.cmd, "ItemOperations");
+        assert_eq!(line.locale, Some(0x0409));
+        assert_eq!(line.device_id, "Appl1234");
+        assert_eq!(line.policy_key, Some(1234));
+        assert_eq!(line.device_type, "iPhone");
+        assert_eq!(line.user, "jdoe");
+        assert_eq!(line.long_id.as_deref(), Some("work:1.ics"));
+        assert!(line.accept_multipart);
+        assert!(!line.save_in_sent);
+    }
+
+    #[test]
+    fn base64_request_line_keeps_plus_and_slash() {
+        let query = base64_line(0, &[0xfb, 0xef, 0xff, 0xfe], &[], &[]);
+        assert!(query.contains('+') && query.contains('/'), "{}", query);
+        let line = RequestLine::parse(&query).unwrap();
+        assert_eq!(line.cmd, "Sync");
+        assert_eq!(line.device_id, "fbeffffe");
+        assert_eq!(line.policy_key, None);
+
+        let escaped = query.replace('+', "%2B").replace('/', "%2F").replace('=', "%3D");
+        assert_eq!(RequestLine::parse(&escaped).unwrap(), line);
+        assert!(RequestLine::parse(&base64_line(0, b"", &[], &[])).is_err());
+    }
+}
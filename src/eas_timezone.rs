@@ -0,0 +1,415 @@
+use anyhow::{Result, bail};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone, Timelike, Utc};
+use chrono_tz::{TZ_VARIANTS, Tz};
+use icalendar::Component;
+use lazy_static::lazy_static;
+
+/// Size of a TIME_ZONE_INFORMATION structure: bias, two 32-character UTF-16
+/// names, two SYSTEMTIMEs and two biases.
+const BLOB_LEN: usize = 172;
+const NAME_CHARS: usize = 32;
+
+/// Zones tried first when a blob carries no usable name, so common rules map to
+/// the zone users expect rather than the alphabetically first one.
+const PREFERRED_ZONES: &[&str] = &[
+    "Europe/London", "Europe/Berlin", "Europe/Amsterdam", "Europe/Paris", "Europe/Helsinki",
+    "Europe/Moscow", "America/New_York", "America/Chicago", "America/Denver", "America/Phoenix",
+    "America/Los_Angeles", "America/Anchorage", "America/Halifax", "America/Sao_Paulo",
+    "Pacific/Honolulu", "Asia/Dubai", "Asia/Kolkata", "Asia/Shanghai", "Asia/Singapore",
+    "Asia/Tokyo", "Australia/Perth", "Australia/Brisbane", "Australia/Adelaide", "Australia/Sydney",
+    "Pacific/Auckland",
+];
+
+/// RRULE weekday abbreviations in SYSTEMTIME order (Sunday = 0).
+const DAY_ABBR: [&str; 7] = ["SU", "MO", "TU", "WE", "TH", "FR", "SA"];
+
+lazy_static! {
+    /// Current-year rules of every IANA zone, in lookup preference order.
+    static ref ZONE_RULES: Vec<(Tz, TimeZoneInfo)> = {
+        let year = Utc::now().year();
+        let preferred = PREFERRED_ZONES.iter().filter_map(|name| name.parse::<Tz>().ok());
+        let fixed = TZ_VARIANTS.iter().copied().filter(|tz| tz.name().starts_with("Etc/GMT"));
+        let regional = TZ_VARIANTS.iter().copied().filter(|tz| {
+            let name = tz.name();
+            name.contains('/') && !name.starts_with("Etc/") && !PREFERRED_ZONES.contains(&name)
+        });
+        preferred.chain(fixed).chain(regional).map(|tz| (tz, TimeZoneInfo::from_tz(tz, year))).collect()
+    };
+}
+
+/// A Windows SYSTEMTIME as used in TIME_ZONE_INFORMATION. With `year` 0 it is a
+/// yearly rule: `day` is the occurrence (1-4, 5 = last) of `day_of_week` in `month`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SystemTime {
+    pub year: u16,
+    pub month: u16,
+    pub day_of_week: u16,
+    pub day: u16,
+    pub hour: u16,
+    pub minute: u16,
+    pub second: u16,
+    pub milliseconds: u16,
+}
+
+impl SystemTime {
+    fn read(b: &[u8]) -> Self {
+        let f = |i: usize| u16::from_le_bytes([b[i * 2], b[i * 2 + 1]]);
+        SystemTime { year: f(0), month: f(1), day_of_week: f(2), day: f(3), hour: f(4), minute: f(5), second: f(6), milliseconds: f(7) }
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        for v in [self.year, self.month, self.day_of_week, self.day, self.hour, self.minute, self.second, self.milliseconds] {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+
+    /// Yearly rule for a transition at local time `t`.
+    fn rule_for(t: NaiveDateTime) -> Self {
+        let last = t.day() + 7 > days_in_month(t.year(), t.month());
+        SystemTime {
+            month: t.month() as u16,
+            day_of_week: t.weekday().num_days_from_sunday() as u16,
+            day: if last { 5 } else { ((t.day() - 1) / 7 + 1) as u16 },
+            hour: t.hour() as u16,
+            minute: t.minute() as u16,
+            ..Default::default()
+        }
+    }
+
+    /// Local time of this transition in `year`.
+    fn in_year(&self, year: i32) -> Option<NaiveDateTime> {
+        let time = NaiveTime::from_hms_opt(self.hour as u32, self.minute as u32, self.second as u32)?;
+        if self.year != 0 {
+            return NaiveDate::from_ymd_opt(year, self.month as u32, self.day as u32).map(|d| d.and_time(time));
+        }
+        let first = NaiveDate::from_ymd_opt(year, self.month as u32, 1)?;
+        let shift = (self.day_of_week as u32 + 7 - first.weekday().num_days_from_sunday()) % 7;
+        let mut day = 1 + shift + (self.day.clamp(1, 5) as u32 - 1) * 7;
+        while day > days_in_month(year, self.month as u32) {
+            day -= 7;
+        }
+        first.with_day(day).map(|d| d.and_time(time))
+    }
+
+    /// The rule as an RRULE, e.g. `FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU`.
+    fn rrule(&self) -> String {
+        let week = if self.day >= 5 { -1 } else { self.day as i32 };
+        format!("FREQ=YEARLY;BYMONTH={};BYDAY={}{}", self.month, week, DAY_ABBR[self.day_of_week as usize % 7])
+    }
+
+    fn same_rule(&self, other: &SystemTime) -> bool {
+        (self.month, self.day_of_week, self.day, self.hour, self.minute) == (other.month, other.day_of_week, other.day, other.hour, other.minute)
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let (y, m) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    NaiveDate::from_ymd_opt(y, m, 1).and_then(|d| d.pred_opt()).map(|d| d.day()).unwrap_or(31)
+}
+
+fn read_name(b: &[u8]) -> String {
+    let units: Vec<u16> = b.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).take_while(|u| *u != 0).collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn write_name(name: &str, out: &mut Vec<u8>) {
+    let mut units: Vec<u16> = name.encode_utf16().take(NAME_CHARS - 1).collect();
+    units.resize(NAME_CHARS, 0);
+    for u in units {
+        out.extend_from_slice(&u.to_le_bytes());
+    }
+}
+
+/// `+0100` style UTC offset for `minutes` east of UTC.
+fn format_offset(minutes: i32) -> String {
+    let sign = if minutes < 0 { '-' } else { '+' };
+    format!("{}{:02}{:02}", sign, minutes.abs() / 60, minutes.abs() % 60)
+}
+
+/// Minutes east of UTC in a `+0100` or `-023000` style offset.
+fn parse_offset(s: &str) -> Option<i32> {
+    let s = s.trim();
+    let (sign, digits) = match s.as_bytes().first()? {
+        b'-' => (-1, &s[1..]),
+        b'+' => (1, &s[1..]),
+        _ => (1, s),
+    };
+    let hours: i32 = digits.get(0..2)?.parse().ok()?;
+    let minutes: i32 = digits.get(2..4)?.parse().ok()?;
+    Some(sign * (hours * 60 + minutes))
+}
+
+/// Yearly transition rule of a VTIMEZONE STANDARD or DAYLIGHT observance.
+fn observance_rule(c: &impl Component) -> Option<SystemTime> {
+    let rrule = c.property_value("RRULE")?;
+    let start = NaiveDateTime::parse_from_str(c.property_value("DTSTART")?, "%Y%m%dT%H%M%S").ok()?;
+    let get = |k: &str| rrule.split(';').filter_map(|p| p.split_once('=')).find(|(key, _)| key.eq_ignore_ascii_case(k)).map(|(_, v)| v);
+    let month: u16 = get("BYMONTH")?.parse().ok()?;
+    let by_day = get("BYDAY")?;
+    let split = by_day.len().checked_sub(2)?;
+    let day_of_week = DAY_ABBR.iter().position(|d| by_day[split..].eq_ignore_ascii_case(d))? as u16;
+    let week: i32 = by_day[..split].parse().unwrap_or(1);
+    Some(SystemTime {
+        month,
+        day_of_week,
+        day: if week < 0 { 5 } else { week.clamp(1, 5) as u16 },
+        hour: start.hour() as u16,
+        minute: start.minute() as u16,
+        ..Default::default()
+    })
+}
+
+/// The ActiveSync Calendar `Timezone` value: a base64 TIME_ZONE_INFORMATION
+/// structure. Biases are minutes west of UTC (UTC = local + bias).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TimeZoneInfo {
+    pub bias: i32,
+    pub standard_name: String,
+    pub standard_date: SystemTime,
+    pub standard_bias: i32,
+    pub daylight_name: String,
+    pub daylight_date: SystemTime,
+    pub daylight_bias: i32,
+}
+
+impl TimeZoneInfo {
+    pub fn decode(blob: &str) -> Result<Self> {
+        let b = STANDARD.decode(blob.trim())?;
+        if b.len() < BLOB_LEN {
+            bail!("Timezone blob is {} bytes, expected {}", b.len(), BLOB_LEN);
+        }
+        let i32_at = |i: usize| i32::from_le_bytes([b[i], b[i + 1], b[i + 2], b[i + 3]]);
+        Ok(TimeZoneInfo {
+            bias: i32_at(0),
+            standard_name: read_name(&b[4..68]),
+            standard_date: SystemTime::read(&b[68..84]),
+            standard_bias: i32_at(84),
+            daylight_name: read_name(&b[88..152]),
+            daylight_date: SystemTime::read(&b[152..168]),
+            daylight_bias: i32_at(168),
+        })
+    }
+
+    pub fn encode(&self) -> String {
+        let mut out = Vec::with_capacity(BLOB_LEN);
+        out.extend_from_slice(&self.bias.to_le_bytes());
+        write_name(&self.standard_name, &mut out);
+        self.standard_date.write(&mut out);
+        out.extend_from_slice(&self.standard_bias.to_le_bytes());
+        write_name(&self.daylight_name, &mut out);
+        self.daylight_date.write(&mut out);
+        out.extend_from_slice(&self.daylight_bias.to_le_bytes());
+        STANDARD.encode(out)
+    }
+
+    pub fn has_dst(&self) -> bool {
+        self.standard_date.month != 0 && self.daylight_date.month != 0 && self.daylight_bias != self.standard_bias
+    }
+
+    /// True when the zone is UTC all year, as clients send for floating items.
+    pub fn is_utc(&self) -> bool {
+        self.standard_offset() == 0 && !self.has_dst()
+    }
+
+    /// Minutes east of UTC outside daylight saving time.
+    fn standard_offset(&self) -> i32 {
+        -(self.bias + self.standard_bias)
+    }
+
+    fn daylight_offset(&self) -> i32 {
+        -(self.bias + self.daylight_bias)
+    }
+
+    fn is_daylight(&self, local: NaiveDateTime) -> bool {
+        if !self.has_dst() {
+            return false;
+        }
+        let (Some(dst_start), Some(dst_end)) = (self.daylight_date.in_year(local.year()), self.standard_date.in_year(local.year())) else {
+            return false;
+        };
+        if dst_start < dst_end {
+            local >= dst_start && local < dst_end
+        } else {
+            local >= dst_start || local < dst_end
+        }
+    }
+
+    pub fn to_utc(&self, local: NaiveDateTime) -> DateTime<Utc> {
+        let offset = if self.is_daylight(local) { self.daylight_offset() } else { self.standard_offset() };
+        (local - Duration::minutes(offset as i64)).and_utc()
+    }
+
+    pub fn to_local(&self, utc: DateTime<Utc>) -> NaiveDateTime {
+        let daylight = utc.naive_utc() + Duration::minutes(self.daylight_offset() as i64);
+        if self.is_daylight(daylight) {
+            daylight
+        } else {
+            utc.naive_utc() + Duration::minutes(self.standard_offset() as i64)
+        }
+    }
+
+    /// Rules of an IANA zone as they apply in `year`. The zone name goes in both
+    /// name fields so the zone can be recognised when a client echoes it back.
+    pub fn from_tz(tz: Tz, year: i32) -> Self {
+        let offset_at = |t: NaiveDateTime| tz.offset_from_utc_datetime(&t).fix().local_minus_utc() / 60;
+        let Some(start) = NaiveDate::from_ymd_opt(year, 1, 1).map(|d| d.and_time(NaiveTime::MIN)) else {
+            return TimeZoneInfo::default();
+        };
+
+        // (local time before the change, offset before, offset after)
+        let mut transitions = Vec::new();
+        let mut previous = offset_at(start);
+        let mut day = start;
+        while day.year() == year {
+            let next = day + Duration::days(1);
+            let offset = offset_at(next);
+            if offset != previous {
+                let (mut lo, mut hi) = (day, next);
+                while hi - lo > Duration::minutes(1) {
+                    let mid = lo + (hi - lo) / 2;
+                    if offset_at(mid) == previous { lo = mid } else { hi = mid }
+                }
+                transitions.push((hi + Duration::minutes(previous as i64), previous, offset));
+                previous = offset;
+            }
+            day = next;
+        }
+
+        let name = tz.name().to_string();
+        let mut info = TimeZoneInfo { standard_name: name.clone(), daylight_name: name, ..Default::default() };
+        if let [a, b] = transitions[..] {
+            let standard = a.2.min(b.2);
+            let daylight = a.2.max(b.2);
+            let (to_daylight, to_standard) = if a.2 == daylight { (a, b) } else { (b, a) };
+            info.bias = -standard;
+            info.daylight_bias = -(daylight - standard);
+            info.daylight_date = SystemTime::rule_for(to_daylight.0);
+            info.standard_date = SystemTime::rule_for(to_standard.0);
+        } else {
+            info.bias = -previous;
+        }
+        info
+    }
+
+    /// Rules from a VTIMEZONE component, using the newest STANDARD and DAYLIGHT
+    /// observances. DST is only kept if both recur yearly.
+    pub fn from_vtimezone(vtimezone: &impl Component) -> Option<Self> {
+        let newest = |kind: &str| vtimezone.components().iter()
+            .filter(|c| c.component_kind().eq_ignore_ascii_case(kind))
+            .max_by_key(|c| c.property_value("DTSTART").unwrap_or_default().to_string());
+        let standard = newest("STANDARD").or_else(|| newest("DAYLIGHT"))?;
+        let standard_offset = parse_offset(standard.property_value("TZOFFSETTO")?)?;
+        let name = vtimezone.property_value("TZID").unwrap_or_default().to_string();
+        let mut info = TimeZoneInfo { bias: -standard_offset, standard_name: name.clone(), daylight_name: name, ..Default::default() };
+
+        let daylight = newest("DAYLIGHT").filter(|_| newest("STANDARD").is_some());
+        if let Some((daylight_date, daylight_offset)) = daylight.and_then(|d| Some((observance_rule(d)?, parse_offset(d.property_value("TZOFFSETTO")?)?)))
+            && let Some(standard_date) = observance_rule(standard)
+        {
+            info.standard_date = standard_date;
+            info.daylight_date = daylight_date;
+            info.daylight_bias = -(daylight_offset - standard_offset);
+        }
+        Some(info)
+    }
+
+    /// Whether two zones share offsets and transition rules, ignoring names.
+    fn same_rules(&self, other: &TimeZoneInfo) -> bool {
+        if self.standard_offset() != other.standard_offset() || self.has_dst() != other.has_dst() {
+            return false;
+        }
+        !self.has_dst() || (self.daylight_offset() == other.daylight_offset()
+            && self.standard_date.same_rule(&other.standard_date)
+            && self.daylight_date.same_rule(&other.daylight_date))
+    }
+
+    /// The IANA zone these rules describe: the zone named in the blob if its
+    /// rules agree, otherwise the first zone with the same current-year rules.
+    pub fn resolve(&self) -> Option<Tz> {
+        ZONE_RULES.iter()
+            .find(|(tz, rules)| tz.name() == self.standard_name && rules.same_rules(self))
+            .or_else(|| ZONE_RULES.iter().find(|(_, rules)| rules.same_rules(self)))
+            .map(|(tz, _)| *tz)
+    }
+
+    /// TZID to use for these rules in iCalendar: the IANA zone if one matches,
+    /// otherwise the client's name for the zone or its UTC offset.
+    pub fn tzid(&self) -> String {
+        if let Some(tz) = self.resolve() {
+            return tz.name().to_string();
+        }
+        let name = self.standard_name.trim();
+        if name.is_empty() { format!("UTC{}", format_offset(self.standard_offset())) } else { name.to_string() }
+    }
+
+    /// A VTIMEZONE component describing these rules.
+    pub fn to_vtimezone(&self, tzid: &str) -> String {
+        let mut out = format!("BEGIN:VTIMEZONE\r\nTZID:{}\r\n", tzid);
+        let standard = format_offset(self.standard_offset());
+        let daylight = format_offset(self.daylight_offset());
+        let observance = |kind: &str, rule: &SystemTime, from: &str, to: &str| {
+            let start = rule.in_year(1970).unwrap_or_default().format("%Y%m%dT%H%M%S");
+            format!("BEGIN:{kind}\r\nDTSTART:{start}\r\nRRULE:{}\r\nTZOFFSETFROM:{from}\r\nTZOFFSETTO:{to}\r\nEND:{kind}\r\n", rule.rrule())
+        };
+        if self.has_dst() {
+            out += &observance("STANDARD", &self.standard_date, &daylight, &standard);
+            out += &observance("DAYLIGHT", &self.daylight_date, &standard, &daylight);
+        } else {
+            out += &format!("BEGIN:STANDARD\r\nDTSTART:19700101T000000\r\nTZOFFSETFROM:{standard}\r\nTZOFFSETTO:{standard}\r\nEND:STANDARD\r\n");
+        }
+        out + "END:VTIMEZONE\r\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y%m%dT%H%M%S").unwrap()
+    }
+
+    #[test]
+    fn blob_round_trip() {
+        let info = TimeZoneInfo::from_tz(Tz::Europe__Berlin, 2025);
+        assert_eq!(info.bias, -60);
+        assert_eq!(info.daylight_bias, -60);
+        assert_eq!(info.daylight_date, SystemTime { month: 3, day_of_week: 0, day: 5, hour: 2, ..Default::default() });
+        assert_eq!(info.standard_date, SystemTime { month: 10, day_of_week: 0, day: 5, hour: 3, ..Default::default() });
+
+        let blob = info.encode();
+        assert_eq!(STANDARD.decode(&blob).unwrap().len(), BLOB_LEN);
+        assert_eq!(TimeZoneInfo::decode(&blob).unwrap(), info);
+        assert_eq!(info.resolve(), Some(Tz::Europe__Berlin));
+    }
+
+    #[test]
+    fn converts_local_times() {
+        let ny = TimeZoneInfo::from_tz(Tz::America__New_York, 2025);
+        assert_eq!(ny.daylight_date.day, 2);
+        assert_eq!(ny.to_utc(local("20250115T090000")).to_rfc3339(), "2025-01-15T14:00:00+00:00");
+        assert_eq!(ny.to_utc(local("20250715T090000")).to_rfc3339(), "2025-07-15T13:00:00+00:00");
+        assert_eq!(ny.to_local(ny.to_utc(local("20250715T090000"))), local("20250715T090000"));
+
+        let sydney = TimeZoneInfo::from_tz(Tz::Australia__Sydney, 2025);
+        assert_eq!(sydney.to_utc(local("20250115T090000")).to_rfc3339(), "2025-01-14T22:00:00+00:00");
+        assert_eq!(sydney.to_utc(local("20250715T090000")).to_rfc3339(), "2025-07-14T23:00:00+00:00");
+    }
+
+    #[test]
+    fn matches_unnamed_and_vtimezone_rules() {
+        let mut info = TimeZoneInfo::from_tz(Tz::America__Los_Angeles, 2025);
+        info.standard_name = "Pacific Standard Time".to_string();
+        assert_eq!(info.tzid(), "America/Los_Angeles");
+        assert_eq!(TimeZoneInfo { bias: -330, ..Default::default() }.tzid(), "Asia/Kolkata");
+
+        let ics = format!("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{}END:VCALENDAR\r\n", info.to_vtimezone("Custom"));
+        let cal: icalendar::Calendar = ics.parse().unwrap();
+        let icalendar::CalendarComponent::Other(vtimezone) = &cal.components[0] else { panic!("no VTIMEZONE") };
+        let parsed = TimeZoneInfo::from_vtimezone(vtimezone).unwrap();
+        assert!(parsed.same_rules(&info));
+        assert_eq!(parsed.standard_name, "Custom");
+    }
+}
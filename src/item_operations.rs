@@ -0,0 +1,155 @@
+use crate::caldav::CaldavClient;
+use crate::eas_marshaller::{event_attachments, AttachmentContent};
+use crate::eas_models::{AttachmentProperties, FetchProperties, FetchResponse, ItemOperationsFetch};
+use crate::models::AppState;
+use crate::storage::Storage;
+use crate::search::parse_long_id;
+use crate::sync::{generate_server_id, outgoing_data, parse_file_reference, SyncSession};
+use crate::utils::parse_range;
+use crate::wbxml_serde::Opaque;
+use anyhow::Result;
+use reqwest::Url;
+
+// ItemOperations status codes
+const ITEMOPS_STATUS_SUCCESS: u32 = 1;
+const ITEMOPS_STATUS_PROTOCOL_ERROR: u32 = 2;
+pub const ITEMOPS_STATUS_SERVER_ERROR: u32 = 3;
+const ITEMOPS_STATUS_OBJECT_NOT_FOUND: u32 = 6;
+const ITEMOPS_STATUS_INVALID_RANGE: u32 = 8;
+const ITEMOPS_STATUS_UNKNOWN_STORE: u32 = 9;
+const ITEMOPS_STATUS_CONVERSION_FAILED: u32 = 14;
+const ITEMOPS_STATUS_INVALID_ATTACHMENT: u32 = 15;
+
+/// Run one Fetch from the mailbox. An item (`CollectionId` and `ServerId`, or
+/// the `LongId` of a Search result) comes back as its ApplicationData, whole unless the Fetch's own
+/// BodyPreference asks for less; this is how clients get a body that Sync
+/// truncated. An attachment (`FileReference`) comes back as its data, or the
+/// part of it that `Range` selects; linked attachments are downloaded from the
+/// CalDAV server.
+pub async fn perform_fetch(state: &AppState, session: &SyncSession<'_>, fetch: &ItemOperationsFetch) -> Result<FetchResponse> {
+    let mut resp = FetchResponse {
+        collection_id: fetch.collection_id.clone(),
+        server_id: fetch.server_id.clone(),
+        long_id: fetch.long_id.clone(),
+        file_reference: fetch.file_reference.clone(),
+        ..Default::default()
+    };
+    if !fetch.store.eq_ignore_ascii_case("Mailbox") {
+        resp.status = ITEMOPS_STATUS_UNKNOWN_STORE;
+        return Ok(resp);
+    }
+    if let Some(reference) = &fetch.file_reference {
+        return fetch_attachment(state, session, fetch, reference, resp).await;
+    }
+    let storage: &Storage = &state.storage;
+    let found = if let Some(long_id) = &fetch.long_id {
+        match parse_long_id(long_id) {
+            Some((collection_id, name)) => match storage.get_calendar(session.owner, collection_id).await? {
+                Some(calendar) => {
+                    let href = Url::parse(&calendar.caldav_href)?.join(name)?.to_string();
+                    Some((generate_server_id(&state.cfg.hmac_secret, &href), href))
+                }
+                None => None,
+            },
+            None => None,
+        }
+    } else {
+        let Some(server_id) = &fetch.server_id else {
+            resp.status = ITEMOPS_STATUS_PROTOCOL_ERROR;
+            return Ok(resp);
+        };
+        if let Some(collection_id) = &fetch.collection_id
+            && storage.get_calendar(session.owner, collection_id).await?.is_none()
+        {
+            resp.status = ITEMOPS_STATUS_OBJECT_NOT_FOUND;
+            return Ok(resp);
+        }
+        storage.get_item_by_server_id(session.owner, session.device_id, server_id).await?
+            .map(|(_, href)| (server_id.clone(), href))
+    };
+    let Some((server_id, href)) = found else {
+        resp.status = ITEMOPS_STATUS_OBJECT_NOT_FOUND;
+        return Ok(resp);
+    };
+    let caldav = CaldavClient::new(&state.cfg);
+    let ics = match caldav.get_event(&href, session.username, session.password).await {
+        Ok(ics) => ics,
+        Err(e) => {
+            tracing::warn!("ItemOperations could not read {}: {}", href, e);
+            resp.status = ITEMOPS_STATUS_OBJECT_NOT_FOUND;
+            return Ok(resp);
+        }
+    };
+    let body_preference = fetch.options.as_ref().map(|o| o.body_preference.as_slice()).unwrap_or_default();
+    match outgoing_data(&ics, &server_id, session, body_preference) {
+        Ok(data) => {
+            resp.status = ITEMOPS_STATUS_SUCCESS;
+            resp.class = Some("Calendar".to_string());
+            resp.properties = Some(FetchProperties::Item(Box::new(data)));
+        }
+        Err(e) => {
+            tracing::warn!("ItemOperations could not convert {}: {}", href, e);
+            resp.status = ITEMOPS_STATUS_CONVERSION_FAILED;
+        }
+    }
+    Ok(resp)
+}
+
+async fn fetch_attachment(state: &AppState, session: &SyncSession<'_>, fetch: &ItemOperationsFetch, reference: &str, mut resp: FetchResponse) -> Result<FetchResponse> {
+    let storage: &Storage = &state.storage;
+    let caldav = CaldavClient::new(&state.cfg);
+    let found = match parse_file_reference(reference) {
+        Some((server_id, index)) => storage.get_item_by_server_id(session.owner, session.device_id, server_id).await?
+            .map(|(_, href)| (href, index)),
+        None => None,
+    };
+    let Some((href, index)) = found else {
+        resp.status = ITEMOPS_STATUS_INVALID_ATTACHMENT;
+        return Ok(resp);
+    };
+    let attachment = caldav.get_event(&href, session.username, session.password).await
+        .and_then(|ics| event_attachments(&ics))
+        .map(|mut all| (index < all.len()).then(|| all.swap_remove(index)));
+    let attachment = match attachment {
+        Ok(Some(a)) => a,
+        Ok(None) => {
+            resp.status = ITEMOPS_STATUS_INVALID_ATTACHMENT;
+            return Ok(resp);
+        }
+        Err(e) => {
+            tracing::warn!("ItemOperations could not read {}: {}", href, e);
+            resp.status = ITEMOPS_STATUS_INVALID_ATTACHMENT;
+            return Ok(resp);
+        }
+    };
+    let data = match attachment.content {
+        AttachmentContent::Inline(data) => data,
+        AttachmentContent::Uri(url) => match caldav.get_attachment(&url, session.username, session.password).await {
+            Ok(data) => data,
+            Err(e) => {
+                tracing::warn!("ItemOperations could not download {}: {}", url, e);
+                resp.status = ITEMOPS_STATUS_SERVER_ERROR;
+                return Ok(resp);
+            }
+        },
+    };
+
+    let mut properties = AttachmentProperties { content_type: attachment.content_type, ..Default::default() };
+    let data = match fetch.options.as_ref().and_then(|o| o.range.as_deref()) {
+        None => data,
+        Some(range) => {
+            let Some((first, last)) = parse_range(range).filter(|(first, _)| *first < data.len().max(1)) else {
+                resp.status = ITEMOPS_STATUS_INVALID_RANGE;
+                return Ok(resp);
+            };
+            let last = last.min(data.len().saturating_sub(1));
+            properties.total = Some(data.len() as u32);
+            properties.range = Some(format!("{}-{}", first, last));
+            data.get(first..=last).unwrap_or_default().to_vec()
+        }
+    };
+    properties.data = Some(Opaque(data));
+    resp.status = ITEMOPS_STATUS_SUCCESS;
+    resp.properties = Some(FetchProperties::Attachment(properties));
+    Ok(resp)
+}
@@ -0,0 +1,106 @@
+use crate::config::Config;
+use anyhow::{anyhow, Result};
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+const CAPABILITY_CORE: &str = "urn:ietf:params:jmap:core";
+const CAPABILITY_VACATION: &str = "urn:ietf:params:jmap:vacationresponse";
+
+/// The JMAP VacationResponse (RFC 8621 section 8), which Stalwart keeps as the
+/// account's vacation Sieve script. Dates are UTCDates such as
+/// `2025-06-01T08:00:00Z`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VacationResponse {
+    pub is_enabled: bool,
+    pub from_date: Option<String>,
+    pub to_date: Option<String>,
+    pub subject: Option<String>,
+    pub text_body: Option<String>,
+    pub html_body: Option<String>,
+}
+
+pub struct JmapClient {
+    session_url: String,
+    client: Client,
+}
+
+/// The parts of a JMAP session resource the gateway needs.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Session {
+    api_url: String,
+    primary_accounts: std::collections::HashMap<String, String>,
+}
+
+impl JmapClient {
+    pub fn new(cfg: &Config) -> Self {
+        let client = Client::builder().build().unwrap();
+        let session_url = cfg.jmap_url.clone().unwrap_or_else(|| {
+            Url::parse(&cfg.caldav_base).and_then(|u| u.join("/.well-known/jmap")).map(String::from)
+                .unwrap_or_else(|_| format!("{}/.well-known/jmap", cfg.caldav_base.trim_end_matches('/')))
+        });
+        JmapClient { session_url, client }
+    }
+
+    /// The API endpoint and the account holding the user's vacation response.
+    /// The session names the server by its public URL, which the gateway may
+    /// not reach, so only the path of `apiUrl` is used, on the host the session
+    /// came from.
+    async fn session(&self, username: &str, password: &str) -> Result<(Url, String)> {
+        let resp = self.client.get(&self.session_url).basic_auth(username, Some(password)).send().await?;
+        if !resp.status().is_success() {
+            return Err(anyhow!("JMAP session failed: {}", resp.status()));
+        }
+        let final_url = resp.url().clone();
+        let session: Session = resp.json().await?;
+        let account = session.primary_accounts.get(CAPABILITY_VACATION)
+            .ok_or_else(|| anyhow!("JMAP server does not offer vacation responses"))?;
+        let api = Url::parse(&session.api_url).or_else(|_| final_url.join(&session.api_url))?;
+        let mut api_url = final_url;
+        api_url.set_path(api.path());
+        api_url.set_query(api.query());
+        Ok((api_url, account.clone()))
+    }
+
+    /// Make one method call and return its arguments.
+    async fn call(&self, username: &str, password: &str, method: &str, args: impl FnOnce(&str) -> Value) -> Result<Value> {
+        let (api_url, account) = self.session(username, password).await?;
+        let request = json!({
+            "using": [CAPABILITY_CORE, CAPABILITY_VACATION],
+            "methodCalls": [[method, args(&account), "0"]],
+        });
+        let resp = self.client.post(api_url).basic_auth(username, Some(password)).json(&request).send().await?;
+        if !resp.status().is_success() {
+            return Err(anyhow!("JMAP {} failed: {}", method, resp.status()));
+        }
+        let mut body: Value = resp.json().await?;
+        let response = body["methodResponses"][0].take();
+        match response[0].as_str() {
+            Some(name) if name == method => Ok(response[1].clone()),
+            Some("error") => Err(anyhow!("JMAP {} failed: {}", method, response[1]["type"].as_str().unwrap_or("unknown error"))),
+            _ => Err(anyhow!("unexpected JMAP response to {}", method)),
+        }
+    }
+
+    pub async fn get_vacation(&self, username: &str, password: &str) -> Result<VacationResponse> {
+        let result = self.call(username, password, "VacationResponse/get", |account| json!({
+            "accountId": account,
+            "ids": ["singleton"],
+        })).await?;
+        let vacation = result["list"].get(0).cloned().ok_or_else(|| anyhow!("no vacation response"))?;
+        Ok(serde_json::from_value(vacation)?)
+    }
+
+    pub async fn set_vacation(&self, username: &str, password: &str, vacation: &VacationResponse) -> Result<()> {
+        let result = self.call(username, password, "VacationResponse/set", |account| json!({
+            "accountId": account,
+            "update": { "singleton": vacation },
+        })).await?;
+        match result["notUpdated"].get("singleton") {
+            Some(error) => Err(anyhow!("vacation response not updated: {}", error["description"].as_str().or(error["type"].as_str()).unwrap_or("unknown error"))),
+            None => Ok(()),
+        }
+    }
+}
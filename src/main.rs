@@ -9,13 +9,28 @@ use std::net::SocketAddr;
 mod config;
 mod storage;
 mod wbxml;
+mod codepages;
+mod wbxml_serde;
 mod caldav;
 mod ews;
 mod eas;
+mod eas_models;
+mod eas_request;
 mod sync;
+mod provision;
+mod meeting;
+mod item_operations;
+mod settings;
+mod search;
+mod resolve_recipients;
+mod directory;
+mod jmap;
 mod models;
 mod utils;
 mod ews_marshaller;
+mod eas_marshaller;
+mod eas_timezone;
+mod admin;
 
 use config::Config;
 use storage::Storage;
@@ -23,13 +38,27 @@ use models::AppState;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Initialize tracing/logging
-    tracing_subscriber::fmt::init();
+    // load config; `--config <path>` overrides the default location and any
+    // remaining arguments are an admin subcommand
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let mut config_path = "/etc/exchange-gateway/config.toml".to_string();
+    if let Some(i) = args.iter().position(|a| a == "--config") {
+        let Some(path) = args.get(i + 1).cloned() else {
+            anyhow::bail!("--config needs a path");
+        };
+        config_path = path;
+        args.drain(i..=i + 1);
+    }
+    let cfg = Config::load(&config_path)?;
 
-    // load config
-    let cfg = Config::load("/etc/exchange-gateway/config.toml")?;
+    // Initialize tracing/logging at the configured level (default: info)
+    let level = cfg.log_level.as_deref().and_then(|l| l.parse().ok()).unwrap_or(tracing::Level::INFO);
+    tracing_subscriber::fmt().with_max_level(level).init();
     let storage_plain = Storage::new(&cfg.db_path).await?;
     storage_plain.run_migrations().await?;
+    if !args.is_empty() {
+        return admin::run(&storage_plain, &args).await;
+    }
 
     // store storage in Arc as AppState expects Arc<Storage>
     let storage = Arc::new(storage_plain);
@@ -41,7 +70,7 @@ async fn main() -> anyhow::Result<()> {
 
     let app = Router::new()
         .route("/EWS/Exchange.asmx", post(ews::handle_ews))
-        .route("/Microsoft-Server-ActiveSync", post(eas::handle_activesync))
+        .route("/Microsoft-Server-ActiveSync", post(eas::handle_activesync).options(eas::handle_options))
         .route("/health", get(|| async { "OK" }))
         .layer(Extension(state));
 
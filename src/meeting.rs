@@ -0,0 +1,84 @@
+use crate::caldav::CaldavClient;
+use crate::eas_marshaller::respond_to_invitation;
+use crate::eas_models::{MeetingResponseItem, MeetingResponseResult};
+use crate::eas_request::ProtocolVersion;
+use crate::models::AppState;
+use crate::storage::Storage;
+use crate::sync::SyncSession;
+use anyhow::Result;
+
+// MeetingResponse status codes
+const MEETING_STATUS_SUCCESS: u32 = 1;
+const MEETING_STATUS_INVALID_REQUEST: u32 = 2;
+const MEETING_STATUS_MAILBOX_ERROR: u32 = 3;
+
+/// UserResponse value for declining, which leaves no event to point at.
+const USER_RESPONSE_DECLINED: u8 = 3;
+
+/// Answer a meeting invitation from the phone. The event named by `RequestId`
+/// gets the user's PARTSTAT and is written back to CalDAV. The organizer is told
+/// with an iTIP REPLY posted to the user's scheduling outbox, or, when the
+/// server has no outbox, by the server's own implicit scheduling on the PUT.
+/// Before 16.0 the client cannot send the reply mail through the gateway, so a
+/// reply always goes out; 16.x clients ask for one with `SendResponse`.
+pub async fn perform_meeting_response(state: &AppState, session: &SyncSession<'_>, req: &MeetingResponseItem) -> Result<MeetingResponseResult> {
+    let storage: &Storage = &state.storage;
+    let caldav = CaldavClient::new(&state.cfg);
+    let status = |status: u32| MeetingResponseResult { request_id: req.request_id.clone(), status, calendar_id: None };
+
+    if let Some(collection_id) = &req.collection_id
+        && storage.get_calendar(session.owner, collection_id).await?.is_none()
+    {
+        return Ok(status(MEETING_STATUS_INVALID_REQUEST));
+    }
+    let Some((_, href)) = storage.get_item_by_server_id(session.owner, session.device_id, &req.request_id).await? else {
+        return Ok(status(MEETING_STATUS_INVALID_REQUEST));
+    };
+    let ics = match caldav.get_event(&href, session.username, session.password).await {
+        Ok(ics) => ics,
+        Err(e) => {
+            tracing::warn!("MeetingResponse could not read {}: {}", href, e);
+            return Ok(status(MEETING_STATUS_MAILBOX_ERROR));
+        }
+    };
+    let comment = req.send_response.as_ref().and_then(|s| s.body.as_ref()).and_then(|b| b.data.as_deref());
+    let reply = match respond_to_invitation(&ics, session.owner, req.user_response, req.instance_id.as_deref(), comment) {
+        Ok(r) => r,
+        Err(e) => {
+            tracing::info!("MeetingResponse for {} rejected: {}", req.request_id, e);
+            return Ok(status(MEETING_STATUS_INVALID_REQUEST));
+        }
+    };
+
+    let send = session.version < ProtocolVersion::V16_0 || req.send_response.is_some();
+    let outbox = if send {
+        caldav.schedule_outbox(session.username, session.password).await.unwrap_or_else(|e| {
+            tracing::warn!("no scheduling outbox for {}: {}", session.owner, e);
+            None
+        })
+    } else {
+        None
+    };
+    // Only the implicit path lets the server reply on the PUT
+    let stored = if send && outbox.is_none() {
+        let (collection, name) = href.rsplit_once('/').unwrap_or((&href, ""));
+        caldav.put_event(collection, name, &reply.ics, session.username, session.password).await
+    } else {
+        caldav.put_event_without_reply(&href, &reply.ics, session.username, session.password).await
+    };
+    if let Err(e) = stored {
+        tracing::warn!("MeetingResponse could not update {}: {}", href, e);
+        return Ok(status(MEETING_STATUS_MAILBOX_ERROR));
+    }
+    if let Some(outbox) = outbox
+        && let Err(e) = caldav.send_reply(&outbox, &reply.attendee, &reply.organizer, &reply.reply, session.username, session.password).await
+    {
+        tracing::warn!("REPLY from {} to {} was not delivered: {}", reply.attendee, reply.organizer, e);
+    }
+
+    Ok(MeetingResponseResult {
+        request_id: req.request_id.clone(),
+        status: MEETING_STATUS_SUCCESS,
+        calendar_id: (req.user_response != USER_RESPONSE_DECLINED).then(|| req.request_id.clone()),
+    })
+}
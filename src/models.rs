@@ -7,3 +7,113 @@ pub struct AppState {
     pub cfg: Config,
     pub storage: Arc<Storage>,
 }
+
+/// A CalDAV calendar collection as exposed to ActiveSync clients as a folder.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CalendarFolder {
+    pub collection_id: String,
+    pub caldav_href: String,
+    pub display_name: String,
+    pub folder_type: u32,
+}
+
+/// Kind of a server-side change waiting to be sent to a client.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    Add,
+    Change,
+    Delete,
+    /// The item fell out of the FilterType window; the client drops its copy.
+    SoftDelete,
+}
+
+impl ChangeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChangeKind::Add => "add",
+            ChangeKind::Change => "change",
+            ChangeKind::Delete => "delete",
+            ChangeKind::SoftDelete => "soft_delete",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "add" => Some(ChangeKind::Add),
+            "change" => Some(ChangeKind::Change),
+            "delete" => Some(ChangeKind::Delete),
+            "soft_delete" => Some(ChangeKind::SoftDelete),
+            _ => None,
+        }
+    }
+}
+
+/// A change to one calendar object, queued until it fits in a Sync window.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingChange {
+    pub kind: ChangeKind,
+    pub server_id: String,
+    pub resource_href: String,
+    pub etag: Option<String>,
+    pub calendar_data: Option<String>,
+}
+
+/// Management state of an ActiveSync device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceStatus {
+    Active,
+    /// A remote wipe of the whole device is waiting to be sent.
+    WipePending,
+    /// An account-only wipe (16.1) is waiting to be sent.
+    AccountWipePending,
+    /// The device acknowledged a wipe, or was shut out; every request is refused.
+    Blocked,
+}
+
+impl DeviceStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeviceStatus::Active => "active",
+            DeviceStatus::WipePending => "wipe_pending",
+            DeviceStatus::AccountWipePending => "account_wipe_pending",
+            DeviceStatus::Blocked => "blocked",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "active" => Some(DeviceStatus::Active),
+            "wipe_pending" => Some(DeviceStatus::WipePending),
+            "account_wipe_pending" => Some(DeviceStatus::AccountWipePending),
+            "blocked" => Some(DeviceStatus::Blocked),
+            _ => None,
+        }
+    }
+}
+
+/// A device that has talked to the gateway.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Device {
+    pub device_id: String,
+    pub device_type: String,
+    pub user_agent: String,
+    pub first_seen_ts: Option<i64>,
+    pub last_seen_ts: Option<i64>,
+    pub status: DeviceStatus,
+    pub policy_key: Option<u32>,
+    pub wipe_requested_ts: Option<i64>,
+    pub wipe_acked_ts: Option<i64>,
+    pub information: DeviceInformation,
+}
+
+/// What a device reports about itself with Settings DeviceInformation.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DeviceInformation {
+    pub model: Option<String>,
+    pub imei: Option<String>,
+    pub friendly_name: Option<String>,
+    pub os: Option<String>,
+    pub os_language: Option<String>,
+    pub phone_number: Option<String>,
+    pub mobile_operator: Option<String>,
+}
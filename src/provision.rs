@@ -0,0 +1,188 @@
+use crate::config::PolicyConfig;
+use crate::eas_models::{EasProvisionDoc, PolicyData, ProvisionPolicies, ProvisionPolicy, ProvisionRequest, ProvisionResponse};
+use crate::eas_request::ProtocolVersion;
+use crate::models::{AppState, DeviceStatus};
+use crate::settings;
+use crate::sync::SyncSession;
+use anyhow::Result;
+use uuid::Uuid;
+
+/// The policy type of the WBXML policy document (protocol 12.0 and later). The
+/// 2.5 XML document type is not offered.
+const POLICY_TYPE: &str = "MS-EAS-Provisioning-WBXML";
+
+// Provision status codes
+const PROVISION_STATUS_SUCCESS: u32 = 1;
+const PROVISION_STATUS_PROTOCOL_ERROR: u32 = 2;
+pub const PROVISION_STATUS_SERVER_ERROR: u32 = 3;
+
+// Wipe acknowledgment status
+const WIPE_STATUS_SUCCESS: u32 = 1;
+
+// Policy status codes
+const POLICY_STATUS_SUCCESS: u32 = 1;
+const POLICY_STATUS_UNKNOWN_TYPE: u32 = 3;
+const POLICY_STATUS_WRONG_KEY: u32 = 5;
+
+/// A random non-zero policy key; zero means "not provisioned" to clients.
+fn new_policy_key() -> u32 {
+    loop {
+        let bytes = Uuid::new_v4().into_bytes();
+        let key = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        if key != 0 {
+            return key;
+        }
+    }
+}
+
+/// The EASProvisionDoc for the configured policy. Without a `[policy]` table the
+/// document imposes nothing.
+fn policy_document(policy: Option<&PolicyConfig>) -> EasProvisionDoc {
+    let Some(p) = policy else {
+        return EasProvisionDoc::default();
+    };
+    EasProvisionDoc {
+        device_password_enabled: p.password_required,
+        alphanumeric_device_password_required: p.password_required.then_some(p.alphanumeric_password_required),
+        require_storage_card_encryption: Some(p.require_device_encryption),
+        min_device_password_length: p.min_password_length.filter(|_| p.password_required),
+        max_inactivity_time_device_lock: p.max_inactivity_lock.filter(|_| p.password_required),
+        max_device_password_failed_attempts: p.max_password_failed_attempts.filter(|_| p.password_required),
+        allow_simple_device_password: p.password_required.then_some(!p.alphanumeric_password_required),
+        require_device_encryption: Some(p.require_device_encryption),
+    }
+}
+
+/// Whether a device may run a command.
+pub enum Admission {
+    Allowed,
+    /// The device must (re)provision first: HTTP 449.
+    MustProvision,
+    /// The device is blocked: HTTP 403.
+    Forbidden,
+}
+
+/// Decide whether a registered device may run a command. Blocked devices are
+/// refused, and a device with a pending wipe may only Provision, which
+/// delivers the wipe. Otherwise, when a policy is configured, the device must
+/// present its current policy key. An account-only wipe cannot be delivered
+/// before 16.1, so such a device is blocked instead.
+pub async fn admit(state: &AppState, session: &SyncSession<'_>, is_provision: bool, policy_key: Option<u32>) -> Result<Admission> {
+    let storage = &state.storage;
+    match storage.get_device_status(session.owner, session.device_id).await? {
+        DeviceStatus::Blocked => return Ok(Admission::Forbidden),
+        DeviceStatus::AccountWipePending if session.version < ProtocolVersion::V16_1 => {
+            tracing::warn!("device {} of {} speaks {} and cannot do an account-only wipe; blocking it", session.device_id, session.owner, session.version);
+            storage.set_device_status(session.owner, session.device_id, DeviceStatus::Blocked).await?;
+            return Ok(Admission::Forbidden);
+        }
+        DeviceStatus::WipePending | DeviceStatus::AccountWipePending if !is_provision => return Ok(Admission::MustProvision),
+        _ => {}
+    }
+    if is_provision || policy_key_is_current(state, session, policy_key).await? {
+        Ok(Admission::Allowed)
+    } else {
+        Ok(Admission::MustProvision)
+    }
+}
+
+/// Deliver a pending wipe, or record the device's acknowledgment of one, after
+/// which the device is blocked.
+async fn wipe_step(state: &AppState, session: &SyncSession<'_>, req: &ProvisionRequest, status: DeviceStatus) -> Result<ProvisionResponse> {
+    let acknowledgment = match status {
+        DeviceStatus::AccountWipePending => req.account_only_remote_wipe.as_ref(),
+        _ => req.remote_wipe.as_ref(),
+    };
+    if let Some(ack) = acknowledgment {
+        if ack.status == WIPE_STATUS_SUCCESS {
+            tracing::info!("device {} of {} acknowledged {}", session.device_id, session.owner, status.as_str());
+        } else {
+            tracing::warn!("device {} of {} failed to wipe (status {}); blocking it anyway", session.device_id, session.owner, ack.status);
+        }
+        state.storage.set_device_status(session.owner, session.device_id, DeviceStatus::Blocked).await?;
+        return Ok(ProvisionResponse { status: PROVISION_STATUS_SUCCESS, ..Default::default() });
+    }
+    Ok(ProvisionResponse {
+        status: PROVISION_STATUS_SUCCESS,
+        remote_wipe: (status == DeviceStatus::WipePending).then_some(()),
+        account_only_remote_wipe: (status == DeviceStatus::AccountWipePending).then_some(()),
+        ..Default::default()
+    })
+}
+
+/// Run one step of the Provision handshake. A request without a key gets the
+/// policy document and a temporary key; acknowledging that key with the policy
+/// applied earns the final key, which the device then sends with every command.
+/// A pending wipe takes precedence over the policy exchange. DeviceInformation
+/// sent along (14.1+) is recorded as with Settings.
+pub async fn perform_provision(state: &AppState, session: &SyncSession<'_>, req: &ProvisionRequest) -> Result<ProvisionResponse> {
+    let device_information = match &req.device_information {
+        Some(info) => Some(settings::store_device_information(state, session, info).await?),
+        None => None,
+    };
+    let status = state.storage.get_device_status(session.owner, session.device_id).await?;
+    let mut resp = if matches!(status, DeviceStatus::WipePending | DeviceStatus::AccountWipePending) {
+        wipe_step(state, session, req, status).await?
+    } else {
+        policy_step(state, session, req).await?
+    };
+    resp.device_information = device_information;
+    Ok(resp)
+}
+
+/// Exchange the policy document and keys.
+async fn policy_step(state: &AppState, session: &SyncSession<'_>, req: &ProvisionRequest) -> Result<ProvisionResponse> {
+    let Some(requested) = req.policies.as_ref().map(|p| &p.policy) else {
+        return Ok(ProvisionResponse { status: PROVISION_STATUS_PROTOCOL_ERROR, ..Default::default() });
+    };
+    let mut policy = ProvisionPolicy { policy_type: requested.policy_type.clone(), ..Default::default() };
+    let respond = |policy: ProvisionPolicy| ProvisionResponse {
+        status: PROVISION_STATUS_SUCCESS,
+        policies: Some(ProvisionPolicies { policy }),
+        ..Default::default()
+    };
+    if requested.policy_type != POLICY_TYPE {
+        policy.status = Some(POLICY_STATUS_UNKNOWN_TYPE);
+        return Ok(respond(policy));
+    }
+
+    let storage = &state.storage;
+    let acknowledged = requested.policy_key.as_deref().and_then(|k| k.trim().parse::<u32>().ok()).filter(|k| *k != 0);
+    match acknowledged {
+        None => {
+            let key = new_policy_key();
+            storage.set_pending_policy_key(session.owner, session.device_id, key).await?;
+            policy.status = Some(POLICY_STATUS_SUCCESS);
+            policy.policy_key = Some(key.to_string());
+            policy.data = Some(PolicyData { eas_provision_doc: policy_document(state.cfg.policy.as_ref()) });
+        }
+        Some(key) => {
+            let (_, pending) = storage.get_policy_keys(session.owner, session.device_id).await?;
+            if pending != Some(key) {
+                policy.status = Some(POLICY_STATUS_WRONG_KEY);
+                return Ok(respond(policy));
+            }
+            if requested.status != Some(POLICY_STATUS_SUCCESS) {
+                tracing::warn!("device {} of {} did not fully apply the policy (status {:?})", session.device_id, session.owner, requested.status);
+            }
+            let key = new_policy_key();
+            storage.confirm_policy_key(session.owner, session.device_id, key).await?;
+            policy.status = Some(POLICY_STATUS_SUCCESS);
+            policy.policy_key = Some(key.to_string());
+        }
+    }
+    Ok(respond(policy))
+}
+
+/// Whether `key` is the device's current policy key. Only enforced when a policy
+/// is configured.
+async fn policy_key_is_current(state: &AppState, session: &SyncSession<'_>, key: Option<u32>) -> Result<bool> {
+    if state.cfg.policy.is_none() {
+        return Ok(true);
+    }
+    let Some(key) = key.filter(|k| *k != 0) else {
+        return Ok(false);
+    };
+    let (current, _) = state.storage.get_policy_keys(session.owner, session.device_id).await?;
+    Ok(current == Some(key))
+}
@@ -0,0 +1,133 @@
+use crate::caldav::CaldavClient;
+use crate::directory::{Directory, DirectoryEntry};
+use crate::eas_marshaller::{format_eas_date, merged_free_busy, parse_eas_date};
+use crate::eas_models::{Availability, AvailabilityRequest, Certificates, Recipient, RecipientResponse, ResolveRecipientsRequest, ResolveRecipientsResponse};
+use crate::models::AppState;
+use crate::sync::SyncSession;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+
+// ResolveRecipients status codes
+const RESOLVE_STATUS_SUCCESS: u32 = 1;
+const RESOLVE_STATUS_PROTOCOL_ERROR: u32 = 5;
+pub const RESOLVE_STATUS_SERVER_ERROR: u32 = 6;
+
+// Status of one To
+const RECIPIENT_STATUS_RESOLVED: u32 = 1;
+const RECIPIENT_STATUS_AMBIGUOUS: u32 = 2;
+const RECIPIENT_STATUS_AMBIGUOUS_PARTIAL: u32 = 3;
+const RECIPIENT_STATUS_NOT_FOUND: u32 = 4;
+
+// Availability status codes
+const AVAILABILITY_STATUS_SUCCESS: u32 = 1;
+const AVAILABILITY_STATUS_TOO_MANY_RECIPIENTS: u32 = 160;
+const AVAILABILITY_STATUS_UNAVAILABLE: u32 = 162;
+
+/// Certificates status of a recipient without a valid S/MIME certificate.
+const CERTIFICATES_STATUS_NONE: u32 = 7;
+
+/// CertificateRetrieval value asking for no certificates.
+const CERTIFICATE_RETRIEVAL_NONE: u8 = 1;
+
+/// Recipient Type of a GAL entry.
+const RECIPIENT_TYPE_GAL: u8 = 1;
+
+/// Most To elements one request may carry.
+const MAX_RECIPIENTS: usize = 100;
+/// Most recipients whose free/busy time one request looks up.
+const MAX_AVAILABILITY_LOOKUPS: usize = 20;
+/// Longest availability window, in days.
+const MAX_AVAILABILITY_DAYS: i64 = 42;
+/// Suggestions for an ambiguous recipient when the client sets no
+/// MaxAmbiguousRecipients.
+const DEFAULT_MAX_AMBIGUOUS: usize = 20;
+
+fn status(status: u32) -> ResolveRecipientsResponse {
+    ResolveRecipientsResponse { status, response: Vec::new() }
+}
+
+/// Resolve each To against the directory. A To matching one entry exactly
+/// resolves to it; otherwise the entries containing it come back as
+/// suggestions. With Availability, each recipient's free/busy time in the
+/// window is read from the CalDAV server as a MergedFreeBusy string. The
+/// gateway holds no certificates, so requested ones are reported missing.
+pub async fn perform_resolve_recipients(state: &AppState, session: &SyncSession<'_>, req: &ResolveRecipientsRequest) -> Result<ResolveRecipientsResponse> {
+    let options = req.options.clone().unwrap_or_default();
+    if req.to.is_empty() || req.to.len() > MAX_RECIPIENTS {
+        return Ok(status(RESOLVE_STATUS_PROTOCOL_ERROR));
+    }
+    let window = match &options.availability {
+        Some(availability) => match availability_window(availability) {
+            Some(window) => Some(window),
+            None => return Ok(status(RESOLVE_STATUS_PROTOCOL_ERROR)),
+        },
+        None => None,
+    };
+    let max_ambiguous = options.max_ambiguous_recipients.map(|m| m as usize).unwrap_or(DEFAULT_MAX_AMBIGUOUS);
+    let certificates = options.certificate_retrieval.is_some_and(|c| c != CERTIFICATE_RETRIEVAL_NONE)
+        .then_some(Certificates { status: CERTIFICATES_STATUS_NONE, certificate_count: 0 });
+
+    let directory = Directory::new(&state.cfg);
+    let caldav = CaldavClient::new(&state.cfg);
+    let mut lookups = 0;
+    let mut response = Vec::new();
+    for to in &req.to {
+        let mut entries = directory.resolve(to, session.username, session.password).await?;
+        let recipient_status = match entries.len() {
+            0 => RECIPIENT_STATUS_NOT_FOUND,
+            1 => RECIPIENT_STATUS_RESOLVED,
+            n if n > max_ambiguous => RECIPIENT_STATUS_AMBIGUOUS_PARTIAL,
+            _ => RECIPIENT_STATUS_AMBIGUOUS,
+        };
+        if recipient_status != RECIPIENT_STATUS_RESOLVED {
+            entries.truncate(max_ambiguous);
+        }
+        let mut recipient = Vec::new();
+        for entry in entries {
+            let availability = match window {
+                Some(_) if lookups >= MAX_AVAILABILITY_LOOKUPS => Some(Availability { status: AVAILABILITY_STATUS_TOO_MANY_RECIPIENTS, merged_free_busy: None }),
+                Some((start, end)) => {
+                    lookups += 1;
+                    Some(availability(&caldav, session, &entry, start, end).await)
+                }
+                None => None,
+            };
+            recipient.push(Recipient {
+                r#type: RECIPIENT_TYPE_GAL,
+                display_name: entry.display_name.or_else(|| entry.alias.clone()),
+                email_address: entry.email_addresses.into_iter().next().unwrap_or_default(),
+                availability,
+                certificates: certificates.clone(),
+            });
+        }
+        response.push(RecipientResponse { to: to.clone(), status: recipient_status, recipient_count: recipient.len() as u32, recipient });
+    }
+    Ok(ResolveRecipientsResponse { status: RESOLVE_STATUS_SUCCESS, response })
+}
+
+/// The requested availability window; None unless it is well formed, ends
+/// after it starts and spans at most `MAX_AVAILABILITY_DAYS`.
+fn availability_window(req: &AvailabilityRequest) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let start = parse_eas_date(req.start_time.trim())?;
+    let end = parse_eas_date(req.end_time.trim())?;
+    (end > start && end - start <= Duration::days(MAX_AVAILABILITY_DAYS)).then_some((start, end))
+}
+
+/// Free/busy time of one recipient, looked up in their calendar home by
+/// account name.
+async fn availability(caldav: &CaldavClient, session: &SyncSession<'_>, entry: &DirectoryEntry, start: DateTime<Utc>, end: DateTime<Utc>) -> Availability {
+    let unavailable = Availability { status: AVAILABILITY_STATUS_UNAVAILABLE, merged_free_busy: None };
+    let Some(account) = entry.alias.as_deref() else {
+        return unavailable;
+    };
+    let merged = caldav.free_busy(account, &format_eas_date(start), &format_eas_date(end), session.username, session.password).await
+        .and_then(|ics| merged_free_busy(&ics, start, end));
+    match merged {
+        Ok(merged) => Availability { status: AVAILABILITY_STATUS_SUCCESS, merged_free_busy: Some(merged) },
+        Err(e) => {
+            tracing::warn!("no free/busy time for {}: {}", account, e);
+            unavailable
+        }
+    }
+}
+
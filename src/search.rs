@@ -0,0 +1,229 @@
+use crate::caldav::{parse_multistatus, CaldavClient};
+use crate::directory::Directory;
+use crate::eas_marshaller::{parse_eas_date, EAS_DATE_FORMAT};
+use crate::eas_models::{BodyPreference, FindRequest, FindResponse, FindResponseStore, FindResult, GalProperties, SearchAnd, SearchComparison, SearchProperties, SearchQuery, SearchRequest, SearchResponse, SearchResponseStore, SearchResult, SearchResults};
+use crate::models::AppState;
+use crate::sync::{generate_server_id, outgoing_data, SyncSession};
+use crate::utils::parse_range;
+use anyhow::Result;
+use reqwest::Url;
+use std::collections::HashMap;
+
+// Search status codes
+const SEARCH_STATUS_SUCCESS: u32 = 1;
+pub const SEARCH_STATUS_SERVER_ERROR: u32 = 3;
+
+// Search Store status codes
+const STORE_STATUS_SUCCESS: u32 = 1;
+const STORE_STATUS_INVALID_REQUEST: u32 = 2;
+const STORE_STATUS_SERVER_ERROR: u32 = 3;
+const STORE_STATUS_NOT_FOUND: u32 = 6;
+
+// Find status codes
+const FIND_STATUS_SUCCESS: u32 = 1;
+const FIND_STATUS_INVALID_REQUEST: u32 = 2;
+
+/// Most results one Search or Find returns, and the most GAL entries looked at.
+const MAX_RESULTS: usize = 100;
+
+/// Event properties a mailbox search matches its FreeText against.
+const SEARCHED_PROPERTIES: &[&str] = &["SUMMARY", "LOCATION", "DESCRIPTION"];
+
+/// The LongId of a search result: the calendar's CollectionId and the name of
+/// the event's resource in it. ItemOperations fetches the event by it.
+fn long_id(collection_id: &str, resource_href: &str) -> String {
+    format!("{}:{}", collection_id, resource_href.trim_end_matches('/').rsplit('/').next().unwrap_or_default())
+}
+
+/// Split a LongId into CollectionId and resource name. The name must be a
+/// single path segment, so it cannot lead out of the calendar.
+pub fn parse_long_id(long_id: &str) -> Option<(&str, &str)> {
+    let (collection_id, name) = long_id.split_once(':')?;
+    let single_segment = !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\', '?', '#']);
+    single_segment.then_some((collection_id, name))
+}
+
+/// Run a Search in the GAL or in the mailbox's calendars; other stores are
+/// not supported. Results are paged by the request's Range, at most
+/// `MAX_RESULTS` at a time.
+pub async fn perform_search(state: &AppState, session: &SyncSession<'_>, req: &SearchRequest) -> Result<SearchResponse> {
+    let store = &req.store;
+    let options = store.options.clone().unwrap_or_default();
+    let result = match requested_range(options.range.as_deref()) {
+        None => Ok(store_status(STORE_STATUS_INVALID_REQUEST)),
+        Some((first, last)) => match store.name.to_ascii_lowercase().as_str() {
+            "gal" => search_gal(state, session, &store.query, first, last).await,
+            "mailbox" => search_mailbox(state, session, &store.query, &options.body_preference, first, last).await,
+            _ => Ok(store_status(STORE_STATUS_INVALID_REQUEST)),
+        },
+    };
+    let store = result.unwrap_or_else(|e| {
+        tracing::warn!("Search in {} for {} failed: {}", store.name, session.owner, e);
+        store_status(STORE_STATUS_SERVER_ERROR)
+    });
+    Ok(SearchResponse { status: SEARCH_STATUS_SUCCESS, response: Some(SearchResults { store }) })
+}
+
+fn store_status(status: u32) -> SearchResponseStore {
+    SearchResponseStore { status, ..Default::default() }
+}
+
+/// The first and last result a Range asks for, at most `MAX_RESULTS` of them;
+/// the first `MAX_RESULTS` without a Range. None if the Range is malformed.
+fn requested_range(range: Option<&str>) -> Option<(usize, usize)> {
+    match range {
+        None => Some((0, MAX_RESULTS - 1)),
+        Some(range) => parse_range(range).map(|(first, last)| (first, last.min(first.saturating_add(MAX_RESULTS - 1)))),
+    }
+}
+
+/// The `first` through `last` results, with the Range actually returned and
+/// the number of matches.
+fn page<T>(results: Vec<T>, first: usize, last: usize) -> (Vec<T>, Option<String>, u32) {
+    let total = results.len() as u32;
+    let result: Vec<T> = results.into_iter().skip(first).take((last - first).saturating_add(1)).collect();
+    let range = (!result.is_empty()).then(|| format!("{}-{}", first, first + result.len() - 1));
+    (result, range, total)
+}
+
+/// A page of Search results. A search without matches has one empty Result.
+fn search_page(results: Vec<SearchResult>, first: usize, last: usize) -> SearchResponseStore {
+    let (mut result, range, total) = page(results, first, last);
+    if result.is_empty() {
+        result.push(SearchResult::default());
+    }
+    SearchResponseStore { status: STORE_STATUS_SUCCESS, result, range, total: Some(total) }
+}
+
+/// Directory entries matching a GAL query, as GAL properties.
+async fn gal_matches(state: &AppState, session: &SyncSession<'_>, text: &str) -> Result<Vec<GalProperties>> {
+    let directory = Directory::new(&state.cfg);
+    Ok(directory.search(text, MAX_RESULTS, session.username, session.password).await?.into_iter().map(|entry| GalProperties {
+        display_name: entry.display_name.or_else(|| entry.alias.clone()),
+        alias: entry.alias,
+        email_address: entry.email_addresses.into_iter().next(),
+    }).collect())
+}
+
+/// Look the Query text up in the directory.
+async fn search_gal(state: &AppState, session: &SyncSession<'_>, query: &SearchQuery, first: usize, last: usize) -> Result<SearchResponseStore> {
+    let Some(text) = query.text.as_deref().map(str::trim).filter(|t| !t.is_empty()) else {
+        return Ok(store_status(STORE_STATUS_INVALID_REQUEST));
+    };
+    let results = gal_matches(state, session, text).await?.into_iter()
+        .map(|gal| SearchResult { properties: Some(SearchProperties::Gal(gal)), ..Default::default() })
+        .collect();
+    Ok(search_page(results, first, last))
+}
+
+/// Run a Find (16.1). Only GalSearchCriterion is served, from the directory
+/// as with Search; mailbox searches are refused as invalid.
+pub async fn perform_find(state: &AppState, session: &SyncSession<'_>, req: &FindRequest) -> FindResponse {
+    let Some(criterion) = &req.execute_search.gal_search_criterion else {
+        return FindResponse { status: FIND_STATUS_INVALID_REQUEST, response: None };
+    };
+    let store = |status: u32| FindResponseStore { store: "GAL".to_string(), status, ..Default::default() };
+    let text = criterion.query.free_text.as_deref().map(str::trim).filter(|t| !t.is_empty());
+    let range = requested_range(criterion.options.as_ref().and_then(|o| o.range.as_deref()));
+    let response = match (text, range) {
+        (Some(text), Some((first, last))) => match gal_matches(state, session, text).await {
+            Ok(matches) => {
+                let (result, range, total) = page(matches, first, last);
+                let result = result.into_iter().map(|gal| FindResult { properties: Some(gal) }).collect();
+                FindResponseStore { result, range, total: Some(total), ..store(STORE_STATUS_SUCCESS) }
+            }
+            Err(e) => {
+                tracing::warn!("Find {} in the GAL for {} failed: {}", req.search_id, session.owner, e);
+                store(STORE_STATUS_SERVER_ERROR)
+            }
+        },
+        _ => store(STORE_STATUS_INVALID_REQUEST),
+    };
+    FindResponse { status: FIND_STATUS_SUCCESS, response: Some(response) }
+}
+
+/// Find events whose summary, location or description contains the FreeText
+/// with CalDAV `text-match` queries, in one calendar or all of them. The
+/// `DateReceived` bounds limit the events' time range. Newest events come
+/// first.
+async fn search_mailbox(state: &AppState, session: &SyncSession<'_>, query: &SearchQuery, body_preference: &[BodyPreference], first: usize, last: usize) -> Result<SearchResponseStore> {
+    let Some(and) = &query.and else {
+        return Ok(store_status(STORE_STATUS_INVALID_REQUEST));
+    };
+    if !and.class.is_empty() && !and.class.iter().any(|c| c == "Calendar") {
+        return Ok(search_page(Vec::new(), first, last));
+    }
+    let Some(text) = and.free_text.as_deref().map(str::trim).filter(|t| !t.is_empty()) else {
+        return Ok(store_status(STORE_STATUS_INVALID_REQUEST));
+    };
+    let Some((start, end)) = time_range(and) else {
+        return Ok(store_status(STORE_STATUS_INVALID_REQUEST));
+    };
+    let calendars = match &and.collection_id {
+        Some(collection_id) => match state.storage.get_calendar(session.owner, collection_id).await? {
+            Some(calendar) => vec![calendar],
+            None => return Ok(store_status(STORE_STATUS_NOT_FOUND)),
+        },
+        None => state.storage.list_calendars(session.owner).await?,
+    };
+
+    let caldav = CaldavClient::new(&state.cfg);
+    let mut found: HashMap<String, (String, String)> = HashMap::new();
+    for calendar in &calendars {
+        let base = Url::parse(&calendar.caldav_href)?;
+        for property in SEARCHED_PROPERTIES {
+            let multistatus = caldav.search_events(&calendar.caldav_href, property, text, start.as_deref(), end.as_deref(), session.username, session.password).await?;
+            for r in parse_multistatus(&multistatus)? {
+                if let Some(ics) = r.props.get("calendar-data") {
+                    found.insert(base.join(&r.href)?.to_string(), (calendar.collection_id.clone(), ics.clone()));
+                }
+            }
+        }
+    }
+
+    let mut matches = Vec::new();
+    for (href, (collection_id, ics)) in found {
+        let server_id = generate_server_id(&state.cfg.hmac_secret, &href);
+        match outgoing_data(&ics, &server_id, session, body_preference) {
+            Ok(data) => matches.push((href, collection_id, data)),
+            Err(e) => tracing::warn!("Search could not convert {}: {}", href, e),
+        }
+    }
+    // EAS dates sort chronologically as text
+    matches.sort_by(|a, b| b.2.start_time.cmp(&a.2.start_time).then_with(|| a.0.cmp(&b.0)));
+    let results = matches.into_iter().map(|(href, collection_id, data)| SearchResult {
+        class: Some("Calendar".to_string()),
+        long_id: Some(long_id(&collection_id, &href)),
+        collection_id: Some(collection_id),
+        properties: Some(SearchProperties::Item(Box::new(data))),
+    }).collect();
+    Ok(search_page(results, first, last))
+}
+
+/// The CalDAV time range of the `GreaterThan` and `LessThan` bounds; None if
+/// a bound is not a date.
+fn time_range(and: &SearchAnd) -> Option<(Option<String>, Option<String>)> {
+    let bound = |comparison: &Option<SearchComparison>| match comparison {
+        Some(c) => parse_eas_date(c.value.trim()).map(|d| Some(d.format(EAS_DATE_FORMAT).to_string())),
+        None => Some(None),
+    };
+    Some((bound(&and.greater_than)?, bound(&and.less_than)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_ranges_without_overflow() {
+        assert_eq!(requested_range(None), Some((0, MAX_RESULTS - 1)));
+        assert_eq!(requested_range(Some("10-500")), Some((10, 10 + MAX_RESULTS - 1)));
+        assert_eq!(requested_range(Some("5-2")), None);
+        let huge = format!("{0}-{0}", usize::MAX);
+        assert_eq!(requested_range(Some(&huge)), Some((usize::MAX, usize::MAX)));
+
+        assert_eq!(page(vec![1, 2, 3], 1, 5), (vec![2, 3], Some("1-2".to_string()), 3));
+        assert_eq!(page(vec![1, 2, 3], 0, usize::MAX), (vec![1, 2, 3], Some("0-2".to_string()), 3));
+        assert_eq!(page(vec![1, 2, 3], usize::MAX, usize::MAX), (vec![], None, 3));
+    }
+}
@@ -0,0 +1,208 @@
+use crate::caldav::CaldavClient;
+use crate::eas_marshaller::parse_eas_date;
+use crate::eas_models::{DeviceInformationRequest, EmailAddresses, OofMessage, OofRequest, OofResponse, OofSettings, SettingStatus, SettingsRequest, SettingsResponse, UserAccount, UserAccounts, UserInformation, UserInformationResponse};
+use crate::eas_request::ProtocolVersion;
+use crate::jmap::{JmapClient, VacationResponse};
+use crate::models::{AppState, DeviceInformation};
+use crate::sync::SyncSession;
+use crate::utils;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+
+// Settings status codes
+const SETTINGS_STATUS_SUCCESS: u32 = 1;
+const SETTINGS_STATUS_PROTOCOL_ERROR: u32 = 2;
+pub const SETTINGS_STATUS_SERVER_UNAVAILABLE: u32 = 4;
+const SETTINGS_STATUS_INVALID_ARGUMENTS: u32 = 5;
+
+// OofState values
+const OOF_DISABLED: u8 = 0;
+const OOF_GLOBAL: u8 = 1;
+const OOF_TIME_BASED: u8 = 2;
+
+// Oof BodyType values
+const BODY_TYPE_TEXT: &str = "Text";
+const BODY_TYPE_HTML: &str = "HTML";
+
+/// Run a Settings request. Each setting the client sent gets its own status;
+/// DevicePassword and RightsManagementInformation are not supported and
+/// ignored.
+pub async fn perform_settings(state: &AppState, session: &SyncSession<'_>, req: &SettingsRequest) -> Result<SettingsResponse> {
+    let mut resp = SettingsResponse { status: SETTINGS_STATUS_SUCCESS, ..Default::default() };
+    if let Some(oof) = &req.oof {
+        resp.oof = Some(oof_settings(state, session, oof).await);
+    }
+    if let Some(info) = &req.device_information {
+        resp.device_information = Some(store_device_information(state, session, info).await?);
+    }
+    if req.user_information.as_ref().is_some_and(|u| u.get.is_some()) {
+        resp.user_information = Some(user_information(state, session).await);
+    }
+    Ok(resp)
+}
+
+/// Record DeviceInformation in the device registry. Sent with Settings, or
+/// inside Provision from 14.1 on.
+pub async fn store_device_information(state: &AppState, session: &SyncSession<'_>, req: &DeviceInformationRequest) -> Result<SettingStatus> {
+    let set = &req.set;
+    let info = DeviceInformation {
+        model: set.model.clone(),
+        imei: set.imei.clone(),
+        friendly_name: set.friendly_name.clone(),
+        os: set.os.clone(),
+        os_language: set.os_language.clone(),
+        phone_number: set.phone_number.clone(),
+        mobile_operator: set.mobile_operator.clone(),
+    };
+    state.storage.set_device_information(session.owner, session.device_id, &info).await?;
+    tracing::debug!("device {} of {} is {:?} running {:?}", session.device_id, session.owner, info.model, info.os);
+    Ok(SettingStatus { status: SETTINGS_STATUS_SUCCESS })
+}
+
+/// The user's addresses from their CalDAV principal; the first is the primary
+/// one. A principal without addresses falls back to the login name when that
+/// is an address.
+async fn user_information(state: &AppState, session: &SyncSession<'_>) -> UserInformationResponse {
+    let caldav = CaldavClient::new(&state.cfg);
+    let (display_name, mut addresses) = match caldav.user_identity(session.username, session.password).await {
+        Ok(identity) => identity,
+        Err(e) => {
+            tracing::warn!("no user information for {}: {}", session.owner, e);
+            return UserInformationResponse { status: SETTINGS_STATUS_SERVER_UNAVAILABLE, get: None };
+        }
+    };
+    if addresses.is_empty() && session.owner.contains('@') {
+        addresses.push(session.owner.to_string());
+    }
+    let get = if session.version >= ProtocolVersion::V14_1 {
+        UserInformation {
+            accounts: Some(UserAccounts {
+                account: vec![UserAccount {
+                    user_display_name: display_name,
+                    email_addresses: EmailAddresses { primary_smtp_address: addresses.first().cloned(), smtp_address: addresses },
+                    ..Default::default()
+                }],
+            }),
+            ..Default::default()
+        }
+    } else {
+        UserInformation { email_addresses: Some(EmailAddresses { smtp_address: addresses, primary_smtp_address: None }), ..Default::default() }
+    };
+    UserInformationResponse { status: SETTINGS_STATUS_SUCCESS, get: Some(get) }
+}
+
+/// Settings dates look like `2025-06-01T08:00:00.000Z`.
+fn settings_date(dt: DateTime<Utc>) -> String {
+    dt.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
+}
+
+/// JMAP UTCDates look like `2025-06-01T08:00:00Z`.
+fn jmap_date(dt: DateTime<Utc>) -> String {
+    dt.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+/// Out-of-office Get or Set against the JMAP vacation response.
+async fn oof_settings(state: &AppState, session: &SyncSession<'_>, req: &OofRequest) -> OofResponse {
+    let status = |status: u32| OofResponse { status, get: None };
+    let jmap = JmapClient::new(&state.cfg);
+    let current = match jmap.get_vacation(session.username, session.password).await {
+        Ok(v) => v,
+        Err(e) => {
+            tracing::warn!("no vacation response for {}: {}", session.owner, e);
+            return status(SETTINGS_STATUS_SERVER_UNAVAILABLE);
+        }
+    };
+    match (&req.get, &req.set) {
+        (Some(get), None) => OofResponse { status: SETTINGS_STATUS_SUCCESS, get: Some(oof_from_vacation(&current, &get.body_type)) },
+        (None, Some(set)) => {
+            let Some(vacation) = vacation_from_oof(set, current) else {
+                return status(SETTINGS_STATUS_INVALID_ARGUMENTS);
+            };
+            match jmap.set_vacation(session.username, session.password, &vacation).await {
+                Ok(()) => status(SETTINGS_STATUS_SUCCESS),
+                Err(e) => {
+                    tracing::warn!("could not update the vacation response of {}: {}", session.owner, e);
+                    status(SETTINGS_STATUS_SERVER_UNAVAILABLE)
+                }
+            }
+        }
+        _ => status(SETTINGS_STATUS_PROTOCOL_ERROR),
+    }
+}
+
+/// Present the vacation response as out-of-office settings. Stalwart answers
+/// every sender alike, so the same reply is returned for all three audiences.
+fn oof_from_vacation(vacation: &VacationResponse, body_type: &str) -> OofSettings {
+    let from = vacation.from_date.as_deref().and_then(parse_eas_date);
+    let to = vacation.to_date.as_deref().and_then(parse_eas_date);
+    let oof_state = match (vacation.is_enabled, from.is_some() || to.is_some()) {
+        (false, _) => OOF_DISABLED,
+        (true, false) => OOF_GLOBAL,
+        (true, true) => OOF_TIME_BASED,
+    };
+    let html = body_type.eq_ignore_ascii_case(BODY_TYPE_HTML);
+    let reply = if html {
+        vacation.html_body.clone().or_else(|| vacation.text_body.as_deref().map(utils::text_to_html))
+    } else {
+        vacation.text_body.clone().or_else(|| vacation.html_body.as_deref().map(utils::html_to_text))
+    };
+    let message = |internal: bool, known: bool, unknown: bool| OofMessage {
+        applies_to_internal: internal.then_some(()),
+        applies_to_external_known: known.then_some(()),
+        applies_to_external_unknown: unknown.then_some(()),
+        enabled: Some(reply.is_some()),
+        reply_message: reply.clone(),
+        body_type: Some(if html { BODY_TYPE_HTML } else { BODY_TYPE_TEXT }.to_string()),
+    };
+    OofSettings {
+        oof_state: Some(oof_state),
+        start_time: from.filter(|_| oof_state == OOF_TIME_BASED).map(settings_date),
+        end_time: to.filter(|_| oof_state == OOF_TIME_BASED).map(settings_date),
+        oof_message: vec![message(true, false, false), message(false, true, false), message(false, false, true)],
+    }
+}
+
+/// Apply out-of-office settings to the vacation response. The reply is the
+/// first enabled message, the internal one if possible; a Set whose messages
+/// are all disabled turns the vacation response off. Returns None if a
+/// time-based state lacks its times.
+fn vacation_from_oof(set: &OofSettings, mut vacation: VacationResponse) -> Option<VacationResponse> {
+    match set.oof_state {
+        Some(OOF_DISABLED) => vacation.is_enabled = false,
+        Some(OOF_GLOBAL) => {
+            vacation.is_enabled = true;
+            vacation.from_date = None;
+            vacation.to_date = None;
+        }
+        Some(OOF_TIME_BASED) => {
+            let from = parse_eas_date(set.start_time.as_deref()?)?;
+            let to = parse_eas_date(set.end_time.as_deref()?)?;
+            if to <= from {
+                return None;
+            }
+            vacation.is_enabled = true;
+            vacation.from_date = Some(jmap_date(from));
+            vacation.to_date = Some(jmap_date(to));
+        }
+        Some(_) => return None,
+        None => {}
+    }
+    if !set.oof_message.is_empty() {
+        let mut enabled: Vec<&OofMessage> = set.oof_message.iter().filter(|m| m.enabled == Some(true)).collect();
+        enabled.sort_by_key(|m| m.applies_to_internal.is_none());
+        match enabled.first() {
+            Some(message) => {
+                let text = message.reply_message.clone().unwrap_or_default();
+                if message.body_type.as_deref().is_some_and(|t| t.eq_ignore_ascii_case(BODY_TYPE_HTML)) {
+                    vacation.text_body = Some(utils::html_to_text(&text));
+                    vacation.html_body = Some(text);
+                } else {
+                    vacation.text_body = Some(text);
+                    vacation.html_body = None;
+                }
+            }
+            None => vacation.is_enabled = false,
+        }
+    }
+    Some(vacation)
+}
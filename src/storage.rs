@@ -1,10 +1,27 @@
 use sqlx::{SqlitePool, sqlite::SqlitePoolOptions, Row};
 use std::path::Path;
 use anyhow::Result;
+use crate::models::{CalendarFolder, ChangeKind, Device, DeviceInformation, DeviceStatus, PendingChange};
+
+/// Schema migrations in the order they are applied. Each runs once and is
+/// recorded in `schema_migrations`.
+const MIGRATIONS: &[(&str, &str)] = &[
+    ("001_init", include_str!("../migrations/001_init.sql")),
+    ("002_folder_sync", include_str!("../migrations/002_folder_sync.sql")),
+    ("003_sync_pending", include_str!("../migrations/003_sync_pending.sql")),
+    ("004_ping", include_str!("../migrations/004_ping.sql")),
+    ("005_devices", include_str!("../migrations/005_devices.sql")),
+    ("006_device_wipe", include_str!("../migrations/006_device_wipe.sql")),
+    ("007_device_state", include_str!("../migrations/007_device_state.sql")),
+    ("008_sync_filter", include_str!("../migrations/008_sync_filter.sql")),
+    ("009_device_information", include_str!("../migrations/009_device_information.sql")),
+    ("010_sync_retry", include_str!("../migrations/010_sync_retry.sql")),
+];
 
 #[derive(Clone)]
 pub struct Storage {
     pub pool: SqlitePool,
+    #[allow(dead_code)]
     pub db_path: String,
 }
 
@@ -20,45 +37,119 @@ impl Storage {
     }
 
     pub async fn run_migrations(&self) -> Result<()> {
-        let sql = include_str!("../migrations/001_init.sql");
-        sqlx::query(sql).execute(&self.pool).await?;
+        sqlx::query("CREATE TABLE IF NOT EXISTS schema_migrations (name TEXT PRIMARY KEY, applied_at INTEGER DEFAULT (strftime('%s','now')))")
+            .execute(&self.pool).await?;
+        for (name, sql) in MIGRATIONS {
+            let applied = sqlx::query("SELECT 1 FROM schema_migrations WHERE name = ?")
+                .bind(name)
+                .fetch_optional(&self.pool).await?.is_some();
+            if applied {
+                continue;
+            }
+            let mut tx = self.pool.begin().await?;
+            sqlx::raw_sql(sql).execute(&mut *tx).await?;
+            sqlx::query("INSERT INTO schema_migrations (name) VALUES (?)").bind(name).execute(&mut *tx).await?;
+            tx.commit().await?;
+            tracing::info!("applied migration {}", name);
+        }
         Ok(())
     }
 
-    pub async fn get_sync_key(&self, owner: &str, collection_id: &str) -> Result<Option<String>> {
-        let row = sqlx::query("SELECT sync_key FROM sync_state WHERE owner = ? AND collection_id = ?")
-            .bind(owner).bind(collection_id)
+    pub async fn get_sync_key(&self, owner: &str, device_id: &str, collection_id: &str) -> Result<Option<String>> {
+        let row = sqlx::query("SELECT sync_key FROM sync_state WHERE owner = ? AND device_id = ? AND collection_id = ?")
+            .bind(owner).bind(device_id).bind(collection_id)
             .fetch_optional(&self.pool).await?;
         Ok(row.map(|r| r.get::<String,_>("sync_key")))
     }
 
-    pub async fn set_sync_key(&self, owner: &str, collection_id: &str, sync_key: &str, token: Option<&str>) -> Result<()> {
+    /// Collection sync-token or ctag recorded with the current sync key, if any.
+    pub async fn get_sync_token(&self, owner: &str, device_id: &str, collection_id: &str) -> Result<Option<String>> {
+        let row = sqlx::query("SELECT last_sync_token FROM sync_state WHERE owner = ? AND device_id = ? AND collection_id = ?")
+            .bind(owner).bind(device_id).bind(collection_id)
+            .fetch_optional(&self.pool).await?;
+        Ok(row.and_then(|r| r.get::<Option<String>,_>("last_sync_token")).filter(|t| !t.is_empty()))
+    }
+
+    /// FilterType the device last synced the collection with.
+    pub async fn get_sync_filter(&self, owner: &str, device_id: &str, collection_id: &str) -> Result<Option<u8>> {
+        let row = sqlx::query("SELECT filter_type FROM sync_state WHERE owner = ? AND device_id = ? AND collection_id = ?")
+            .bind(owner).bind(device_id).bind(collection_id)
+            .fetch_optional(&self.pool).await?;
+        Ok(row.and_then(|r| r.get::<Option<i64>,_>("filter_type")).map(|f| f as u8))
+    }
+
+    pub async fn set_sync_key(&self, owner: &str, device_id: &str, collection_id: &str, sync_key: &str, token: Option<&str>, filter_type: Option<u8>) -> Result<()> {
         let token = token.unwrap_or("");
-        sqlx::query("INSERT INTO sync_state (owner, collection_id, sync_key, last_sync_token, last_sync_ts) VALUES (?, ?, ?, ?, strftime('%s','now')) ON CONFLICT(owner, collection_id) DO UPDATE SET sync_key=excluded.sync_key, last_sync_token=excluded.last_sync_token, last_sync_ts=strftime('%s','now')")
-            .bind(owner).bind(collection_id).bind(sync_key).bind(token)
+        sqlx::query("INSERT INTO sync_state (owner, device_id, collection_id, sync_key, last_sync_token, last_sync_ts, filter_type) VALUES (?, ?, ?, ?, ?, strftime('%s','now'), ?) ON CONFLICT(owner, device_id, collection_id) DO UPDATE SET sync_key=excluded.sync_key, last_sync_token=excluded.last_sync_token, last_sync_ts=strftime('%s','now'), filter_type=excluded.filter_type, previous_sync_key=NULL, last_response=NULL")
+            .bind(owner).bind(device_id).bind(collection_id).bind(sync_key).bind(token).bind(filter_type.map(|f| f as i64))
             .execute(&self.pool).await?;
         Ok(())
     }
 
-    pub async fn upsert_item_map(&self, owner: &str, caldav_href: &str, resource_href: &str, server_id: &str, uid: &str, etag: &str) -> Result<()> {
-        sqlx::query("INSERT INTO items_map (owner, caldav_href, resource_href, server_id, uid, etag, last_sync) VALUES (?, ?, ?, ?, ?, ?, strftime('%s','now')) ON CONFLICT(server_id) DO UPDATE SET resource_href=excluded.resource_href, uid=excluded.uid, etag=excluded.etag, last_sync=strftime('%s','now')")
-            .bind(owner).bind(caldav_href).bind(resource_href).bind(server_id).bind(uid).bind(etag)
+    /// Record the response the current sync key was issued in, and the key the
+    /// client sent to get it. Cleared by the next `set_sync_key`.
+    pub async fn set_sync_response(&self, owner: &str, device_id: &str, collection_id: &str, previous_sync_key: &str, response: &[u8]) -> Result<()> {
+        sqlx::query("UPDATE sync_state SET previous_sync_key = ?, last_response = ? WHERE owner = ? AND device_id = ? AND collection_id = ?")
+            .bind(previous_sync_key).bind(response).bind(owner).bind(device_id).bind(collection_id)
             .execute(&self.pool).await?;
         Ok(())
     }
 
-    pub async fn get_item_by_server_id(&self, server_id: &str) -> Result<Option<(i64, String)>> {
-        let row = sqlx::query("SELECT id, resource_href FROM items_map WHERE server_id = ?")
-            .bind(server_id)
+    /// The response last issued for `previous_sync_key`, if that is the key
+    /// the current one replaced.
+    pub async fn get_sync_response(&self, owner: &str, device_id: &str, collection_id: &str, previous_sync_key: &str) -> Result<Option<Vec<u8>>> {
+        let row = sqlx::query("SELECT last_response FROM sync_state WHERE owner = ? AND device_id = ? AND collection_id = ? AND previous_sync_key = ?")
+            .bind(owner).bind(device_id).bind(collection_id).bind(previous_sync_key)
+            .fetch_optional(&self.pool).await?;
+        Ok(row.and_then(|r| r.get::<Option<Vec<u8>>,_>("last_response")))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn upsert_item_map(&self, owner: &str, device_id: &str, caldav_href: &str, resource_href: &str, server_id: &str, uid: &str, etag: &str) -> Result<()> {
+        sqlx::query("INSERT INTO items_map (owner, device_id, caldav_href, resource_href, server_id, uid, etag, last_sync) VALUES (?, ?, ?, ?, ?, ?, ?, strftime('%s','now')) ON CONFLICT(owner, device_id, server_id) DO UPDATE SET resource_href=excluded.resource_href, uid=excluded.uid, etag=excluded.etag, last_sync=strftime('%s','now')")
+            .bind(owner).bind(device_id).bind(caldav_href).bind(resource_href).bind(server_id).bind(uid).bind(etag)
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    pub async fn get_item_by_server_id(&self, owner: &str, device_id: &str, server_id: &str) -> Result<Option<(i64, String)>> {
+        let row = sqlx::query("SELECT id, resource_href FROM items_map WHERE owner = ? AND device_id = ? AND server_id = ?")
+            .bind(owner).bind(device_id).bind(server_id)
             .fetch_optional(&self.pool).await?;
         Ok(row.map(|r| (r.get::<i64,_>("id"), r.get::<String,_>("resource_href"))))
     }
 
-    pub async fn delete_item_by_server_id(&self, server_id: &str) -> Result<()> {
-        sqlx::query("DELETE FROM items_map WHERE server_id = ?").bind(server_id).execute(&self.pool).await?;
+    /// Record a new ETag for an item the client already holds, keeping the rest.
+    pub async fn set_item_etag(&self, owner: &str, device_id: &str, server_id: &str, etag: &str) -> Result<()> {
+        sqlx::query("UPDATE items_map SET etag = ?, last_sync = strftime('%s','now') WHERE owner = ? AND device_id = ? AND server_id = ?")
+            .bind(etag).bind(owner).bind(device_id).bind(server_id)
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    pub async fn delete_item_by_server_id(&self, owner: &str, device_id: &str, server_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM items_map WHERE owner = ? AND device_id = ? AND server_id = ?")
+            .bind(owner).bind(device_id).bind(server_id)
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Items of one calendar as last sent to the client: (server_id, resource_href, etag).
+    pub async fn list_items(&self, owner: &str, device_id: &str, caldav_href: &str) -> Result<Vec<(String, String, String)>> {
+        let rows = sqlx::query("SELECT server_id, resource_href, etag FROM items_map WHERE owner = ? AND device_id = ? AND caldav_href = ?")
+            .bind(owner).bind(device_id).bind(caldav_href)
+            .fetch_all(&self.pool).await?;
+        Ok(rows.iter().map(|r| (r.get("server_id"), r.get("resource_href"), r.get::<Option<String>,_>("etag").unwrap_or_default())).collect())
+    }
+
+    pub async fn delete_items_for_calendar(&self, owner: &str, device_id: &str, caldav_href: &str) -> Result<()> {
+        sqlx::query("DELETE FROM items_map WHERE owner = ? AND device_id = ? AND caldav_href = ?")
+            .bind(owner).bind(device_id).bind(caldav_href)
+            .execute(&self.pool).await?;
         Ok(())
     }
 
+    #[allow(dead_code)]
     pub async fn list_changes_since(&self, owner: &str, since_unix_ts: i64) -> Result<Vec<(String, String)>> {
         let rows = sqlx::query("SELECT server_id, resource_href FROM items_map WHERE owner = ? AND last_sync >= ?")
             .bind(owner).bind(since_unix_ts)
@@ -69,4 +160,230 @@ impl Storage {
         }
         Ok(res)
     }
+
+    pub async fn list_calendars(&self, owner: &str) -> Result<Vec<CalendarFolder>> {
+        let rows = sqlx::query("SELECT collection_id, caldav_href, display_name, folder_type FROM calendars WHERE owner = ? ORDER BY caldav_href")
+            .bind(owner)
+            .fetch_all(&self.pool).await?;
+        Ok(rows.iter().map(calendar_folder_from_row).collect())
+    }
+
+    pub async fn get_calendar(&self, owner: &str, collection_id: &str) -> Result<Option<CalendarFolder>> {
+        let row = sqlx::query("SELECT collection_id, caldav_href, display_name, folder_type FROM calendars WHERE owner = ? AND collection_id = ?")
+            .bind(owner).bind(collection_id)
+            .fetch_optional(&self.pool).await?;
+        Ok(row.as_ref().map(calendar_folder_from_row))
+    }
+
+    pub async fn upsert_calendar(&self, owner: &str, folder: &CalendarFolder) -> Result<()> {
+        sqlx::query("INSERT INTO calendars (owner, caldav_href, collection_id, display_name, folder_type) VALUES (?, ?, ?, ?, ?) ON CONFLICT(owner, caldav_href) DO UPDATE SET collection_id=excluded.collection_id, display_name=excluded.display_name, folder_type=excluded.folder_type")
+            .bind(owner).bind(&folder.caldav_href).bind(&folder.collection_id).bind(&folder.display_name).bind(folder.folder_type)
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    pub async fn delete_calendar(&self, owner: &str, collection_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM calendars WHERE owner = ? AND collection_id = ?")
+            .bind(owner).bind(collection_id)
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    pub async fn get_folder_sync_key(&self, owner: &str, device_id: &str) -> Result<Option<String>> {
+        let row = sqlx::query("SELECT sync_key FROM folder_sync_state WHERE owner = ? AND device_id = ?")
+            .bind(owner).bind(device_id)
+            .fetch_optional(&self.pool).await?;
+        Ok(row.map(|r| r.get::<String,_>("sync_key")))
+    }
+
+    /// Folders the device was sent with its current hierarchy sync key.
+    pub async fn get_folder_snapshot(&self, owner: &str, device_id: &str) -> Result<Vec<CalendarFolder>> {
+        let rows = sqlx::query("SELECT collection_id, caldav_href, display_name, folder_type FROM folder_snapshot WHERE owner = ? AND device_id = ? ORDER BY caldav_href")
+            .bind(owner).bind(device_id)
+            .fetch_all(&self.pool).await?;
+        Ok(rows.iter().map(calendar_folder_from_row).collect())
+    }
+
+    /// Record a new hierarchy sync key together with the folders sent under it.
+    pub async fn set_folder_snapshot(&self, owner: &str, device_id: &str, sync_key: &str, folders: &[CalendarFolder]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT INTO folder_sync_state (owner, device_id, sync_key, last_sync_ts) VALUES (?, ?, ?, strftime('%s','now')) ON CONFLICT(owner, device_id) DO UPDATE SET sync_key=excluded.sync_key, last_sync_ts=strftime('%s','now')")
+            .bind(owner).bind(device_id).bind(sync_key)
+            .execute(&mut *tx).await?;
+        sqlx::query("DELETE FROM folder_snapshot WHERE owner = ? AND device_id = ?")
+            .bind(owner).bind(device_id)
+            .execute(&mut *tx).await?;
+        for f in folders {
+            sqlx::query("INSERT INTO folder_snapshot (owner, device_id, collection_id, caldav_href, display_name, folder_type) VALUES (?, ?, ?, ?, ?, ?)")
+                .bind(owner).bind(device_id).bind(&f.collection_id).bind(&f.caldav_href).bind(&f.display_name).bind(f.folder_type)
+                .execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn add_pending_changes(&self, owner: &str, device_id: &str, collection_id: &str, changes: &[PendingChange]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for c in changes {
+            sqlx::query("INSERT INTO sync_pending (owner, device_id, collection_id, kind, server_id, resource_href, etag, calendar_data) VALUES (?, ?, ?, ?, ?, ?, ?, ?)")
+                .bind(owner).bind(device_id).bind(collection_id).bind(c.kind.as_str()).bind(&c.server_id).bind(&c.resource_href).bind(&c.etag).bind(&c.calendar_data)
+                .execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Remove and return up to `limit` queued changes, oldest first.
+    pub async fn take_pending_changes(&self, owner: &str, device_id: &str, collection_id: &str, limit: usize) -> Result<Vec<PendingChange>> {
+        let mut tx = self.pool.begin().await?;
+        let rows = sqlx::query("SELECT id, kind, server_id, resource_href, etag, calendar_data FROM sync_pending WHERE owner = ? AND device_id = ? AND collection_id = ? ORDER BY id LIMIT ?")
+            .bind(owner).bind(device_id).bind(collection_id).bind(limit as i64)
+            .fetch_all(&mut *tx).await?;
+        let mut changes = Vec::new();
+        for r in &rows {
+            sqlx::query("DELETE FROM sync_pending WHERE id = ?").bind(r.get::<i64,_>("id")).execute(&mut *tx).await?;
+            let Some(kind) = ChangeKind::parse(r.get("kind")) else { continue };
+            changes.push(PendingChange {
+                kind,
+                server_id: r.get("server_id"),
+                resource_href: r.get("resource_href"),
+                etag: r.get("etag"),
+                calendar_data: r.get("calendar_data"),
+            });
+        }
+        tx.commit().await?;
+        Ok(changes)
+    }
+
+    pub async fn has_pending_changes(&self, owner: &str, device_id: &str, collection_id: &str) -> Result<bool> {
+        let row = sqlx::query("SELECT 1 FROM sync_pending WHERE owner = ? AND device_id = ? AND collection_id = ? LIMIT 1")
+            .bind(owner).bind(device_id).bind(collection_id)
+            .fetch_optional(&self.pool).await?;
+        Ok(row.is_some())
+    }
+
+    pub async fn clear_pending_changes(&self, owner: &str, device_id: &str, collection_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM sync_pending WHERE owner = ? AND device_id = ? AND collection_id = ?")
+            .bind(owner).bind(device_id).bind(collection_id)
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// HeartbeatInterval and folder ids of the device's last Ping.
+    pub async fn get_ping_state(&self, owner: &str, device_id: &str) -> Result<Option<(u32, Vec<String>)>> {
+        let row = sqlx::query("SELECT heartbeat_interval, folder_ids FROM ping_state WHERE owner = ? AND device_id = ?")
+            .bind(owner).bind(device_id)
+            .fetch_optional(&self.pool).await?;
+        Ok(row.map(|r| {
+            let folders: String = r.get("folder_ids");
+            (r.get::<i64,_>("heartbeat_interval") as u32, folders.split('\n').filter(|f| !f.is_empty()).map(str::to_string).collect())
+        }))
+    }
+
+    pub async fn set_ping_state(&self, owner: &str, device_id: &str, heartbeat_interval: u32, folder_ids: &[String]) -> Result<()> {
+        sqlx::query("INSERT INTO ping_state (owner, device_id, heartbeat_interval, folder_ids) VALUES (?, ?, ?, ?) ON CONFLICT(owner, device_id) DO UPDATE SET heartbeat_interval=excluded.heartbeat_interval, folder_ids=excluded.folder_ids")
+            .bind(owner).bind(device_id).bind(heartbeat_interval).bind(folder_ids.join("\n"))
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// The device's acknowledged policy key and the key it was last offered.
+    pub async fn get_policy_keys(&self, owner: &str, device_id: &str) -> Result<(Option<u32>, Option<u32>)> {
+        let row = sqlx::query("SELECT policy_key, pending_policy_key FROM devices WHERE owner = ? AND device_id = ?")
+            .bind(owner).bind(device_id)
+            .fetch_optional(&self.pool).await?;
+        Ok(row.map(|r| (
+            r.get::<Option<i64>,_>("policy_key").map(|k| k as u32),
+            r.get::<Option<i64>,_>("pending_policy_key").map(|k| k as u32),
+        )).unwrap_or_default())
+    }
+
+    /// Record the temporary key sent in the first Provision response.
+    pub async fn set_pending_policy_key(&self, owner: &str, device_id: &str, key: u32) -> Result<()> {
+        sqlx::query("INSERT INTO devices (owner, device_id, pending_policy_key) VALUES (?, ?, ?) ON CONFLICT(owner, device_id) DO UPDATE SET pending_policy_key=excluded.pending_policy_key")
+            .bind(owner).bind(device_id).bind(key as i64)
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Make `key` the device's policy key once it has acknowledged the policy.
+    pub async fn confirm_policy_key(&self, owner: &str, device_id: &str, key: u32) -> Result<()> {
+        sqlx::query("UPDATE devices SET policy_key = ?, pending_policy_key = NULL, provisioned_ts = strftime('%s','now') WHERE owner = ? AND device_id = ?")
+            .bind(key as i64).bind(owner).bind(device_id)
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Record that a device has connected, with what it reports about itself.
+    pub async fn register_device(&self, owner: &str, device_id: &str, device_type: &str, user_agent: &str) -> Result<()> {
+        sqlx::query("INSERT INTO devices (owner, device_id, device_type, user_agent, first_seen_ts, last_seen_ts) VALUES (?, ?, ?, ?, strftime('%s','now'), strftime('%s','now')) ON CONFLICT(owner, device_id) DO UPDATE SET device_type=excluded.device_type, user_agent=excluded.user_agent, last_seen_ts=excluded.last_seen_ts")
+            .bind(owner).bind(device_id).bind(device_type).bind(user_agent)
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    pub async fn get_device_status(&self, owner: &str, device_id: &str) -> Result<DeviceStatus> {
+        let row = sqlx::query("SELECT status FROM devices WHERE owner = ? AND device_id = ?")
+            .bind(owner).bind(device_id)
+            .fetch_optional(&self.pool).await?;
+        Ok(row.and_then(|r| DeviceStatus::parse(&r.get::<String,_>("status"))).unwrap_or(DeviceStatus::Active))
+    }
+
+    /// Set a device's status, stamping the wipe request or acknowledgment time.
+    /// Returns false if the device is unknown.
+    pub async fn set_device_status(&self, owner: &str, device_id: &str, status: DeviceStatus) -> Result<bool> {
+        let stamp = match status {
+            DeviceStatus::WipePending | DeviceStatus::AccountWipePending => ", wipe_requested_ts = strftime('%s','now'), wipe_acked_ts = NULL",
+            DeviceStatus::Blocked => ", wipe_acked_ts = strftime('%s','now')",
+            DeviceStatus::Active => ", wipe_requested_ts = NULL, wipe_acked_ts = NULL",
+        };
+        let result = sqlx::query(&format!("UPDATE devices SET status = ?{} WHERE owner = ? AND device_id = ?", stamp))
+            .bind(status.as_str()).bind(owner).bind(device_id)
+            .execute(&self.pool).await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Record what a device reported about itself in Settings DeviceInformation.
+    pub async fn set_device_information(&self, owner: &str, device_id: &str, info: &DeviceInformation) -> Result<()> {
+        sqlx::query("UPDATE devices SET model = ?, imei = ?, friendly_name = ?, os = ?, os_language = ?, phone_number = ?, mobile_operator = ? WHERE owner = ? AND device_id = ?")
+            .bind(&info.model).bind(&info.imei).bind(&info.friendly_name).bind(&info.os).bind(&info.os_language).bind(&info.phone_number).bind(&info.mobile_operator)
+            .bind(owner).bind(device_id)
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    pub async fn list_devices(&self, owner: &str) -> Result<Vec<Device>> {
+        let rows = sqlx::query("SELECT device_id, device_type, user_agent, first_seen_ts, last_seen_ts, status, policy_key, wipe_requested_ts, wipe_acked_ts, model, imei, friendly_name, os, os_language, phone_number, mobile_operator FROM devices WHERE owner = ? ORDER BY device_id")
+            .bind(owner)
+            .fetch_all(&self.pool).await?;
+        Ok(rows.iter().map(|r| Device {
+            device_id: r.get("device_id"),
+            device_type: r.get("device_type"),
+            user_agent: r.get("user_agent"),
+            first_seen_ts: r.get("first_seen_ts"),
+            last_seen_ts: r.get("last_seen_ts"),
+            status: DeviceStatus::parse(&r.get::<String,_>("status")).unwrap_or(DeviceStatus::Active),
+            policy_key: r.get::<Option<i64>,_>("policy_key").map(|k| k as u32),
+            wipe_requested_ts: r.get("wipe_requested_ts"),
+            wipe_acked_ts: r.get("wipe_acked_ts"),
+            information: DeviceInformation {
+                model: r.get("model"),
+                imei: r.get("imei"),
+                friendly_name: r.get("friendly_name"),
+                os: r.get("os"),
+                os_language: r.get("os_language"),
+                phone_number: r.get("phone_number"),
+                mobile_operator: r.get("mobile_operator"),
+            },
+        }).collect())
+    }
+}
+
+fn calendar_folder_from_row(r: &sqlx::sqlite::SqliteRow) -> CalendarFolder {
+    CalendarFolder {
+        collection_id: r.get("collection_id"),
+        caldav_href: r.get("caldav_href"),
+        display_name: r.get("display_name"),
+        folder_type: r.get::<i64,_>("folder_type") as u32,
+    }
 }
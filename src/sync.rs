@@ -1,9 +1,21 @@
 use crate::models::AppState;
+use crate::utils;
 use crate::caldav::CaldavClient;
 use crate::storage::Storage;
+use crate::caldav::parse_multistatus;
+use crate::eas_models::{AddResult, ApplicationData, Attachment, Attachments, Body, BodyPreference, ClientCommands, EstimateRequestCollection, EstimateResponse, EstimateResponseCollection, FetchResult, Folder, FolderChanges, FolderDelete, FolderSyncResponse, ItemRef, ItemStatus, Location, ServerCommands, ServerItem, SyncRequestCollection, SyncResponse, SyncResponseCollection, SyncResponseCollections, SyncResponses};
+use crate::models::{CalendarFolder, ChangeKind, PendingChange};
+use crate::eas_request::ProtocolVersion;
+use crate::eas_marshaller::{application_data_to_ics, event_attachments, exclude_instance, ics_to_application_data, keep_server_properties};
+use crate::wbxml::Wbxml;
+use crate::wbxml_serde::{from_element, to_element};
 use anyhow::Result;
 use std::sync::Arc;
-use chrono::Utc;
+use chrono::{DateTime, Months, Utc};
+use reqwest::Url;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tokio::time::Instant;
 use uuid::Uuid;
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
@@ -16,7 +28,7 @@ pub fn generate_server_id(secret: &str, resource_href: &str) -> String {
     mac.update(resource_href.as_bytes());
     let result = mac.finalize().into_bytes();
     // Make sure Engine trait is in scope
-    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(&result)
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(result)
 }
 
 pub fn generate_change_key(etag: &str) -> String {
@@ -27,27 +39,767 @@ pub fn generate_change_key(etag: &str) -> String {
     base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(payload.as_bytes())
 }
 
-/// Perform Sync: list changes via CalDAV REPORT, map them to Add/Change/Delete
-pub async fn perform_sync(state: Arc<AppState>, owner: &str, collection_id: &str, _incoming_sync_key: &str, _window_size: usize, username_for_caldav: &str, password_for_caldav: &str) -> Result<String> {
+/// Reshape outgoing calendar data for the protocol version the client speaks.
+/// Items are built in the 12.x-14.x shape; 2.5 lacks AirSyncBase bodies and
+/// 16.x moves locations and exception identifiers to AirSyncBase and is the
+/// only version with calendar attachments.
+pub fn shape_for_version(data: &mut ApplicationData, version: ProtocolVersion) {
+    if !version.has_airsyncbase() {
+        if let Some(body) = data.body.take() {
+            data.legacy_body = body.data;
+            data.legacy_body_truncated = body.truncated;
+        }
+        for ex in data.exceptions.iter_mut().flat_map(|e| e.exception.iter_mut()) {
+            ex.body = None;
+        }
+    }
+    if version.has_instance_ids() {
+        if let Some(name) = data.location.take() {
+            data.structured_location = Some(Location { display_name: Some(name), ..Default::default() });
+        }
+        for ex in data.exceptions.iter_mut().flat_map(|e| e.exception.iter_mut()) {
+            ex.instance_id = ex.exception_start_time.take();
+        }
+    } else {
+        if let Some(loc) = data.structured_location.take() {
+            data.location = data.location.take().or(loc.display_name);
+        }
+        data.client_uid = None;
+        data.instance_id = None;
+        data.attachments = None;
+    }
+}
+
+// AirSyncBase body types
+const BODY_TYPE_PLAIN: u8 = 1;
+const BODY_TYPE_HTML: u8 = 2;
+
+/// Longest body preview a client may ask for.
+const MAX_PREVIEW: usize = 255;
+
+/// The first `max` bytes of `text`, cut at a character boundary.
+fn truncate_utf8(text: &str, max: usize) -> &str {
+    let mut end = max.min(text.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Fit one plain-text body to a BodyPreference: convert it to HTML if asked,
+/// cut it at TruncationSize (or drop it entirely under AllOrNone) and add the
+/// requested preview.
+fn apply_preference(body: &mut Body, pref: &BodyPreference) {
+    let text = body.data.take().unwrap_or_default();
+    if let Some(chars) = pref.preview.filter(|p| *p > 0) {
+        body.preview = Some(text.chars().take((chars as usize).min(MAX_PREVIEW)).collect());
+    }
+    let data = if pref.r#type == BODY_TYPE_HTML { utils::text_to_html(&text) } else { text };
+    body.r#type = pref.r#type;
+    body.estimated_data_size = Some(data.len() as u32);
+    match pref.truncation_size.map(|t| t as usize).filter(|t| *t < data.len()) {
+        None => {
+            body.truncated = None;
+            body.data = Some(data);
+        }
+        Some(_) if pref.all_or_none == Some(true) => {
+            body.truncated = Some(true);
+            body.data = None;
+        }
+        Some(size) => {
+            body.truncated = Some(true);
+            body.data = Some(truncate_utf8(&data, size).to_string());
+        }
+    }
+}
+
+/// Fit outgoing bodies, which are plain text, to the client's BodyPreferences:
+/// the plain-text preference if there is one, else the HTML one. Without
+/// either, bodies go out whole.
+pub fn apply_body_preference(data: &mut ApplicationData, prefs: &[BodyPreference]) {
+    let Some(pref) = prefs.iter().find(|p| p.r#type == BODY_TYPE_PLAIN).or_else(|| prefs.iter().find(|p| p.r#type == BODY_TYPE_HTML)) else {
+        return;
+    };
+    let exception_bodies = data.exceptions.iter_mut().flat_map(|e| e.exception.iter_mut()).filter_map(|e| e.body.as_mut());
+    for body in data.body.iter_mut().chain(exception_bodies) {
+        apply_preference(body, pref);
+    }
+}
+
+/// The FileReference of an event's attachment: the item's ServerId and the
+/// attachment's position among its ATTACHs.
+pub fn file_reference(server_id: &str, index: usize) -> String {
+    format!("{}:{}", server_id, index)
+}
+
+/// Split a FileReference into ServerId and attachment position.
+pub fn parse_file_reference(reference: &str) -> Option<(&str, usize)> {
+    let (server_id, index) = reference.rsplit_once(':')?;
+    Some((server_id, index.parse().ok()?))
+}
+
+/// AirSyncBase attachment Method of an ordinary file.
+const ATTACHMENT_METHOD_NORMAL: u8 = 1;
+
+fn attachment_list(ics: &str, server_id: &str) -> Result<Option<Attachments>> {
+    let attachment: Vec<Attachment> = event_attachments(ics)?.into_iter().enumerate().map(|(i, a)| Attachment {
+        display_name: Some(a.display_name),
+        file_reference: file_reference(server_id, i),
+        method: ATTACHMENT_METHOD_NORMAL,
+        estimated_data_size: a.size.unwrap_or_default(),
+        content_type: a.content_type,
+        ..Default::default()
+    }).collect();
+    Ok((!attachment.is_empty()).then_some(Attachments { attachment }))
+}
+
+/// The ApplicationData a client gets for the calendar object `ics` stored as
+/// `server_id`: bodies fitted to `body_preference`, attachments listed, and
+/// the whole shaped for the client's protocol version.
+pub fn outgoing_data(ics: &str, server_id: &str, session: &SyncSession<'_>, body_preference: &[BodyPreference]) -> Result<ApplicationData> {
+    let mut data = ics_to_application_data(ics, session.owner)?;
+    data.attachments = attachment_list(ics, server_id)?;
+    apply_body_preference(&mut data, body_preference);
+    shape_for_version(&mut data, session.version);
+    Ok(data)
+}
+
+/// Bring client-sent calendar data from any protocol version into the 12.x-14.x
+/// shape the converters expect; the inverse of `shape_for_version`.
+pub fn normalize_client_data(data: &mut ApplicationData, version: ProtocolVersion) {
+    if let Some(text) = data.legacy_body.take() {
+        data.body.get_or_insert_with(|| Body { r#type: 1, data: Some(text), ..Default::default() });
+    }
+    if let Some(loc) = data.structured_location.take() {
+        data.location = data.location.take().or(loc.display_name);
+    }
+    if version.has_instance_ids() {
+        if data.uid.is_none() {
+            data.uid = data.client_uid.take();
+        }
+        for ex in data.exceptions.iter_mut().flat_map(|e| e.exception.iter_mut()) {
+            if ex.exception_start_time.is_none() {
+                ex.exception_start_time = ex.instance_id.take();
+            }
+        }
+    }
+}
+
+/// Who is syncing and over which protocol version; shared by every collection
+/// in one Sync request.
+pub struct SyncSession<'a> {
+    pub owner: &'a str,
+    pub device_id: &'a str,
+    pub username: &'a str,
+    pub password: &'a str,
+    pub version: ProtocolVersion,
+}
+
+// FolderSync folder types (MS-ASCMD 2.2.3.186.3)
+const FOLDER_TYPE_DEFAULT_CALENDAR: u32 = 8;
+const FOLDER_TYPE_USER_CALENDAR: u32 = 13;
+
+// FolderSync status codes
+const FOLDER_STATUS_SUCCESS: u32 = 1;
+const FOLDER_STATUS_INVALID_SYNC_KEY: u32 = 9;
+pub const FOLDER_STATUS_SERVER_ERROR: u32 = 6;
+
+/// Fetch the user's calendars from CalDAV and bring the `calendars` table in line
+/// with them. ServerIds are derived from the collection href, so a calendar keeps
+/// its id for as long as it exists on the server.
+async fn refresh_calendars(state: &AppState, session: &SyncSession<'_>) -> Result<Vec<CalendarFolder>> {
+    let caldav = CaldavClient::new(&state.cfg);
+    let mut found = caldav.list_calendars(session.username, session.password).await?;
+    found.sort_by(|a, b| a.href.cmp(&b.href));
+    // Stalwart names the calendar it creates for every account "default"
+    let default_idx = found.iter().position(|c| c.href.trim_end_matches('/').ends_with("/default")).unwrap_or(0);
+
+    let folders: Vec<CalendarFolder> = found.into_iter().enumerate().map(|(i, c)| CalendarFolder {
+        collection_id: generate_server_id(&state.cfg.hmac_secret, &c.href),
+        caldav_href: c.href,
+        display_name: c.display_name,
+        folder_type: if i == default_idx { FOLDER_TYPE_DEFAULT_CALENDAR } else { FOLDER_TYPE_USER_CALENDAR },
+    }).collect();
+
+    for stale in state.storage.list_calendars(session.owner).await? {
+        if !folders.iter().any(|f| f.caldav_href == stale.caldav_href) {
+            state.storage.delete_calendar(session.owner, &stale.collection_id).await?;
+        }
+    }
+    for f in &folders {
+        state.storage.upsert_calendar(session.owner, f).await?;
+    }
+    Ok(folders)
+}
+
+fn to_folder(f: &CalendarFolder) -> Folder {
+    Folder {
+        server_id: f.collection_id.clone(),
+        parent_id: "0".to_string(),
+        display_name: f.display_name.clone(),
+        r#type: f.folder_type,
+    }
+}
+
+/// Changes that take a device from the `known` folder list to `current`.
+fn diff_folders(known: &[CalendarFolder], current: &[CalendarFolder]) -> FolderChanges {
+    let mut changes = FolderChanges::default();
+    for f in current {
+        match known.iter().find(|k| k.collection_id == f.collection_id) {
+            None => changes.add.push(to_folder(f)),
+            Some(k) if k.display_name != f.display_name || k.folder_type != f.folder_type => changes.update.push(to_folder(f)),
+            Some(_) => {}
+        }
+    }
+    for k in known {
+        if !current.iter().any(|f| f.collection_id == k.collection_id) {
+            changes.delete.push(FolderDelete { server_id: k.collection_id.clone() });
+        }
+    }
+    changes.count = (changes.add.len() + changes.update.len() + changes.delete.len()) as u32;
+    changes
+}
+
+/// Perform FolderSync for one device. SyncKey 0 starts over and returns every
+/// calendar as an Add; a known key returns what changed since that key was issued.
+/// Hierarchy sync keys are a per-device counter.
+pub async fn perform_folder_sync(state: Arc<AppState>, session: &SyncSession<'_>, client_key: &str) -> Result<FolderSyncResponse> {
     let storage: &Storage = &state.storage;
+    let stored_key = storage.get_folder_sync_key(session.owner, session.device_id).await?;
+    let known = if client_key == "0" {
+        Vec::new()
+    } else if stored_key.as_deref() == Some(client_key) {
+        storage.get_folder_snapshot(session.owner, session.device_id).await?
+    } else {
+        return Ok(FolderSyncResponse { status: FOLDER_STATUS_INVALID_SYNC_KEY, ..Default::default() });
+    };
+
+    let current = refresh_calendars(&state, session).await?;
+    let changes = diff_folders(&known, &current);
+    let sync_key = if client_key != "0" && changes.count == 0 {
+        client_key.to_string()
+    } else {
+        let next = stored_key.and_then(|k| k.parse::<u64>().ok()).unwrap_or(0) + 1;
+        let key = next.to_string();
+        storage.set_folder_snapshot(session.owner, session.device_id, &key, &current).await?;
+        key
+    };
+
+    Ok(FolderSyncResponse { status: FOLDER_STATUS_SUCCESS, sync_key: Some(sync_key), changes: Some(changes) })
+}
+
+// Sync status codes (MS-ASCMD 2.2.3.177.16)
+const SYNC_STATUS_SUCCESS: u32 = 1;
+const SYNC_STATUS_INVALID_SYNC_KEY: u32 = 3;
+const SYNC_STATUS_CONVERSION_ERROR: u32 = 6;
+const SYNC_STATUS_SERVER_ERROR: u32 = 5;
+const SYNC_STATUS_OBJECT_NOT_FOUND: u32 = 8;
+const SYNC_STATUS_HIERARCHY_CHANGED: u32 = 12;
+
+/// WindowSize used when the client sends none, and the protocol maximum.
+pub const DEFAULT_WINDOW_SIZE: u32 = 100;
+pub const MAX_WINDOW_SIZE: u32 = 512;
+
+/// Perform Sync for one collection. SyncKey 0 (re)starts the collection: the
+/// client gets a fresh key and no items, and everything is sent as Adds on the
+/// next round. A known key diffs the CalDAV collection against `items_map`, the
+/// record of what the client holds, and returns the differences as commands,
+/// at most `window_size` of them; the rest wait in `sync_pending` and
+/// `MoreAvailable` tells the client to come back. The key the current one
+/// replaced is answered with the response that issued it, since the client
+/// cannot have seen that response; other unknown keys get Status 3 so the
+/// client starts over. The collection's change token is stored with the
+/// key so Ping can tell when the collection changes again, and so is the
+/// FilterType, which later requests without Options reuse.
+pub async fn perform_sync(state: Arc<AppState>, session: &SyncSession<'_>, coll: &SyncRequestCollection, window_size: usize) -> Result<SyncResponseCollection> {
+    let storage: &Storage = &state.storage;
+    let mut resp = SyncResponseCollection {
+        class: Some("Calendar".to_string()),
+        sync_key: coll.sync_key.clone(),
+        collection_id: coll.collection_id.clone(),
+        status: SYNC_STATUS_SUCCESS,
+        ..Default::default()
+    };
+
+    let Some(calendar) = storage.get_calendar(session.owner, &coll.collection_id).await? else {
+        resp.status = SYNC_STATUS_HIERARCHY_CHANGED;
+        return Ok(resp);
+    };
+
+    let requested_filter = coll.options.iter().find_map(|o| o.filter_type);
+    let stored_filter = storage.get_sync_filter(session.owner, session.device_id, &coll.collection_id).await?;
+    let filter_type = requested_filter.or(stored_filter);
+    let body_preference = coll.options.iter().map(|o| o.body_preference.as_slice()).find(|p| !p.is_empty()).unwrap_or_default();
+
+    let mut token = None;
+    if coll.sync_key == "0" {
+        storage.delete_items_for_calendar(session.owner, session.device_id, &calendar.caldav_href).await?;
+        storage.clear_pending_changes(session.owner, session.device_id, &coll.collection_id).await?;
+    } else if storage.get_sync_key(session.owner, session.device_id, &coll.collection_id).await?.as_deref() != Some(coll.sync_key.as_str()) {
+        // A retry of the last request, whose response never reached the client
+        if let Some(previous) = storage.get_sync_response(session.owner, session.device_id, &coll.collection_id, &coll.sync_key).await? {
+            tracing::info!("resending the last Sync response for {} to device {} of {}", coll.collection_id, session.device_id, session.owner);
+            return decode_sync_response(&previous);
+        }
+        storage.clear_pending_changes(session.owner, session.device_id, &coll.collection_id).await?;
+        resp.sync_key = "0".to_string();
+        resp.status = SYNC_STATUS_INVALID_SYNC_KEY;
+        return Ok(resp);
+    } else {
+        if let Some(commands) = &coll.commands {
+            let responses = apply_client_commands(&state, session, &calendar, commands, body_preference).await?;
+            let has_responses = !(responses.add.is_empty() && responses.change.is_empty() && responses.delete.is_empty() && responses.fetch.is_empty());
+            resp.responses = has_responses.then_some(responses);
+        }
+        if coll.get_changes == Some(false) {
+            // Server changes were not fetched, so the old token still applies
+            let token = storage.get_sync_token(session.owner, session.device_id, &coll.collection_id).await?;
+            issue_sync_key(storage, session, coll, &mut resp, token.as_deref(), filter_type).await?;
+            return Ok(resp);
+        }
+        // Changes queued under another window are stale; diff again
+        if filter_type != stored_filter {
+            storage.clear_pending_changes(session.owner, session.device_id, &coll.collection_id).await?;
+        }
+        // Read before diffing, so changes made during the diff still show up
+        let caldav = CaldavClient::new(&state.cfg);
+        token = caldav.collection_token(&calendar.caldav_href, session.username, session.password).await
+            .unwrap_or_else(|e| {
+                tracing::warn!("no change token for {}: {}", calendar.caldav_href, e);
+                None
+            });
+        let commands = collect_changes(&state, session, &calendar, filter_type, body_preference, window_size).await?;
+        if storage.has_pending_changes(session.owner, session.device_id, &coll.collection_id).await? {
+            resp.more_available = Some(());
+        }
+        resp.commands = Some(commands);
+    }
+
+    issue_sync_key(storage, session, coll, &mut resp, token.as_deref(), filter_type).await?;
+    Ok(resp)
+}
+
+/// Give the response a new sync key and store it with the collection's change
+/// token. Unless the collection was (re)started, the response is kept along
+/// with the key the client sent, so a retry with that key gets it again.
+async fn issue_sync_key(storage: &Storage, session: &SyncSession<'_>, coll: &SyncRequestCollection, resp: &mut SyncResponseCollection, token: Option<&str>, filter_type: Option<u8>) -> Result<()> {
+    resp.sync_key = Uuid::new_v4().to_string();
+    storage.set_sync_key(session.owner, session.device_id, &coll.collection_id, &resp.sync_key, token, filter_type).await?;
+    if coll.sync_key != "0" {
+        storage.set_sync_response(session.owner, session.device_id, &coll.collection_id, &coll.sync_key, &encode_sync_response(resp)?).await?;
+    }
+    Ok(())
+}
+
+/// A collection's Sync response as stored for retries: the WBXML of a Sync
+/// holding only that collection.
+fn encode_sync_response(resp: &SyncResponseCollection) -> Result<Vec<u8>> {
+    let sync = SyncResponse { status: None, collections: Some(SyncResponseCollections { collection: vec![resp.clone()] }) };
+    Wbxml::new().encode(&to_element(&sync)?)
+}
+
+fn decode_sync_response(bytes: &[u8]) -> Result<SyncResponseCollection> {
+    let sync: SyncResponse = from_element(&Wbxml::new().decode(bytes)?)?;
+    sync.collections.and_then(|c| c.collection.into_iter().next()).ok_or_else(|| anyhow::anyhow!("stored Sync response holds no collection"))
+}
+
+// GetItemEstimate status codes
+const ESTIMATE_STATUS_SUCCESS: u32 = 1;
+const ESTIMATE_STATUS_INVALID_COLLECTION: u32 = 2;
+const ESTIMATE_STATUS_NOT_PRIMED: u32 = 3;
+const ESTIMATE_STATUS_INVALID_SYNC_KEY: u32 = 4;
+
+/// Estimate how many changes the next Sync of a collection would send. This is
+/// the diff `perform_sync` runs, over the requested FilterType window or the
+/// one the last Sync used, without recording anything; changes still queued in
+/// `sync_pending` are part of it because `items_map` only records what was
+/// actually sent.
+pub async fn estimate_changes(state: &AppState, session: &SyncSession<'_>, coll: &EstimateRequestCollection) -> Result<EstimateResponse> {
+    let storage: &Storage = &state.storage;
+    let status = |status: u32| EstimateResponse { status, collection: None };
+    let Some(calendar) = storage.get_calendar(session.owner, &coll.collection_id).await? else {
+        return Ok(status(ESTIMATE_STATUS_INVALID_COLLECTION));
+    };
+    let Some(stored_key) = storage.get_sync_key(session.owner, session.device_id, &coll.collection_id).await? else {
+        return Ok(status(ESTIMATE_STATUS_NOT_PRIMED));
+    };
+    match coll.sync_key.as_deref() {
+        Some("0") => return Ok(status(ESTIMATE_STATUS_NOT_PRIMED)),
+        Some(key) if key != stored_key => return Ok(status(ESTIMATE_STATUS_INVALID_SYNC_KEY)),
+        _ => {}
+    }
+
+    let filter_type = match coll.options.iter().find_map(|o| o.filter_type).or(coll.filter_type) {
+        Some(f) => Some(f),
+        None => storage.get_sync_filter(session.owner, session.device_id, &coll.collection_id).await?,
+    };
+    let changes = diff_collection(state, session, &calendar, filter_type).await?;
+    Ok(EstimateResponse {
+        status: ESTIMATE_STATUS_SUCCESS,
+        collection: Some(EstimateResponseCollection { collection_id: coll.collection_id.clone(), estimate: changes.len() as u32 }),
+    })
+}
+
+// Ping status codes
+pub const PING_STATUS_EXPIRED: u32 = 1;
+pub const PING_STATUS_CHANGES: u32 = 2;
+pub const PING_STATUS_MISSING_PARAMETERS: u32 = 3;
+pub const PING_STATUS_BAD_HEARTBEAT: u32 = 5;
+pub const PING_STATUS_TOO_MANY_FOLDERS: u32 = 6;
+pub const PING_STATUS_HIERARCHY_CHANGED: u32 = 7;
+pub const PING_STATUS_SERVER_ERROR: u32 = 8;
+
+/// How often a Ping re-reads collection tokens while it waits.
+const PING_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Outcome of watching folders for a Ping.
+pub enum PingResult {
+    /// The heartbeat ran out without changes.
+    Expired,
+    /// These folders have changes the client has not synced.
+    Changed(Vec<String>),
+    /// A watched folder is gone or calendars were added; the client must FolderSync.
+    HierarchyChanged,
+}
+
+/// Watch folders until one of them changes or `heartbeat` runs out. A folder has
+/// changed when its CalDAV change token differs from the one stored at its last
+/// Sync, or when changes are still queued for it. The tokens of all calendars
+/// come from one PROPFIND, repeated every `PING_POLL_INTERVAL`.
+pub async fn watch_folders(state: Arc<AppState>, session: &SyncSession<'_>, folder_ids: &[String], heartbeat: Duration) -> Result<PingResult> {
+    let storage: &Storage = &state.storage;
+    let caldav = CaldavClient::new(&state.cfg);
+    let deadline = Instant::now() + heartbeat;
+
+    let mut folders = Vec::new();
+    for id in folder_ids {
+        match storage.get_calendar(session.owner, id).await? {
+            Some(calendar) => folders.push(calendar),
+            None => return Ok(PingResult::HierarchyChanged),
+        }
+    }
+    let known: HashSet<String> = storage.get_folder_snapshot(session.owner, session.device_id).await?
+        .into_iter().map(|f| f.caldav_href).collect();
+
+    loop {
+        let tokens = caldav.collection_tokens(session.username, session.password).await?;
+        if tokens.keys().cloned().collect::<HashSet<_>>() != known {
+            return Ok(PingResult::HierarchyChanged);
+        }
+        let mut changed = Vec::new();
+        for calendar in &folders {
+            let current = tokens.get(&calendar.caldav_href).cloned().flatten();
+            let synced = storage.get_sync_token(session.owner, session.device_id, &calendar.collection_id).await?;
+            let token_moved = current.is_some() && current != synced;
+            if token_moved || storage.has_pending_changes(session.owner, session.device_id, &calendar.collection_id).await? {
+                changed.push(calendar.collection_id.clone());
+            }
+        }
+        if !changed.is_empty() {
+            return Ok(PingResult::Changed(changed));
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(PingResult::Expired);
+        }
+        tokio::time::sleep(PING_POLL_INTERVAL.min(deadline - now)).await;
+    }
+}
+
+/// Apply the Add/Change/Delete/Fetch commands a client uploaded. Adds always get
+/// a response carrying the new ServerId; Change and Delete only report failures.
+/// Written items are recorded in `items_map` so they are not echoed back.
+/// Fetched items honor the collection's BodyPreference.
+async fn apply_client_commands(state: &AppState, session: &SyncSession<'_>, calendar: &CalendarFolder, commands: &ClientCommands, body_preference: &[BodyPreference]) -> Result<SyncResponses> {
+    let storage: &Storage = &state.storage;
+    let caldav = CaldavClient::new(&state.cfg);
+    let mut responses = SyncResponses::default();
+
+    for add in &commands.add {
+        let mut data = add.application_data.clone();
+        normalize_client_data(&mut data, session.version);
+        let uid = data.uid.clone().unwrap_or_else(|| Uuid::new_v4().to_string());
+        let mut result = AddResult { class: add.class.clone(), client_id: add.client_id.clone(), server_id: None, status: SYNC_STATUS_SUCCESS };
+        match application_data_to_ics(&data, &uid) {
+            Err(e) => {
+                tracing::warn!("client Add {} rejected: {}", add.client_id, e);
+                result.status = SYNC_STATUS_CONVERSION_ERROR;
+            }
+            Ok(ics) => {
+                let resource_name = format!("{}.ics", Uuid::new_v4());
+                match caldav.put_event(&calendar.caldav_href, &resource_name, &ics, session.username, session.password).await {
+                    Ok(etag) => {
+                        let href = format!("{}/{}", calendar.caldav_href.trim_end_matches('/'), resource_name);
+                        let server_id = generate_server_id(&state.cfg.hmac_secret, &href);
+                        storage.upsert_item_map(session.owner, session.device_id, &calendar.caldav_href, &href, &server_id, &uid, &etag).await?;
+                        result.server_id = Some(server_id);
+                    }
+                    Err(e) => {
+                        tracing::warn!("client Add {} failed: {}", add.client_id, e);
+                        result.status = SYNC_STATUS_SERVER_ERROR;
+                    }
+                }
+            }
+        }
+        responses.add.push(result);
+    }
+
+    for change in &commands.change {
+        let Some((_, href)) = storage.get_item_by_server_id(session.owner, session.device_id, &change.server_id).await? else {
+            responses.change.push(ItemStatus { server_id: change.server_id.clone(), status: SYNC_STATUS_OBJECT_NOT_FOUND });
+            continue;
+        };
+        let mut data = change.application_data.clone();
+        normalize_client_data(&mut data, session.version);
+        // Keep the UID the item already has on the server, and what the client
+        // never got to see. Without the stored copy those would be lost.
+        let stored = match caldav.get_event(&href, session.username, session.password).await {
+            Ok(stored) => stored,
+            Err(e) => {
+                tracing::warn!("client Change {} failed: could not read {}: {}", change.server_id, href, e);
+                responses.change.push(ItemStatus { server_id: change.server_id.clone(), status: SYNC_STATUS_SERVER_ERROR });
+                continue;
+            }
+        };
+        let existing = ics_to_application_data(&stored, session.owner).ok().and_then(|d| d.uid);
+        let uid = existing.or(data.uid.clone()).unwrap_or_else(|| Uuid::new_v4().to_string());
+        let rebuilt = application_data_to_ics(&data, &uid).and_then(|ics| keep_server_properties(&ics, &stored, data.body.is_none()));
+        let status = match rebuilt {
+            Err(e) => {
+                tracing::warn!("client Change {} rejected: {}", change.server_id, e);
+                SYNC_STATUS_CONVERSION_ERROR
+            }
+            Ok(ics) => {
+                let (collection, name) = href.rsplit_once('/').unwrap_or((&calendar.caldav_href, &href));
+                match caldav.put_event(collection, name, &ics, session.username, session.password).await {
+                    Ok(etag) => {
+                        storage.upsert_item_map(session.owner, session.device_id, &calendar.caldav_href, &href, &change.server_id, &uid, &etag).await?;
+                        SYNC_STATUS_SUCCESS
+                    }
+                    Err(e) => {
+                        tracing::warn!("client Change {} failed: {}", change.server_id, e);
+                        SYNC_STATUS_SERVER_ERROR
+                    }
+                }
+            }
+        };
+        if status != SYNC_STATUS_SUCCESS {
+            responses.change.push(ItemStatus { server_id: change.server_id.clone(), status });
+        }
+    }
+
+    for delete in &commands.delete {
+        let Some((_, href)) = storage.get_item_by_server_id(session.owner, session.device_id, &delete.server_id).await? else {
+            responses.delete.push(ItemStatus { server_id: delete.server_id.clone(), status: SYNC_STATUS_OBJECT_NOT_FOUND });
+            continue;
+        };
+        let result = match &delete.instance_id {
+            // 16.x: delete a single occurrence of a series
+            Some(instance_id) => async {
+                let ics = caldav.get_event(&href, session.username, session.password).await?;
+                let ics = exclude_instance(&ics, instance_id)?;
+                let (collection, name) = href.rsplit_once('/').unwrap_or((&calendar.caldav_href, &href));
+                let etag = caldav.put_event(collection, name, &ics, session.username, session.password).await?;
+                storage.set_item_etag(session.owner, session.device_id, &delete.server_id, &etag).await
+            }.await,
+            None => async {
+                caldav.delete_event(&href, session.username, session.password).await?;
+                storage.delete_item_by_server_id(session.owner, session.device_id, &delete.server_id).await
+            }.await,
+        };
+        if let Err(e) = result {
+            tracing::warn!("client Delete {} failed: {}", delete.server_id, e);
+            responses.delete.push(ItemStatus { server_id: delete.server_id.clone(), status: SYNC_STATUS_SERVER_ERROR });
+        }
+    }
+
+    for fetch in &commands.fetch {
+        let mut result = FetchResult { server_id: fetch.server_id.clone(), status: SYNC_STATUS_OBJECT_NOT_FOUND, application_data: None };
+        if let Some((_, href)) = storage.get_item_by_server_id(session.owner, session.device_id, &fetch.server_id).await? {
+            let fetched = caldav.get_event(&href, session.username, session.password).await
+                .and_then(|ics| outgoing_data(&ics, &fetch.server_id, session, body_preference));
+            match fetched {
+                Ok(data) => {
+                    result.status = SYNC_STATUS_SUCCESS;
+                    result.application_data = Some(data);
+                }
+                Err(e) => {
+                    tracing::warn!("client Fetch {} failed: {}", fetch.server_id, e);
+                    result.status = SYNC_STATUS_SERVER_ERROR;
+                }
+            }
+        }
+        responses.fetch.push(result);
+    }
+
+    Ok(responses)
+}
+
+/// Next window of changes for a collection: queued changes first, otherwise a
+/// fresh diff whose overflow is queued. `items_map` is updated only for the
+/// changes actually sent.
+async fn collect_changes(state: &AppState, session: &SyncSession<'_>, calendar: &CalendarFolder, filter_type: Option<u8>, body_preference: &[BodyPreference], window_size: usize) -> Result<ServerCommands> {
+    let storage: &Storage = &state.storage;
+    let mut batch = storage.take_pending_changes(session.owner, session.device_id, &calendar.collection_id, window_size).await?;
+    if batch.is_empty() {
+        batch = diff_collection(state, session, calendar, filter_type).await?;
+        let overflow = batch.split_off(batch.len().min(window_size));
+        storage.add_pending_changes(session.owner, session.device_id, &calendar.collection_id, &overflow).await?;
+    }
+
+    let mut commands = ServerCommands::default();
+    for change in batch {
+        match change.kind {
+            ChangeKind::Delete | ChangeKind::SoftDelete => {
+                storage.delete_item_by_server_id(session.owner, session.device_id, &change.server_id).await?;
+                let item = ItemRef { server_id: change.server_id, instance_id: None };
+                if change.kind == ChangeKind::Delete {
+                    commands.delete.push(item);
+                } else {
+                    commands.soft_delete.push(item);
+                }
+                continue;
+            }
+            ChangeKind::Add | ChangeKind::Change => {}
+        }
+        let data = match outgoing_data(change.calendar_data.as_deref().unwrap_or_default(), &change.server_id, session, body_preference) {
+            Ok(d) => d,
+            Err(e) => {
+                tracing::warn!("skipping {}: {}", change.resource_href, e);
+                continue;
+            }
+        };
+        let etag = change.etag.as_deref().unwrap_or_default();
+        storage.upsert_item_map(session.owner, session.device_id, &calendar.caldav_href, &change.resource_href, &change.server_id, data.uid.as_deref().unwrap_or_default(), etag).await?;
+        let item = ServerItem { server_id: change.server_id, application_data: data };
+        if change.kind == ChangeKind::Change {
+            commands.change.push(item);
+        } else {
+            commands.add.push(item);
+        }
+    }
+    Ok(commands)
+}
+
+// Calendar FilterType values (MS-ASCMD 2.2.3.70.1)
+const FILTER_TYPE_TWO_WEEKS: u8 = 4;
+const FILTER_TYPE_ONE_MONTH: u8 = 5;
+const FILTER_TYPE_THREE_MONTHS: u8 = 6;
+const FILTER_TYPE_SIX_MONTHS: u8 = 7;
+
+/// The CalDAV time-range a diff covers: as far back as FilterType asks, with no
+/// lower bound when it asks for everything (or for nothing, which means the
+/// same), and a year ahead.
+fn sync_window(filter_type: Option<u8>, now: DateTime<Utc>) -> (Option<DateTime<Utc>>, DateTime<Utc>) {
+    let start = match filter_type {
+        Some(FILTER_TYPE_TWO_WEEKS) => Some(now - chrono::Duration::weeks(2)),
+        Some(FILTER_TYPE_ONE_MONTH) => Some(now - Months::new(1)),
+        Some(FILTER_TYPE_THREE_MONTHS) => Some(now - Months::new(3)),
+        Some(FILTER_TYPE_SIX_MONTHS) => Some(now - Months::new(6)),
+        _ => None,
+    };
+    (start, now + chrono::Duration::weeks(52))
+}
+
+/// Compare the CalDAV collection within the FilterType window with what
+/// `items_map` says the client holds.
+async fn diff_collection(state: &AppState, session: &SyncSession<'_>, calendar: &CalendarFolder, filter_type: Option<u8>) -> Result<Vec<PendingChange>> {
     let caldav = CaldavClient::new(&state.cfg);
-    let calendars = caldav.find_user_calendars(username_for_caldav, password_for_caldav).await?;
-    let collection_href = calendars.get(0).ok_or_else(|| anyhow::anyhow!("no calendars found"))?.clone();
+    let (start, end) = sync_window(filter_type, Utc::now());
+    let start = start.map(|s| s.format("%Y%m%dT%H%M%SZ").to_string());
+    let end = end.format("%Y%m%dT%H%M%SZ").to_string();
+    let multistatus = caldav.query_events(&calendar.caldav_href, start.as_deref(), &end, session.username, session.password).await?;
+
+    let base = Url::parse(&calendar.caldav_href)?;
+    let mut known: HashMap<String, (String, String)> = state.storage.list_items(session.owner, session.device_id, &calendar.caldav_href).await?
+        .into_iter().map(|(server_id, href, etag)| (href, (server_id, etag))).collect();
+
+    let mut changes = Vec::new();
+    for r in parse_multistatus(&multistatus)? {
+        let (Some(etag), Some(ics)) = (r.props.get("getetag"), r.props.get("calendar-data")) else {
+            continue;
+        };
+        let href = base.join(&r.href)?.to_string();
+        let kind = match known.remove(&href) {
+            Some((_, known_etag)) if &known_etag == etag => continue,
+            Some(_) => ChangeKind::Change,
+            None => ChangeKind::Add,
+        };
+        changes.push(PendingChange {
+            kind,
+            server_id: generate_server_id(&state.cfg.hmac_secret, &href),
+            resource_href: href,
+            etag: Some(etag.clone()),
+            calendar_data: Some(ics.clone()),
+        });
+    }
+
+    // Whatever the client holds that CalDAV no longer returned was deleted, or
+    // has moved out of the window as time went on and is only soft-deleted.
+    if !known.is_empty() {
+        let existing = caldav.list_resources(&calendar.caldav_href, session.username, session.password).await?;
+        for (href, (server_id, _)) in known {
+            let kind = if existing.contains(&href) { ChangeKind::SoftDelete } else { ChangeKind::Delete };
+            changes.push(PendingChange { kind, server_id, resource_href: href, etag: None, calendar_data: None });
+        }
+    }
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use axum::http::StatusCode;
+    use axum::routing::any;
+    use axum::Router;
 
-    let start = (Utc::now() - chrono::Duration::weeks(52)).format("%Y%m%dT%H%M%SZ").to_string();
-    let end = (Utc::now() + chrono::Duration::weeks(52)).format("%Y%m%dT%H%M%SZ").to_string();
+    /// A gateway whose CalDAV server answers every REPORT on `/fail/` with a
+    /// 500 and every one on `/empty/` with an empty 207.
+    async fn failing_caldav() -> (AppState, String) {
+        let app = Router::new()
+            .route("/fail/", any(|| async { StatusCode::INTERNAL_SERVER_ERROR }))
+            .route("/empty/", any(|| async { StatusCode::MULTI_STATUS }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
 
-    // Query events (we keep the returned value in case future code uses it)
-    let _multistatus = caldav.query_events(&collection_href, &start, &end, username_for_caldav, password_for_caldav).await?;
+        let db_path = std::env::temp_dir().join(format!("exchange_gateway_sync_{}.db", Uuid::new_v4()));
+        let cfg: Config = toml::from_str(&format!(r#"
+            bind = "127.0.0.1:0"
+            http_bind = "127.0.0.1:0"
+            tls_cert = ""
+            tls_key = ""
+            caldav_base = "{base}/"
+            db_path = "{db}"
+            hmac_secret = "secret"
+            log_level = "info"
+        "#, base = base, db = db_path.display())).unwrap();
+        let storage = Storage::new(&cfg.db_path).await.unwrap();
+        storage.run_migrations().await.unwrap();
+        (AppState { cfg, storage: Arc::new(storage) }, base)
+    }
 
-    let new_sync_key = Uuid::new_v4().to_string();
-    storage.set_sync_key(owner, collection_id, &new_sync_key, Some("token")).await?;
+    #[tokio::test]
+    async fn failed_calendar_query_keeps_device_state() {
+        let (state, base) = failing_caldav().await;
+        let session = SyncSession { owner: "alice", device_id: "dev1", username: "alice", password: "pw", version: ProtocolVersion::V14_1 };
+        for path in ["fail", "empty"] {
+            let calendar = CalendarFolder {
+                collection_id: path.to_string(),
+                caldav_href: format!("{}/{}/", base, path),
+                display_name: path.to_string(),
+                folder_type: 8,
+            };
+            let href = format!("{}event.ics", calendar.caldav_href);
+            let server_id = format!("sid-{}", path);
+            state.storage.upsert_item_map("alice", "dev1", &calendar.caldav_href, &href, &server_id, "uid", "\"1\"").await.unwrap();
 
-    let mut xml = String::new();
-    xml.push_str(r#"<?xml version="1.0" encoding="utf-8"?>"#);
-    xml.push_str(r#"<Sync xmlns="AirSync:"><Collections><Collection><Class>Calendar</Class>"#);
-    xml.push_str(&format!(r#"<SyncKey>{}</SyncKey>"#, new_sync_key));
-    xml.push_str(&format!(r#"<CollectionId>{}</CollectionId>"#, collection_id));
-    xml.push_str(r#"<Status>1</Status><Commands></Commands></Collection></Collections></Sync>"#);
-    Ok(xml)
+            assert!(collect_changes(&state, &session, &calendar, None, &[], 10).await.is_err());
+            let items = state.storage.list_items("alice", "dev1", &calendar.caldav_href).await.unwrap();
+            assert_eq!(items, vec![(server_id, href, "\"1\"".to_string())]);
+            assert!(!state.storage.has_pending_changes("alice", "dev1", &calendar.collection_id).await.unwrap());
+        }
+        let _ = std::fs::remove_file(&state.cfg.db_path);
+    }
 }
@@ -4,3 +4,39 @@ pub fn ews_soap_envelope(body: &str) -> String {
   <s:Body>{}</s:Body>
 </s:Envelope>"#, body)
 }
+
+/// Wrap plain text in a minimal HTML document, keeping its line breaks.
+pub fn text_to_html(text: &str) -> String {
+    let escaped = text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('\n', "<br>\n");
+    format!("<html><body>{}</body></html>", escaped)
+}
+
+/// Reduce HTML to its text: tags are dropped, source line breaks are spaces,
+/// `<br>`, `</p>` and `</div>` become line breaks and the common entities are
+/// decoded.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start].replace(['\r', '\n'], " "));
+        let Some(end) = rest[start..].find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = rest[start + 1..start + end].trim().to_ascii_lowercase();
+        if tag.starts_with("br") || tag == "/p" || tag == "/div" {
+            text.push('\n');
+        }
+        rest = &rest[start + end + 1..];
+    }
+    text.push_str(&rest.replace(['\r', '\n'], " "));
+    let text = text.replace("&nbsp;", " ").replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&amp;", "&");
+    text.lines().map(str::trim).collect::<Vec<_>>().join("\n").trim().to_string()
+}
+
+/// Parse an ActiveSync Range such as `0-1023` (both ends inclusive).
+pub fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let (first, last) = range.trim().split_once('-')?;
+    let (first, last) = (first.trim().parse().ok()?, last.trim().parse().ok()?);
+    (first <= last).then_some((first, last))
+}
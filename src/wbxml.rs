@@ -1,10 +1,39 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
+use base64::Engine;
+use quick_xml::NsReader;
+use quick_xml::events::Event;
+use quick_xml::name::ResolveResult;
 use std::collections::HashMap;
+use crate::codepages::CODE_PAGES;
 
-/// WBXML token tables for ActiveSync code pages used by calendar handling.
-/// Token maps for codepages 0,4,17.
+// WBXML 1.3 global tokens (WAP-192-WBXML section 7.1)
+const SWITCH_PAGE: u8 = 0x00;
+const END: u8 = 0x01;
+const ENTITY: u8 = 0x02;
+const STR_I: u8 = 0x03;
+const STR_T: u8 = 0x83;
+const OPAQUE: u8 = 0xC3;
+
+// Tag token flag bits
+const TAG_HAS_ATTRS: u8 = 0x80;
+const TAG_HAS_CONTENT: u8 = 0x40;
+const TAG_ID_MASK: u8 = 0x3F;
+
+/// IANA MIBenum for UTF-8, the only charset ActiveSync clients use.
+const CHARSET_UTF8: u32 = 106;
+
+/// WBXML 1.3, public id 1 (unknown), UTF-8, empty string table.
+const WBXML_HEADER: [u8; 4] = [0x03, 0x01, CHARSET_UTF8 as u8, 0x00];
+
+/// Elements carrying binary data. In XML form their text is base64.
+const OPAQUE_TAGS: &[(&str, &str)] = &[
+    ("ComposeMail", "Mime"),
+    ("Email2", "ConversationId"),
+    ("Email2", "ConversationIndex"),
+];
+
+/// WBXML codec with token tables for every ActiveSync code page (see `codepages`).
 pub struct Wbxml {
-    pub codepage: u8,
     pub tok_to_tag: HashMap<(u8,u8), &'static str>,
     pub tag_to_tok: HashMap<(&'static str,u8), u8>,
 }
@@ -13,51 +42,13 @@ impl Wbxml {
     pub fn new() -> Self {
         let mut tok_to_tag = HashMap::new();
         let mut tag_to_tok = HashMap::new();
-
-        // Code page 0: AirSync
-        macro_rules! add0 { ($t:expr, $s:expr) => { tok_to_tag.insert((0,$t), $s); tag_to_tok.insert(($s,0), $t); }; }
-        add0!(0x05, "Sync");
-        add0!(0x06, "Responses");
-        add0!(0x07, "Add");
-        add0!(0x08, "Change");
-        add0!(0x09, "Delete");
-        add0!(0x0B, "SyncKey");
-        add0!(0x0D, "ServerId");
-        add0!(0x0C, "ClientId");
-        add0!(0x0E, "Status");
-        add0!(0x0F, "Collection");
-        add0!(0x10, "Class");
-        add0!(0x12, "CollectionId");
-        add0!(0x26, "Commands");
-        add0!(0x2A, "ApplicationData");
-
-        // Code page 4: Calendar
-        macro_rules! add4 { ($t:expr, $s:expr) => { tok_to_tag.insert((4,$t), $s); tag_to_tok.insert(($s,4), $t); }; }
-        add4!(0x05, "Timezone");
-        add4!(0x06, "AllDayEvent");
-        add4!(0x07, "Attendees");
-        add4!(0x08, "Attendee");
-        add4!(0x0B, "StartTime");
-        add4!(0x0C, "EndTime");
-        add4!(0x11, "DtStamp");
-        add4!(0x13, "Subject");
-        add4!(0x14, "Location");
-        add4!(0x15, "Body");
-        add4!(0x1B, "Recurrence");
-        add4!(0x23, "Organizer");
-        add4!(0x24, "RecurrenceId");
-        add4!(0x27, "Recurrences");
-
-        // Code page 17: AirSyncBase
-        macro_rules! add17 { ($t:expr, $s:expr) => { tok_to_tag.insert((17,$t), $s); tag_to_tok.insert(($s,17), $t); }; }
-        add17!(0x05, "BodyPreference");
-        add17!(0x06, "Type");
-        add17!(0x0A, "Body");
-        add17!(0x0B, "Data");
-        add17!(0x0C, "EstimatedDataSize");
-        add17!(0x0D, "Truncated");
-
-        Self { codepage: 0, tok_to_tag, tag_to_tok }
+        for cp in CODE_PAGES {
+            for &(tok, tag) in cp.tags {
+                tok_to_tag.insert((cp.page, tok), tag);
+                tag_to_tok.insert((tag, cp.page), tok);
+            }
+        }
+        Self { tok_to_tag, tag_to_tok }
     }
 
     pub fn token_to_tag(&self, page: u8, token: u8) -> Option<&'static str> {
@@ -68,20 +59,568 @@ impl Wbxml {
         self.tag_to_tok.get(&(tag, page)).copied()
     }
 
-    /// Rudimentary decoder for WBXML or pass-through XML.
-    pub fn decode(&self, bytes: &[u8]) -> Result<String> {
+    /// Decode a WBXML 1.3 document into an element tree.
+    /// Payloads that already are XML (first byte `<`) are parsed with `parse_xml`.
+    pub fn decode(&self, bytes: &[u8]) -> Result<Element> {
         if bytes.is_empty() { return Err(anyhow!("empty payload")); }
         if bytes[0] == b'<' {
-            return Ok(String::from_utf8(bytes.to_vec())?);
+            return self.parse_xml(std::str::from_utf8(bytes)?);
+        }
+
+        let mut r = ByteReader::new(bytes);
+        let version = r.byte()?;
+        if version > 0x03 {
+            bail!("unsupported WBXML version 0x{:02X}", version);
         }
+        // A public id of 0 means the id is given as a string table reference instead.
+        if r.mb_u_int32()? == 0 {
+            r.mb_u_int32()?;
+        }
+        let charset = r.mb_u_int32()?;
+        if charset != 0 && charset != CHARSET_UTF8 {
+            bail!("unsupported WBXML charset {}", charset);
+        }
+        let strtbl_len = r.mb_u_int32()? as usize;
+        let strtbl = r.take(strtbl_len)?;
+
+        let mut page = 0u8;
+        let mut tree = TreeBuilder::default();
+
+        while !r.is_empty() {
+            let tok = r.byte()?;
+            match tok {
+                SWITCH_PAGE => page = r.byte()?,
+                END => tree.close()?,
+                ENTITY => {
+                    let code = r.mb_u_int32()?;
+                    let c = char::from_u32(code).ok_or_else(|| anyhow!("invalid character entity {}", code))?;
+                    tree.text(c.encode_utf8(&mut [0u8; 4]))?;
+                }
+                STR_I => {
+                    let s = r.c_str()?;
+                    tree.text(s)?;
+                }
+                STR_T => {
+                    let offset = r.mb_u_int32()? as usize;
+                    let tail = strtbl.get(offset..).ok_or_else(|| anyhow!("string table offset {} out of range", offset))?;
+                    let s = ByteReader::new(tail).c_str()?;
+                    tree.text(s)?;
+                }
+                OPAQUE => {
+                    let len = r.mb_u_int32()? as usize;
+                    let data = r.take(len)?;
+                    tree.opaque(data.to_vec())?;
+                }
+                _ if tok & TAG_ID_MASK <= 0x04 => bail!("unsupported WBXML token 0x{:02X}", tok),
+                _ => {
+                    if tok & TAG_HAS_ATTRS != 0 {
+                        bail!("WBXML attributes are not used by ActiveSync (token 0x{:02X})", tok);
+                    }
+                    let id = tok & TAG_ID_MASK;
+                    let tag = self.token_to_tag(page, id)
+                        .ok_or_else(|| anyhow!("unknown token 0x{:02X} on code page {}", id, page))?;
+                    tree.open(Element::new(page, tag), tok & TAG_HAS_CONTENT != 0)?;
+                }
+            }
+        }
+
+        tree.finish()
+    }
+
+    /// Encode an element tree into WBXML 1.3, emitting SWITCH_PAGE whenever the
+    /// code page changes. Text is written as STR_I and binary data as OPAQUE.
+    pub fn encode(&self, root: &Element) -> Result<Vec<u8>> {
+        let mut out = WBXML_HEADER.to_vec();
+        let mut page = 0u8;
+        self.write_element(root, &mut page, &mut out)?;
+        Ok(out)
+    }
+
+    fn write_element(&self, e: &Element, page: &mut u8, out: &mut Vec<u8>) -> Result<()> {
+        let id = self.tag_to_token(e.page, e.tag)
+            .ok_or_else(|| anyhow!("no token for {} on code page {}", e.tag, e.page))?;
+        if e.page != *page {
+            out.push(SWITCH_PAGE);
+            out.push(e.page);
+            *page = e.page;
+        }
+        match &e.content {
+            Content::Text(t) if !t.is_empty() => {
+                out.push(id | TAG_HAS_CONTENT);
+                out.push(STR_I);
+                out.extend_from_slice(t.as_bytes());
+                out.push(0);
+                out.push(END);
+            }
+            Content::Opaque(d) => {
+                out.push(id | TAG_HAS_CONTENT);
+                out.push(OPAQUE);
+                write_mb_u_int32(out, d.len() as u32);
+                out.extend_from_slice(d);
+                out.push(END);
+            }
+            Content::Children(children) if !children.is_empty() => {
+                out.push(id | TAG_HAS_CONTENT);
+                for c in children {
+                    self.write_element(c, page, out)?;
+                }
+                out.push(END);
+            }
+            _ => out.push(id),
+        }
+        Ok(())
+    }
+
+    /// Parse namespaced XML into an element tree. Elements are mapped to code pages
+    /// through their namespace; the text of the binary elements in `OPAQUE_TAGS` is
+    /// read as base64. Whitespace between elements is ignored.
+    pub fn parse_xml(&self, xml: &str) -> Result<Element> {
+        let mut reader = NsReader::from_str(xml);
+        let mut tree = TreeBuilder::default();
+        let mut text = String::new();
+
+        loop {
+            let (ns, event) = reader.read_resolved_event()?;
+            match event {
+                Event::Start(ref e) | Event::Empty(ref e) => {
+                    tree.flush_xml_text(&mut text)?;
+                    let local = std::str::from_utf8(e.local_name().into_inner())?.to_string();
+                    let page = match ns {
+                        ResolveResult::Bound(n) => {
+                            let uri = std::str::from_utf8(n.into_inner())?;
+                            code_page_for_namespace(uri).ok_or_else(|| anyhow!("unknown ActiveSync namespace {}", uri))?
+                        }
+                        _ => bail!("element <{}> has no ActiveSync namespace", local),
+                    };
+                    // Resolve through the token table to get the static tag name.
+                    let tag = self.tag_to_token(page, &local)
+                        .and_then(|id| self.token_to_tag(page, id))
+                        .ok_or_else(|| anyhow!("no token for {} on code page {}", local, page))?;
+                    tree.open(Element::new(page, tag), matches!(event, Event::Start(_)))?;
+                }
+                Event::End(_) => {
+                    tree.flush_xml_text(&mut text)?;
+                    tree.close()?;
+                }
+                Event::Text(t) => text.push_str(&t.decode()?),
+                Event::CData(t) => text.push_str(&t.decode()?),
+                Event::GeneralRef(r) => {
+                    if let Some(c) = r.resolve_char_ref()? {
+                        text.push(c);
+                    } else {
+                        let name = r.decode()?;
+                        let resolved = quick_xml::escape::resolve_predefined_entity(&name)
+                            .ok_or_else(|| anyhow!("unknown entity &{};", name))?;
+                        text.push_str(resolved);
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        tree.finish()
+    }
+}
+
+/// Code page number for an XML namespace such as `Calendar:`.
+pub fn code_page_for_namespace(uri: &str) -> Option<u8> {
+    let name = uri.strip_suffix(':').unwrap_or(uri);
+    CODE_PAGES.iter().find(|cp| cp.namespace == name).map(|cp| cp.page)
+}
+
+fn namespace_name(page: u8) -> &'static str {
+    CODE_PAGES.iter().find(|cp| cp.page == page).map(|cp| cp.namespace).unwrap_or("")
+}
+
+/// Value of an element: ActiveSync elements hold either character data, binary
+/// data or child elements, never a mix.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Content {
+    Empty,
+    Text(String),
+    Opaque(Vec<u8>),
+    Children(Vec<Element>),
+}
+
+/// A node of a decoded ActiveSync document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Element {
+    pub page: u8,
+    pub tag: &'static str,
+    pub content: Content,
+}
+
+impl Element {
+    pub fn new(page: u8, tag: &'static str) -> Self {
+        Self { page, tag, content: Content::Empty }
+    }
+
+    /// Append a child element. Any text or binary value is replaced.
+    pub fn push(&mut self, child: Element) -> &mut Self {
+        match &mut self.content {
+            Content::Children(children) => children.push(child),
+            content => *content = Content::Children(vec![child]),
+        }
+        self
+    }
+
+    pub fn children(&self) -> &[Element] {
+        match &self.content {
+            Content::Children(children) => children,
+            _ => &[],
+        }
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        match &self.content {
+            Content::Text(t) => Some(t),
+            Content::Empty => Some(""),
+            _ => None,
+        }
+    }
+
+    /// Render as namespaced XML for logging. Each element whose code page
+    /// differs from its parent's declares that page as its default namespace;
+    /// binary data is written as base64 and Password values are masked.
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from(r#"<?xml version="1.0" encoding="utf-8"?>"#);
+        self.write_xml(None, &mut xml);
+        xml
+    }
+
+    fn write_xml(&self, parent_page: Option<u8>, xml: &mut String) {
+        xml.push('<');
+        xml.push_str(self.tag);
+        if parent_page != Some(self.page) {
+            xml.push_str(&format!(r#" xmlns="{}:""#, namespace_name(self.page)));
+        }
+        let inner = match &self.content {
+            Content::Empty => None,
+            Content::Text(t) if t.is_empty() => None,
+            Content::Text(_) if self.tag == "Password" => Some("***".to_string()),
+            Content::Text(t) => Some(escape_xml(t)),
+            Content::Opaque(d) => Some(base64::engine::general_purpose::STANDARD.encode(d)),
+            Content::Children(children) if children.is_empty() => None,
+            Content::Children(children) => {
+                let mut s = String::new();
+                for c in children {
+                    c.write_xml(Some(self.page), &mut s);
+                }
+                Some(s)
+            }
+        };
+        match inner {
+            Some(inner) => {
+                xml.push('>');
+                xml.push_str(&inner);
+                xml.push_str("</");
+                xml.push_str(self.tag);
+                xml.push('>');
+            }
+            None => xml.push_str("/>"),
+        }
+    }
+}
+
+/// Assembles an element tree from a stream of open/text/close events.
+#[derive(Default)]
+struct TreeBuilder {
+    open: Vec<Element>,
+    root: Option<Element>,
+}
+
+impl TreeBuilder {
+    fn open(&mut self, e: Element, has_content: bool) -> Result<()> {
+        if self.root.is_some() {
+            bail!("content after the root element");
+        }
+        if has_content {
+            self.open.push(e);
+            Ok(())
+        } else {
+            self.attach(e)
+        }
+    }
+
+    fn close(&mut self) -> Result<()> {
+        let e = self.open.pop().ok_or_else(|| anyhow!("end of element without open element"))?;
+        self.attach(e)
+    }
+
+    fn attach(&mut self, e: Element) -> Result<()> {
+        match self.open.last_mut() {
+            Some(parent) => {
+                if matches!(parent.content, Content::Text(_) | Content::Opaque(_)) {
+                    bail!("mixed content in <{}>", parent.tag);
+                }
+                parent.push(e);
+            }
+            None => self.root = Some(e),
+        }
+        Ok(())
+    }
 
-        // Simplified header parse (not full WBXML). Keep simple fallback.
-        let _offset = 0usize;
-        Ok(String::from_utf8(bytes.to_vec())?)
+    fn current(&mut self) -> Result<&mut Element> {
+        self.open.last_mut().ok_or_else(|| anyhow!("text content outside of an element"))
     }
 
-    /// Minimal encoder stub.
-    pub fn encode(&self, xml: &str) -> Result<Vec<u8>> {
-        Ok(xml.as_bytes().to_vec())
+    fn text(&mut self, s: &str) -> Result<()> {
+        let e = self.current()?;
+        match &mut e.content {
+            Content::Empty => e.content = Content::Text(s.to_string()),
+            Content::Text(t) => t.push_str(s),
+            _ => bail!("mixed content in <{}>", e.tag),
+        }
+        Ok(())
+    }
+
+    fn opaque(&mut self, data: Vec<u8>) -> Result<()> {
+        let e = self.current()?;
+        match &mut e.content {
+            Content::Empty => e.content = Content::Opaque(data),
+            Content::Opaque(d) => d.extend_from_slice(&data),
+            _ => bail!("mixed content in <{}>", e.tag),
+        }
+        Ok(())
+    }
+
+    /// Hand buffered XML character data to the innermost open element.
+    fn flush_xml_text(&mut self, text: &mut String) -> Result<()> {
+        if text.trim().is_empty() {
+            text.clear();
+            return Ok(());
+        }
+        let e = self.current()?;
+        if OPAQUE_TAGS.contains(&(namespace_name(e.page), e.tag)) {
+            let data = base64::engine::general_purpose::STANDARD.decode(text.trim())?;
+            self.opaque(data)?;
+        } else {
+            self.text(text)?;
+        }
+        text.clear();
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Element> {
+        if !self.open.is_empty() {
+            bail!("truncated document: {} unclosed element(s)", self.open.len());
+        }
+        self.root.ok_or_else(|| anyhow!("document has no root element"))
+    }
+}
+
+fn write_mb_u_int32(out: &mut Vec<u8>, mut v: u32) {
+    let mut bytes = vec![(v & 0x7F) as u8];
+    v >>= 7;
+    while v > 0 {
+        bytes.push((v & 0x7F) as u8 | 0x80);
+        v >>= 7;
+    }
+    bytes.reverse();
+    out.extend_from_slice(&bytes);
+}
+
+pub fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+     .replace('<', "&lt;")
+     .replace('>', "&gt;")
+     .replace('"', "&quot;")
+}
+
+/// Cursor over a WBXML byte stream.
+struct ByteReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        let b = *self.buf.get(self.pos).ok_or_else(|| anyhow!("unexpected end of WBXML"))?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).filter(|e| *e <= self.buf.len())
+            .ok_or_else(|| anyhow!("unexpected end of WBXML"))?;
+        let out = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+
+    /// Multi-byte unsigned integer: 7 bits per byte, high bit set on all but the last.
+    fn mb_u_int32(&mut self) -> Result<u32> {
+        let mut v: u32 = 0;
+        for _ in 0..5 {
+            let b = self.byte()?;
+            v = (v << 7) | u32::from(b & 0x7F);
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(anyhow!("mb_u_int32 longer than 5 bytes"))
+    }
+
+    /// NUL-terminated UTF-8 string.
+    fn c_str(&mut self) -> Result<&'a str> {
+        let rest = &self.buf[self.pos..];
+        let len = rest.iter().position(|b| *b == 0).ok_or_else(|| anyhow!("unterminated inline string"))?;
+        self.pos += len + 1;
+        Ok(std::str::from_utf8(&rest[..len])?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codepages::{AIRSYNC, CALENDAR, COMPOSE_MAIL, FOLDER_HIERARCHY};
+
+    fn leaf(page: u8, tag: &'static str, content: Content) -> Element {
+        Element { page, tag, content }
+    }
+
+    fn text(page: u8, tag: &'static str, text: &str) -> Element {
+        leaf(page, tag, Content::Text(text.to_string()))
+    }
+
+    fn parent(page: u8, tag: &'static str, children: Vec<Element>) -> Element {
+        leaf(page, tag, Content::Children(children))
+    }
+
+    /// The FolderSync request of the MS-ASWBXML examples: WBXML 1.3, unknown
+    /// public id, UTF-8, no string table, then SWITCH_PAGE to FolderHierarchy,
+    /// `<FolderSync><SyncKey>0</SyncKey></FolderSync>`.
+    const FOLDER_SYNC: &[u8] = &[0x03, 0x01, 0x6A, 0x00, 0x00, 0x07, 0x56, 0x52, 0x03, 0x30, 0x00, 0x01, 0x01];
+
+    /// A Sync request whose ApplicationData switches to the Calendar page and
+    /// back to AirSync for the empty GetChanges.
+    const SYNC: &[u8] = &[
+        0x03, 0x01, 0x6A, 0x00,
+        0x45, 0x5C, 0x4F,
+        0x4B, 0x03, b'1', 0x00, 0x01,
+        0x52, 0x03, b'w', b'o', b'r', b'k', 0x00, 0x01,
+        0x56, 0x47,
+        0x4C, 0x03, b'c', b'1', 0x00, 0x01,
+        0x5D, 0x00, 0x04,
+        0x66, 0x03, b'H', b'i', 0x00, 0x01,
+        0x01, 0x01, 0x01,
+        0x00, 0x00, 0x13,
+        0x01, 0x01, 0x01,
+    ];
+
+    fn folder_sync() -> Element {
+        parent(FOLDER_HIERARCHY, "FolderSync", vec![text(FOLDER_HIERARCHY, "SyncKey", "0")])
+    }
+
+    fn sync() -> Element {
+        let add = parent(AIRSYNC, "Add", vec![
+            text(AIRSYNC, "ClientId", "c1"),
+            parent(AIRSYNC, "ApplicationData", vec![text(CALENDAR, "Subject", "Hi")]),
+        ]);
+        parent(AIRSYNC, "Sync", vec![parent(AIRSYNC, "Collections", vec![parent(AIRSYNC, "Collection", vec![
+            text(AIRSYNC, "SyncKey", "1"),
+            text(AIRSYNC, "CollectionId", "work"),
+            parent(AIRSYNC, "Commands", vec![add]),
+            leaf(AIRSYNC, "GetChanges", Content::Empty),
+        ])])])
+    }
+
+    /// SendMail carrying 200 bytes of MIME as OPAQUE, whose length takes two
+    /// mb_u_int32 bytes (0x81 0x48).
+    fn send_mail() -> (Vec<u8>, Element) {
+        let mime: Vec<u8> = (0..200u8).collect();
+        let mut bytes = vec![0x03, 0x01, 0x6A, 0x00, 0x00, 0x15, 0x45, 0x50, 0xC3, 0x81, 0x48];
+        bytes.extend_from_slice(&mime);
+        bytes.extend_from_slice(&[0x01, 0x01]);
+        (bytes, parent(COMPOSE_MAIL, "SendMail", vec![leaf(COMPOSE_MAIL, "Mime", Content::Opaque(mime))]))
+    }
+
+    #[test]
+    fn decodes_example_documents() {
+        let wbxml = Wbxml::new();
+        assert_eq!(wbxml.decode(FOLDER_SYNC).unwrap(), folder_sync());
+        assert_eq!(wbxml.decode(SYNC).unwrap(), sync());
+        let (bytes, send_mail) = send_mail();
+        assert_eq!(wbxml.decode(&bytes).unwrap(), send_mail);
+    }
+
+    #[test]
+    fn decodes_string_table_and_multi_byte_header_fields() {
+        // Public id 0x84 (two bytes), a three-byte string table and STR_T
+        let bytes = [0x03, 0x81, 0x04, 0x6A, 0x03, b'a', b'b', 0x00, 0x00, 0x07, 0x56, 0x52, 0x83, 0x00, 0x01, 0x01];
+        let root = Wbxml::new().decode(&bytes).unwrap();
+        assert_eq!(root, parent(FOLDER_HIERARCHY, "FolderSync", vec![text(FOLDER_HIERARCHY, "SyncKey", "ab")]));
+
+        let mut r = ByteReader::new(&[0x7F, 0x81, 0x00, 0x81, 0x80, 0x00, 0x8F, 0xFF, 0xFF, 0xFF, 0x7F]);
+        assert_eq!(r.mb_u_int32().unwrap(), 127);
+        assert_eq!(r.mb_u_int32().unwrap(), 128);
+        assert_eq!(r.mb_u_int32().unwrap(), 16384);
+        assert_eq!(r.mb_u_int32().unwrap(), u32::MAX);
+        assert!(ByteReader::new(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x00]).mb_u_int32().is_err());
+    }
+
+    #[test]
+    fn encodes_example_documents() {
+        let wbxml = Wbxml::new();
+        assert_eq!(wbxml.encode(&folder_sync()).unwrap(), FOLDER_SYNC);
+        assert_eq!(wbxml.encode(&sync()).unwrap(), SYNC);
+        let (bytes, send_mail) = send_mail();
+        assert_eq!(wbxml.encode(&send_mail).unwrap(), bytes);
+    }
+
+    #[test]
+    fn encode_round_trip() {
+        let wbxml = Wbxml::new();
+        let (_, send_mail) = send_mail();
+        let childless = parent(CALENDAR, "Subject", vec![]);
+        let unicode = text(CALENDAR, "Location", "Café <Zürich> & \"Ω\"");
+        for doc in [folder_sync(), sync(), send_mail, unicode] {
+            assert_eq!(wbxml.decode(&wbxml.encode(&doc).unwrap()).unwrap(), doc);
+        }
+        // A childless element is written without content
+        assert_eq!(wbxml.decode(&wbxml.encode(&childless).unwrap()).unwrap(), leaf(CALENDAR, "Subject", Content::Empty));
+    }
+
+    #[test]
+    fn writes_mb_u_int32_boundaries() {
+        for (value, expected) in [
+            (0, &[0x00][..]),
+            (127, &[0x7F]),
+            (128, &[0x81, 0x00]),
+            (16383, &[0xFF, 0x7F]),
+            (16384, &[0x81, 0x80, 0x00]),
+            (u32::MAX, &[0x8F, 0xFF, 0xFF, 0xFF, 0x7F]),
+        ] {
+            let mut out = Vec::new();
+            write_mb_u_int32(&mut out, value);
+            assert_eq!(out, expected, "{}", value);
+            assert_eq!(ByteReader::new(&out).mb_u_int32().unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        let wbxml = Wbxml::new();
+        let (send_mail, _) = send_mail();
+        for doc in [FOLDER_SYNC, SYNC, &send_mail] {
+            for len in 0..doc.len() {
+                assert!(wbxml.decode(&doc[..len]).is_err(), "prefix of {} bytes decoded", len);
+            }
+        }
+        // Continuation bit on the last byte of an mb_u_int32
+        assert!(wbxml.decode(&[0x03, 0x01, 0x6A, 0x80]).is_err());
+        // STR_I without its terminating NUL
+        assert!(wbxml.decode(&[0x03, 0x01, 0x6A, 0x00, 0x00, 0x07, 0x56, 0x52, 0x03, 0x30, 0x01, 0x01]).is_err());
+        // OPAQUE longer than the document
+        assert!(wbxml.decode(&[0x03, 0x01, 0x6A, 0x00, 0x00, 0x15, 0x45, 0x50, 0xC3, 0x05, 0x01, 0x01]).is_err());
     }
 }
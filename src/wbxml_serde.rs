@@ -0,0 +1,525 @@
+//! serde bridge between typed ActiveSync models and the WBXML element tree.
+//!
+//! Element names come from the serde names of structs and fields. A name may be
+//! qualified with its code page namespace (`AirSyncBase:Body`); unqualified names
+//! stay on the code page of the enclosing element, so only the root struct and
+//! fields that switch pages need a prefix.
+//!
+//! - structs map to elements with children, in field order
+//! - `Option` fields are omitted when `None`; `Option<()>` models flag elements
+//!   such as `<MoreAvailable/>`
+//! - `Vec` fields map to repeated sibling elements (use `#[serde(default)]`)
+//! - numbers and strings map to text, bytes (`Opaque`) to OPAQUE data
+//! - booleans map to `1`/`0`; an empty element such as `<GetChanges/>` reads as true
+//! - unit enum variants map to their (renamed) variant name as text
+//! - a field renamed `$text` reads the element's own text, for elements that
+//!   hold either text or children (Search `Query`); requests only
+
+use serde::de::{self, Deserialize, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::ser::{self, Impossible, Serialize};
+use std::fmt::Display;
+use crate::wbxml::{code_page_for_namespace, Content, Element};
+
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct Error(String);
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+/// Binary element content, written as WBXML OPAQUE data rather than text.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Opaque(pub Vec<u8>);
+
+impl Serialize for Opaque {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Opaque {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct OpaqueVisitor;
+
+        impl<'de> Visitor<'de> for OpaqueVisitor {
+            type Value = Opaque;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("opaque data")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Opaque, E> {
+                Ok(Opaque(v.to_vec()))
+            }
+        }
+
+        deserializer.deserialize_bytes(OpaqueVisitor)
+    }
+}
+
+/// Serialize a model into an element tree. The root struct's name must be qualified.
+pub fn to_element<T: Serialize>(value: &T) -> anyhow::Result<Element> {
+    let mut elems = value.serialize(ElementSerializer { name: None })?;
+    if elems.len() != 1 {
+        anyhow::bail!("model must serialize to exactly one root element");
+    }
+    Ok(elems.remove(0))
+}
+
+/// Deserialize a model from an element tree, checking the root element's name.
+pub fn from_element<T: DeserializeOwned>(root: &Element) -> anyhow::Result<T> {
+    Ok(T::deserialize(ElementDeserializer { elems: vec![root], root: true })?)
+}
+
+/// Resolve a serde name to (code page, tag), inheriting `parent_page` when unqualified.
+fn resolve(name: &'static str, parent_page: Option<u8>) -> Result<(u8, &'static str), Error> {
+    match name.split_once(':') {
+        Some((ns, tag)) => {
+            let page = code_page_for_namespace(ns).ok_or_else(|| Error(format!("unknown namespace {}", ns)))?;
+            Ok((page, tag))
+        }
+        None => parent_page.map(|p| (p, name)).ok_or_else(|| Error(format!("root element {} needs a namespace", name))),
+    }
+}
+
+/// Serializes one value into the elements named `name` (none for `None`, several for sequences).
+struct ElementSerializer {
+    name: Option<(u8, &'static str)>,
+}
+
+impl ElementSerializer {
+    fn leaf(self, content: Content) -> Result<Vec<Element>, Error> {
+        let (page, tag) = self.name.ok_or_else(|| Error("top-level model must be a struct".into()))?;
+        Ok(vec![Element { page, tag, content }])
+    }
+
+    fn text(self, v: impl ToString) -> Result<Vec<Element>, Error> {
+        self.leaf(Content::Text(v.to_string()))
+    }
+}
+
+impl ser::Serializer for ElementSerializer {
+    type Ok = Vec<Element>;
+    type Error = Error;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = Impossible<Vec<Element>, Error>;
+    type SerializeTupleStruct = Impossible<Vec<Element>, Error>;
+    type SerializeTupleVariant = Impossible<Vec<Element>, Error>;
+    type SerializeMap = Impossible<Vec<Element>, Error>;
+    type SerializeStruct = StructSerializer;
+    type SerializeStructVariant = Impossible<Vec<Element>, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Error> { self.text(if v { "1" } else { "0" }) }
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Error> { self.text(v) }
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Error> { self.text(v) }
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Error> { self.text(v) }
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Error> { self.text(v) }
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Error> { self.text(v) }
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Error> { self.text(v) }
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Error> { self.text(v) }
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Error> { self.text(v) }
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Error> { self.text(v) }
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Error> { self.text(v) }
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Error> { self.text(v) }
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Error> { self.text(v) }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Error> { self.leaf(Content::Opaque(v.to_vec())) }
+    fn serialize_none(self) -> Result<Self::Ok, Error> { Ok(Vec::new()) }
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Self::Ok, Error> { value.serialize(self) }
+    fn serialize_unit(self) -> Result<Self::Ok, Error> { self.leaf(Content::Empty) }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Error> { self.leaf(Content::Empty) }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<Self::Ok, Error> {
+        self.text(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _name: &'static str, value: &T) -> Result<Self::Ok, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, name: &'static str, _index: u32, _variant: &'static str, _value: &T) -> Result<Self::Ok, Error> {
+        Err(Error(format!("enum {} with data is not supported", name)))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Ok(SeqSerializer { name: self.name, out: Vec::new() })
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(Error("tuples are not supported".into()))
+    }
+
+    fn serialize_tuple_struct(self, name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(Error(format!("tuple struct {} is not supported", name)))
+    }
+
+    fn serialize_tuple_variant(self, name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Error(format!("enum {} with data is not supported", name)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(Error("maps are not supported".into()))
+    }
+
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Self::SerializeStruct, Error> {
+        let (page, tag) = match self.name {
+            Some(n) => n,
+            None => resolve(name, None)?,
+        };
+        Ok(StructSerializer { elem: Element::new(page, tag) })
+    }
+
+    fn serialize_struct_variant(self, name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeStructVariant, Error> {
+        Err(Error(format!("enum {} with data is not supported", name)))
+    }
+}
+
+struct SeqSerializer {
+    name: Option<(u8, &'static str)>,
+    out: Vec<Element>,
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Vec<Element>;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.out.extend(value.serialize(ElementSerializer { name: self.name })?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(self.out)
+    }
+}
+
+struct StructSerializer {
+    elem: Element,
+}
+
+impl ser::SerializeStruct for StructSerializer {
+    type Ok = Vec<Element>;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        let name = resolve(key, Some(self.elem.page))?;
+        for child in value.serialize(ElementSerializer { name: Some(name) })? {
+            self.elem.push(child);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(vec![self.elem])
+    }
+}
+
+/// Deserializes a value from a group of same-named sibling elements.
+/// Sequences consume the whole group; everything else reads the first element.
+struct ElementDeserializer<'de> {
+    elems: Vec<&'de Element>,
+    root: bool,
+}
+
+impl<'de> ElementDeserializer<'de> {
+    fn first(&self) -> &'de Element {
+        self.elems[0]
+    }
+
+    fn text(&self) -> Result<&'de str, Error> {
+        let e = self.first();
+        e.text().ok_or_else(|| Error(format!("expected text in <{}>", e.tag)))
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident),* $(,)?) => { $(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            let t = self.text()?;
+            let v = t.trim().parse().map_err(|_| Error(format!("invalid value '{}' in <{}>", t, self.first().tag)))?;
+            visitor.$visit(v)
+        }
+    )* };
+}
+
+impl<'de> de::Deserializer<'de> for ElementDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let e = self.first();
+        match &e.content {
+            Content::Empty => visitor.visit_unit(),
+            Content::Text(t) => visitor.visit_borrowed_str(t),
+            Content::Opaque(d) => visitor.visit_borrowed_bytes(d),
+            Content::Children(_) => Err(Error(format!("<{}> has children but no struct was expected", e.tag))),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.text()?.trim() {
+            "1" | "true" | "" => visitor.visit_bool(true),
+            "0" | "false" => visitor.visit_bool(false),
+            t => Err(Error(format!("invalid boolean '{}' in <{}>", t, self.first().tag))),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 => visit_i8, deserialize_i16 => visit_i16, deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64, deserialize_u8 => visit_u8, deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32, deserialize_u64 => visit_u64, deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64, deserialize_char => visit_char,
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_str(self.text()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let e = self.first();
+        match &e.content {
+            Content::Opaque(d) => visitor.visit_borrowed_bytes(d),
+            Content::Text(t) => visitor.visit_borrowed_bytes(t.as_bytes()),
+            Content::Empty => visitor.visit_borrowed_bytes(&[]),
+            Content::Children(_) => Err(Error(format!("expected data in <{}>", e.tag))),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        // Missing elements never reach the deserializer; serde fills in None.
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let items = self.elems.into_iter().map(|e| ElementDeserializer { elems: vec![e], root: false });
+        visitor.visit_seq(de::value::SeqDeserializer::new(items))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, _visitor: V) -> Result<V::Value, Error> {
+        Err(Error("tuples are not supported".into()))
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, name: &'static str, _len: usize, _visitor: V) -> Result<V::Value, Error> {
+        Err(Error(format!("tuple struct {} is not supported", name)))
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(Error("maps are not supported".into()))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        let elem = self.first();
+        if self.root {
+            let (page, tag) = resolve(name, None)?;
+            if elem.page != page || elem.tag != tag {
+                return Err(Error(format!("expected <{}>, found <{}>", name, elem.tag)));
+            }
+        }
+        visitor.visit_map(StructAccess { elem, fields: fields.iter(), pending: Vec::new() })
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_enum(self.text()?.trim().into_deserializer())
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for ElementDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Field name that reads the text of the struct's own element.
+const TEXT_FIELD: &str = "$text";
+
+/// Walks a struct's fields, yielding those present as children of `elem`.
+struct StructAccess<'de> {
+    elem: &'de Element,
+    fields: std::slice::Iter<'static, &'static str>,
+    pending: Vec<&'de Element>,
+}
+
+impl<'de> de::MapAccess<'de> for StructAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        for field in self.fields.by_ref() {
+            if *field == TEXT_FIELD {
+                if matches!(self.elem.content, Content::Text(_)) {
+                    self.pending = vec![self.elem];
+                    return seed.deserialize(field.into_deserializer()).map(Some);
+                }
+                continue;
+            }
+            let (page, tag) = resolve(field, Some(self.elem.page))?;
+            let matches: Vec<&Element> = self.elem.children().iter().filter(|c| c.page == page && c.tag == tag).collect();
+            if !matches.is_empty() {
+                self.pending = matches;
+                return seed.deserialize(field.into_deserializer()).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let elems = std::mem::take(&mut self.pending);
+        seed.deserialize(ElementDeserializer { elems, root: false })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codepages::{AIRSYNC, AIRSYNCBASE, CALENDAR, FOLDER_HIERARCHY, PING};
+    use crate::eas_models::*;
+    use crate::wbxml::Wbxml;
+    use std::fmt::Debug;
+
+    /// Take a model through its element tree and WBXML bytes and back, and
+    /// return the decoded tree.
+    fn round_trip<T: Serialize + DeserializeOwned + Debug>(model: &T) -> Element {
+        let wbxml = Wbxml::new();
+        let root = to_element(model).unwrap();
+        let decoded = wbxml.decode(&wbxml.encode(&root).unwrap()).unwrap();
+        assert_eq!(decoded, root);
+        let back: T = from_element(&decoded).unwrap();
+        assert_eq!(format!("{:?}", back), format!("{:?}", model));
+        decoded
+    }
+
+    fn child<'a>(elem: &'a Element, page: u8, tag: &str) -> &'a Element {
+        elem.children().iter().find(|c| c.page == page && c.tag == tag).unwrap_or_else(|| panic!("no {} in {}", tag, elem.tag))
+    }
+
+    #[test]
+    fn folder_sync_round_trip() {
+        let root = round_trip(&FolderSyncRequest { sync_key: "0".to_string() });
+        assert_eq!((root.page, root.tag), (FOLDER_HIERARCHY, "FolderSync"));
+        assert_eq!(child(&root, FOLDER_HIERARCHY, "SyncKey").content, Content::Text("0".to_string()));
+
+        let folder = |id: &str, name: &str| Folder { server_id: id.to_string(), parent_id: "0".to_string(), display_name: name.to_string(), r#type: 8 };
+        let root = round_trip(&FolderSyncResponse {
+            status: 1,
+            sync_key: Some("2".to_string()),
+            changes: Some(FolderChanges {
+                count: 3,
+                update: vec![folder("a", "Work")],
+                delete: vec![FolderDelete { server_id: "b".to_string() }],
+                add: vec![folder("c", "Home"), folder("d", "Team")],
+            }),
+        });
+        let changes = child(&root, FOLDER_HIERARCHY, "Changes");
+        assert_eq!(changes.children().iter().map(|c| c.tag).collect::<Vec<_>>(), ["Count", "Update", "Delete", "Add", "Add"]);
+    }
+
+    #[test]
+    fn sync_round_trip() {
+        let data = ApplicationData {
+            timezone: Some("xP///w==".to_string()),
+            all_day_event: Some(false),
+            body: Some(Body { r#type: 1, estimated_data_size: Some(5), truncated: Some(false), data: Some("Notes".to_string()), preview: None }),
+            subject: Some("Review & plan".to_string()),
+            start_time: Some("20250301T090000Z".to_string()),
+            end_time: Some("20250301T100000Z".to_string()),
+            attendees: Some(Attendees { attendee: vec![Attendee { email: "ann@example.com".to_string(), name: Some("Ann".to_string()), attendee_status: Some(3), attendee_type: Some(1) }] }),
+            categories: Some(Categories { category: vec!["Work".to_string(), "Plans".to_string()] }),
+            recurrence: Some(Recurrence { r#type: 1, interval: Some(2), day_of_week: Some(0b0100010), ..Default::default() }),
+            ..Default::default()
+        };
+        let root = round_trip(&SyncRequest {
+            collections: Some(SyncRequestCollections { collection: vec![SyncRequestCollection {
+                sync_key: "1".to_string(),
+                collection_id: "work".to_string(),
+                get_changes: Some(true),
+                options: vec![SyncOptions { filter_type: Some(5), body_preference: vec![BodyPreference { r#type: 1, truncation_size: Some(2048), ..Default::default() }], ..Default::default() }],
+                commands: Some(ClientCommands { add: vec![ClientAdd { class: None, client_id: "c1".to_string(), application_data: data.clone() }], ..Default::default() }),
+                ..Default::default()
+            }] }),
+            window_size: Some(50),
+            ..Default::default()
+        });
+        let collection = child(child(&root, AIRSYNC, "Collections"), AIRSYNC, "Collection");
+        let add = child(child(collection, AIRSYNC, "Commands"), AIRSYNC, "Add");
+        let app_data = child(add, AIRSYNC, "ApplicationData");
+        assert_eq!(child(app_data, CALENDAR, "Subject").content, Content::Text("Review & plan".to_string()));
+        assert_eq!(child(child(app_data, AIRSYNCBASE, "Body"), AIRSYNCBASE, "Data").content, Content::Text("Notes".to_string()));
+
+        let root = round_trip(&SyncResponse {
+            status: None,
+            collections: Some(SyncResponseCollections { collection: vec![SyncResponseCollection {
+                class: Some("Calendar".to_string()),
+                sync_key: "2".to_string(),
+                collection_id: "work".to_string(),
+                status: 1,
+                responses: Some(SyncResponses { add: vec![AddResult { class: None, client_id: "c1".to_string(), server_id: Some("s1".to_string()), status: 1 }], ..Default::default() }),
+                more_available: Some(()),
+                commands: Some(ServerCommands {
+                    change: vec![ServerItem { server_id: "s2".to_string(), application_data: data }],
+                    delete: vec![ItemRef { server_id: "s3".to_string(), instance_id: None }],
+                    ..Default::default()
+                }),
+            }] }),
+        });
+        let collection = child(child(&root, AIRSYNC, "Collections"), AIRSYNC, "Collection");
+        assert_eq!(child(collection, AIRSYNC, "MoreAvailable").content, Content::Empty);
+    }
+
+    #[test]
+    fn ping_round_trip() {
+        let root = round_trip(&PingRequest {
+            heartbeat_interval: Some(480),
+            folders: Some(PingFolders { folder: vec![
+                PingFolder { id: "work".to_string(), class: "Calendar".to_string() },
+                PingFolder { id: "home".to_string(), class: "Calendar".to_string() },
+            ] }),
+        });
+        assert_eq!((root.page, root.tag), (PING, "Ping"));
+        assert_eq!(child(&root, PING, "Folders").children().len(), 2);
+
+        let root = round_trip(&PingResponse {
+            status: 2,
+            folders: Some(PingChangedFolders { folder: vec!["work".to_string(), "home".to_string()] }),
+            max_folders: None,
+            heartbeat_interval: None,
+        });
+        let folders = child(&root, PING, "Folders");
+        assert_eq!(folders.children().iter().map(|f| f.content.clone()).collect::<Vec<_>>(), [Content::Text("work".to_string()), Content::Text("home".to_string())]);
+    }
+}